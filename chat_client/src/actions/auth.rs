@@ -0,0 +1,55 @@
+use anyhow::{Context, bail};
+use chat_lib::{auth::Credentials, consts::MIN_PASSWORD_LENGTH};
+
+use crate::{
+    config::{AppConfig, SavedSession},
+    consts::CLIENT,
+    requests::authenticate,
+};
+
+/// Registers or logs in to the account named in the config and saves the session
+pub async fn auth_action(config: AppConfig, register: bool) -> anyhow::Result<()> {
+    let url = config.web.url;
+    let name = config
+        .web
+        .defult_name
+        .context("Set the account name with --name")?;
+
+    let password = rpassword::prompt_password("Password: ")?;
+    if register {
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            bail!("The password has to be at least {MIN_PASSWORD_LENGTH} characters");
+        }
+        if rpassword::prompt_password("Repeat password: ")? != password {
+            bail!("The passwords don't match");
+        }
+    }
+
+    let session = authenticate(&CLIENT, &url, &Credentials { name, password }, register).await?;
+    let name = session.user.get_name().to_string();
+
+    SavedSession {
+        url,
+        name: name.clone(),
+        token: session.token,
+    }
+    .save()?;
+
+    if register {
+        println!("Registered and logged in as {name}");
+    } else {
+        println!("Logged in as {name}");
+    }
+
+    Ok(())
+}
+
+pub fn logout_action() -> anyhow::Result<()> {
+    if SavedSession::remove()? {
+        println!("Logged out");
+    } else {
+        println!("There was no saved session");
+    }
+
+    Ok(())
+}
@@ -1,4 +1,3 @@
-use chat_lib::Version;
 use tokio::time::timeout;
 
 use crate::{
@@ -11,7 +10,6 @@ pub async fn echo_action(config: AppConfig, args: EchoArgs) -> anyhow::Result<()
     let (mut room, ws) = connect_room(
         &config,
         &config.web.url,
-        Version::V1,
         &config.web.default_room,
         config.web.defult_name.clone(),
     )
@@ -1,6 +1,10 @@
+use chat_lib::RoomInfo;
+use chrono::Local;
+
 use crate::{
     config::{AppConfig, LsArgs},
     consts::CLIENT,
+    helper::negotiate_version,
     requests::{room_discovery, room_ls},
 };
 
@@ -12,19 +16,53 @@ pub async fn ls_action(config: AppConfig, args: LsArgs) -> anyhow::Result<()> {
 
     println!("Server version = {}", discovery.server_version);
     println!("Available rooms = {:?}", discovery.available_rooms);
+    for info in &discovery.rooms {
+        print_info(info);
+    }
     println!(
         "Supported api versions = {:?}",
         discovery.supported_api_versions
     );
 
     if args.users {
-        let users = room_ls(&CLIENT, &base_url, &room_name).await?;
-        let user_names = users
+        let version = negotiate_version(&discovery)?;
+        let listing = room_ls(&CLIENT, &base_url, version, &room_name).await?;
+        if !discovery.rooms.iter().any(|info| info.name == room_name) {
+            print_info(&listing.info);
+        }
+        let user_names = listing
+            .users
             .into_iter()
-            .map(|u| u.get_name().to_string())
+            .map(|u| format!("{} ({})", u.get_name(), u.get_presence()))
             .collect::<Vec<_>>();
         println!("Users in room {room_name} = {user_names:?}");
     }
 
     Ok(())
 }
+
+/// Prints what's known about the room, indented under its name
+fn print_info(info: &RoomInfo) {
+    if info.persistent {
+        println!("{} (persistent):", info.name);
+    } else {
+        println!("{}:", info.name);
+    }
+    if let Some(topic) = &info.topic {
+        println!("  Topic = {topic}");
+    }
+    if let Some(description) = &info.description {
+        println!("  Description = {description}");
+    }
+    if !info.access.is_open() {
+        println!("  Access = {}", info.access);
+    }
+    if let Some(created_at) = info.created_at {
+        let created_at = created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        let creator = info
+            .creator
+            .as_ref()
+            .map_or("unknown", |creator| creator.get_name());
+        println!("  Created at {created_at} by {creator}");
+    }
+}
@@ -4,11 +4,16 @@
     reason = "The actions are not run in a tui, so they need to be able to output stuff to stdout and stderr"
 )]
 
+mod auth;
 mod echo;
 mod ls;
 
 use crate::{
-    actions::{echo::echo_action, ls::ls_action},
+    actions::{
+        auth::{auth_action, logout_action},
+        echo::echo_action,
+        ls::ls_action,
+    },
     config::{ActionType, AppConfig},
 };
 
@@ -21,6 +26,9 @@ pub async fn actions(config: AppConfig, action: ActionType) -> anyhow::Result<()
     match action {
         ActionType::Ls(args) => ls_action(config, args).await?,
         ActionType::Echo(args) => echo_action(config, args).await?,
+        ActionType::Register => auth_action(config, true).await?,
+        ActionType::Login => auth_action(config, false).await?,
+        ActionType::Logout => logout_action()?,
     }
     Ok(())
 }
@@ -59,7 +59,7 @@ impl App {
     pub fn prepare_app(&mut self) {
         let default_room = self.context.config.web.default_room.clone();
         let default_url = self.context.config.web.url.clone();
-        self.context.join_room(default_url, &default_room);
+        self.context.join_room(default_url, &default_room, None);
     }
 
     pub fn render(&self, f: &mut Frame<'_>) {
@@ -100,6 +100,9 @@ impl App {
     ///
     /// This function panics if there isn't a screen on the screen stack
     pub fn handle_input(&mut self, event: &Event) {
+        if matches!(event, Event::Key(_) | Event::Mouse(_) | Event::Paste(_)) {
+            self.context.input_received();
+        }
         let Self {
             context,
             screen_stack,
@@ -162,8 +165,8 @@ impl App {
             AppAction::PopScreen => self.pop_screen(),
             AppAction::PushComponent(component) => self.push_component(component),
             AppAction::PopComponent => self.pop_component(),
-            AppAction::JoinRoom(url, name) => {
-                self.context.join_room(url, &name);
+            AppAction::JoinRoom(url, name, credentials) => {
+                self.context.join_room(url, &name, credentials);
             }
             AppAction::Quit => self.exit_reason = Some(ExitReason::default()),
         }
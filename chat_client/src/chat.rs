@@ -1,5 +1,6 @@
 use std::num::NonZero;
 
+use chat_lib::types::Reaction;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction::Horizontal, Layout, Rect},
@@ -7,10 +8,12 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Borders},
 };
+use uuid::Uuid;
 
 use crate::{
     components::AppContext,
     event::{EventType, RoomEvent, UserLocator},
+    room::Room,
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -26,25 +29,22 @@ pub fn top_block<'a>() -> Block<'a> {
     Block::new().borders(Borders::BOTTOM)
 }
 
-pub fn draw_top_bar(f: &'_ mut Frame, area: Rect, name: &str, ctx: &AppContext) {
+pub fn draw_top_bar(
+    f: &'_ mut Frame,
+    area: Rect,
+    name: &str,
+    topic: Option<&str>,
+    ctx: &AppContext,
+) {
     let block = Block::new().borders(Borders::BOTTOM);
     f.render_widget(&block, area);
     let area = block.inner(area);
 
     let name_len = name.chars().count();
     let name = name.to_string();
-
-    let chunks = Layout::new(
-        Horizontal,
-        #[allow(clippy::cast_possible_truncation)]
-        [Constraint::Length(name_len as u16), Constraint::Fill(1)],
-    )
-    .split(area);
-
     let msg = "Press Ctrl+h to display help menu";
 
     let notif_count = ctx.notifications().len();
-    let left = Line::from(name);
     let right = Line::from_iter([
         Span::from(notif_count.to_string()).red(),
         Span::from(" "),
@@ -52,23 +52,36 @@ pub fn draw_top_bar(f: &'_ mut Frame, area: Rect, name: &str, ctx: &AppContext)
     ])
     .right_aligned();
 
+    let chunks = Layout::new(
+        Horizontal,
+        #[allow(clippy::cast_possible_truncation)]
+        [
+            Constraint::Length(name_len as u16),
+            Constraint::Fill(1),
+            Constraint::Length(right.width() as u16),
+        ],
+    )
+    .spacing(1)
+    .split(area);
+
+    let left = Line::from(name);
+    let topic = Line::from(topic.unwrap_or_default().to_string())
+        .dark_gray()
+        .centered();
+
     f.render_widget(left, chunks[0]);
-    f.render_widget(right, chunks[1]);
+    f.render_widget(topic, chunks[1]);
+    f.render_widget(right, chunks[2]);
 }
 
-pub fn draw_room_events(
-    f: &'_ mut Frame,
-    area: Rect,
-    chats: &[RoomEvent],
-    users: &impl UserLocator,
-    offset: Option<Offset>,
-) {
+pub fn draw_room_events(f: &'_ mut Frame, area: Rect, room: &Room) {
+    let chats = room.events();
     let chats = chats.iter();
     let height = area.height as usize;
-    match offset {
+    match room.scroll_offset() {
         None => {
             let chats = chats.rev().take(height).rev().collect::<Vec<_>>();
-            draw_lines(f, area, &chats, users, true);
+            draw_lines(f, area, &chats, room, true);
         }
         Some(Offset::Relative(offset)) => {
             let offset = (offset.get() as usize).min(chats.len().saturating_sub(height));
@@ -79,24 +92,69 @@ pub fn draw_room_events(
                 .take(height)
                 .rev()
                 .collect::<Vec<_>>();
-            draw_lines(f, area, &chats, users, true);
+            draw_lines(f, area, &chats, room, true);
         }
         Some(Offset::Absolute(offset)) => {
             let offset = (offset as usize).saturating_sub(height);
 
             let chats = chats.skip(offset).take(height).collect::<Vec<_>>();
-            draw_lines(f, area, &chats, users, false);
+            draw_lines(f, area, &chats, room, false);
         }
     }
 }
 
+/// The quoted preview of the replied to message shown above a reply
+fn reply_line(
+    parent: Option<&RoomEvent>,
+    room: &Room,
+    indent: usize,
+    width: usize,
+) -> Line<'static> {
+    let author_name = |id: &Uuid| {
+        room.users()
+            .get_user(*id)
+            .map_or(id.to_string(), |u| u.get_name().to_owned())
+    };
+    let preview = match parent {
+        Some(RoomEvent::Message(msg)) => {
+            format!("╭ {}: {}", author_name(msg.get_author()), msg.get_content())
+        }
+        Some(RoomEvent::Deleted { author, .. }) => {
+            format!("╭ {}: message deleted", author_name(author))
+        }
+        _ => "╭ an older message".to_string(),
+    };
+    let preview = preview
+        .chars()
+        .take(width.saturating_sub(indent))
+        .collect::<String>();
+
+    Line::from_iter([
+        Span::from(" ".repeat(indent)),
+        Span::from(preview).dark_gray().italic(),
+    ])
+}
+
+/// The reaction counts shown under a message, aligned with the message
+fn reaction_line(reactions: &[Reaction], indent: usize) -> Line<'_> {
+    let mut spans = vec![Span::from(" ".repeat(indent))];
+    for reaction in reactions {
+        spans.push(
+            Span::from(format!("{} {}", reaction.get_reaction(), reaction.count())).dark_gray(),
+        );
+        spans.push(Span::from("  "));
+    }
+
+    Line::from(spans)
+}
+
 /// processes the events into lines and draws them to the passed in `Frame`,
 /// the events may or may not fit onto the screen
 fn draw_lines(
     f: &'_ mut Frame,
     area: Rect,
     events: &[&RoomEvent],
-    users: &impl UserLocator,
+    room: &Room,
     prioritize_last: bool,
 ) {
     if area.width == 0 || area.height == 0 {
@@ -107,9 +165,18 @@ fn draw_lines(
     let area_width = area.width as usize;
     let area_height = area.height as usize;
 
+    let users = room.users();
+    let selected = room.selected();
     let event_props = events
         .iter()
-        .map(|ev| ev.properties(users))
+        .map(|ev| {
+            let props = ev.properties(users);
+            if selected.is_some() && ev.message_id() == selected {
+                props.highlighted()
+            } else {
+                props
+            }
+        })
         .collect::<Vec<_>>();
 
     let max_user_width = 1 + event_props
@@ -128,7 +195,12 @@ fn draw_lines(
         }
     }
 
-    for props in &event_props {
+    for (ev, props) in events.iter().zip(&event_props) {
+        if let Some(parent) = ev.reply_to() {
+            let parent = room.find_message_event(*parent);
+            rows.push(reply_line(parent, room, max_user_width, area_width));
+        }
+
         match props {
             EventType::Info { message, style } => {
                 let message_characters = message.chars().collect::<Vec<_>>();
@@ -142,6 +214,10 @@ fn draw_lines(
                 rows.extend(event_rows);
             }
         }
+
+        if let Some(reactions) = ev.message_id().and_then(|id| room.reactions().get(id)) {
+            rows.push(reaction_line(reactions, max_user_width));
+        }
     }
 
     if prioritize_last {
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, time::Instant};
 
-use chat_lib::{Discovery, Version};
+use chat_lib::{Discovery, Protocol};
 use tokio::sync::{
     broadcast,
     mpsc::{Receiver, Sender, channel},
@@ -8,9 +8,9 @@ use tokio::sync::{
 use url::Url;
 
 use crate::{
-    config::AppConfig,
+    config::{AppConfig, RoomCredentials},
     consts::{CHANNEL_BUFFER_SIZE, NOTIFICATION_LIFETIME},
-    helper::{FetchState, RoomLocation, connect_room_ws},
+    helper::{FetchState, RoomLocation, connect_room_ws, negotiate_protocol},
     notif_error, notif_info,
     notifications::{self, Notification},
     room::Room,
@@ -27,9 +27,14 @@ pub struct AppContext {
     /// We need to store this in order to create new senders
     pub task_tx: Sender<AppTaskResult>,
     pub discoveries: HashMap<Url, (FetchState<Discovery, String>, Instant)>,
-    pub join_queue: Vec<RoomLocation>,
+    /// The rooms waiting for their server's discovery, with what joining them needs
+    pub join_queue: Vec<(RoomLocation, RoomCredentials)>,
     pub notif_rx: broadcast::Receiver<Notification>,
     pub notifications: Vec<Notification>,
+    /// When the user last pressed a key or used the mouse
+    pub last_input: Instant,
+    /// Whether the user was marked away for being idle, rather than by choice
+    pub idle_away: bool,
 }
 
 impl AppContext {
@@ -48,6 +53,8 @@ impl AppContext {
             discoveries: HashMap::new(),
             join_queue: Vec::new(),
             notifications: Vec::new(),
+            last_input: Instant::now(),
+            idle_away: false,
         }
     }
 
@@ -56,6 +63,28 @@ impl AppContext {
         self.poll_tasks();
         self.process_join_queue();
         self.update_notifications();
+        self.update_idle();
+    }
+
+    /// Marks the user back online if they were away for being idle
+    pub fn input_received(&mut self) {
+        self.last_input = Instant::now();
+        if std::mem::take(&mut self.idle_away) {
+            self.rooms
+                .values_mut()
+                .for_each(|room| room.set_idle(false));
+        }
+    }
+
+    /// Marks the user away in every room once they've been idle for long enough
+    fn update_idle(&mut self) {
+        let Some(idle_away) = self.config.chat.idle_away() else {
+            return;
+        };
+        if !self.idle_away && self.last_input.elapsed() >= idle_away {
+            self.idle_away = true;
+            self.rooms.values_mut().for_each(|room| room.set_idle(true));
+        }
     }
 
     pub fn quit_current_room(&mut self) {
@@ -83,24 +112,40 @@ impl AppContext {
     }
 
     #[allow(clippy::needless_pass_by_value)]
-    pub fn join_room(&mut self, base: Url, room_name: impl ToString) {
-        self.join_queue.push(RoomLocation {
-            url: base,
-            room_name: room_name.to_string(),
-        });
+    /// Queues joining the room, with the saved credentials if there are no `credentials`
+    pub fn join_room(
+        &mut self,
+        base: Url,
+        room_name: impl ToString,
+        credentials: Option<RoomCredentials>,
+    ) {
+        let room_name = room_name.to_string();
+        let credentials =
+            credentials.unwrap_or_else(|| self.config.web.credentials(&base, &room_name));
+        self.join_queue.push((
+            RoomLocation {
+                url: base,
+                room_name,
+            },
+            credentials,
+        ));
     }
 
-    fn add_room(&mut self, loc: RoomLocation) {
+    fn add_room(&mut self, loc: RoomLocation, protocol: Protocol, credentials: RoomCredentials) {
         if self.rooms.contains_key(&loc) {
             // We're already in a room, this request is outdated
             return;
         }
 
-        let (room, _) = self.new_room(
+        let (mut room, _) = self.new_room(
             &loc.url,
+            protocol,
             &loc.room_name,
             self.config.web.defult_name.clone(),
+            credentials,
         );
+        // joined while idle, it's marked away once it's connected
+        room.set_idle(self.idle_away);
         self.current_room_or(loc.clone().into());
         self.rooms.insert(loc, room);
     }
@@ -114,10 +159,13 @@ impl AppContext {
     fn new_room(
         &self,
         base: &Url,
+        protocol: Protocol,
         room_name: &str,
         name: Option<String>,
+        credentials: RoomCredentials,
     ) -> (Room, tokio::task::JoinHandle<()>) {
-        let (mut room, ws) = connect_room_ws(&self.config, base, Version::V1, room_name, name);
+        let (mut room, ws) =
+            connect_room_ws(&self.config, base, protocol, room_name, name, credentials);
 
         room.action(WsAction::RequestSelf);
         room.action(WsAction::RequestAll);
@@ -164,13 +212,19 @@ impl AppContext {
         let mut new_queue = Vec::with_capacity(self.join_queue.len());
 
         // very sad, but I see no way around cloning it
-        for loc in self.join_queue.clone() {
+        for (loc, credentials) in self.join_queue.clone() {
             if let Some(fetch) = self.discoveries.get(&loc.url) {
                 let (state, _when) = fetch;
                 match state {
-                    FetchState::Pending => new_queue.push(loc),
-                    FetchState::Value(_) => {
-                        self.add_room(loc);
+                    FetchState::Pending => new_queue.push((loc, credentials)),
+                    FetchState::Value(discovery) => {
+                        match negotiate_protocol(discovery, self.config.web.encoding) {
+                            Ok(protocol) => self.add_room(loc, protocol, credentials),
+                            Err(err) => {
+                                notif_error!("Can't join {}: {err}", loc.url);
+                                log::error!("Version negotiation with {} failed: {err}", loc.url);
+                            }
+                        }
                     }
                     FetchState::Error(err) => {
                         notif_error!("{} is not a valid server", loc.url);
@@ -179,7 +233,7 @@ impl AppContext {
                 }
             } else {
                 self.discover(loc.url.clone());
-                new_queue.push(loc);
+                new_queue.push((loc, credentials));
             }
         }
 
@@ -3,9 +3,11 @@ mod log_view;
 mod notification_view;
 mod popup;
 mod popup_options;
+mod reaction_modal;
 mod room_join;
 mod room_switch;
 mod root;
+mod sanction_modal;
 mod screen;
 mod text_popup;
 mod user_view;
@@ -16,12 +18,16 @@ use crossterm::event::Event;
 use ratatui::{Frame, layout::Rect};
 use url::Url;
 
+use crate::config::RoomCredentials;
+
 pub use context::AppContext;
 pub use log_view::LogView;
 pub use notification_view::NotificationView;
+pub use reaction_modal::ReactionModal;
 pub use room_join::RoomJoinModal;
 pub use room_switch::RoomSwitchModal;
 pub use root::Root;
+pub use sanction_modal::{SanctionModal, TimedSanction};
 pub use screen::Screen;
 
 pub use popup::*;
@@ -65,7 +71,7 @@ impl EventResult {
     #[allow(clippy::needless_pass_by_value)]
     #[must_use]
     pub fn join_room(url: impl Into<Url>, name: impl ToString) -> Self {
-        Self::batch([AppAction::join_room(url, name)])
+        Self::batch([AppAction::join_room(url, name, None)])
     }
 
     #[must_use]
@@ -116,8 +122,9 @@ pub enum AppAction {
     /// Removes the last component from the stack,
     /// quits the screen if the current component was the last one
     PopComponent,
-    /// Tries to join the room with the given name
-    JoinRoom(Url, String),
+    /// Tries to join the room with the given name,
+    /// with the saved credentials if none are given
+    JoinRoom(Url, String, Option<RoomCredentials>),
     Quit,
 }
 
@@ -128,7 +135,8 @@ impl Debug for AppAction {
             Self::PopScreen => write!(f, "PopScreen"),
             Self::PushComponent(_) => f.debug_tuple("<PushComponent>").finish(),
             Self::PopComponent => write!(f, "PopComponent"),
-            Self::JoinRoom(arg0, arg1) => {
+            // the credentials aren't shown, they're secret
+            Self::JoinRoom(arg0, arg1, _) => {
                 f.debug_tuple("JoinRoom").field(arg0).field(arg1).finish()
             }
             Self::Quit => write!(f, "Quit"),
@@ -143,8 +151,12 @@ impl AppAction {
 
     #[allow(clippy::needless_pass_by_value)]
     #[must_use]
-    pub fn join_room(url: impl Into<Url>, name: impl ToString) -> Self {
-        AppAction::JoinRoom(url.into(), name.to_string())
+    pub fn join_room(
+        url: impl Into<Url>,
+        name: impl ToString,
+        credentials: Option<RoomCredentials>,
+    ) -> Self {
+        AppAction::JoinRoom(url.into(), name.to_string(), credentials)
     }
 
     #[must_use]
@@ -0,0 +1,59 @@
+use crossterm::event::Event;
+use ratatui::{Frame, layout::Rect, style::Style, widgets::Block};
+use ratatui_textarea::{Input, Key, TextArea};
+
+use crate::{
+    components::{AppContext, Component, EventResult},
+    helper::text_area,
+};
+
+/// Asks for the reaction to toggle on the selected message
+#[derive(Debug)]
+pub struct ReactionModal<'a> {
+    reaction_field: TextArea<'a>,
+}
+
+impl ReactionModal<'_> {
+    #[must_use]
+    pub fn new() -> Self {
+        let mut reaction_field = text_area();
+        reaction_field.set_block(Block::bordered().title("Reaction (reacting again removes it)"));
+        reaction_field.set_cursor_line_style(Style::new().not_underlined());
+
+        Self { reaction_field }
+    }
+}
+
+impl Default for ReactionModal<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Component for ReactionModal<'_> {
+    fn handle_event(&mut self, event: &Event, ctx: &mut AppContext) -> EventResult {
+        match event.clone().into() {
+            Input {
+                key: Key::Char('m'),
+                ctrl: true,
+                ..
+            }
+            | Input {
+                key: Key::Enter, ..
+            } => {
+                let reaction = self.reaction_field.lines()[0].clone();
+                ctx.current_room_mut_action(|r| r.toggle_reaction(&reaction));
+                return EventResult::pop_component();
+            }
+            _ => {
+                self.reaction_field.input(event.clone());
+            }
+        }
+
+        EventResult::consumed()
+    }
+
+    fn render(&self, f: &mut Frame<'_>, area: Rect, _ctx: &AppContext) {
+        f.render_widget(&self.reaction_field, area);
+    }
+}
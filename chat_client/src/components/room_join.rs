@@ -12,9 +12,29 @@ use url::Url;
 
 use crate::{
     components::{AppAction, Component, EventResult},
-    helper::{ServerUrl, apply_cursor_style, text_area},
+    config::RoomCredentials,
+    consts::{FOCUSED_CURSOR_STYLE, UNFOCUSED_CURSOR_STYLE},
+    helper::{ServerUrl, text_area},
 };
 
+/// The field of the modal that's being typed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Room,
+    Password,
+    Url,
+}
+
+impl Field {
+    const fn next(self) -> Self {
+        match self {
+            Field::Room => Field::Password,
+            Field::Password => Field::Url,
+            Field::Url => Field::Room,
+        }
+    }
+}
+
 /// TODO: make this better
 #[derive(Debug)]
 pub struct RoomJoinModal<'a> {
@@ -22,14 +42,17 @@ pub struct RoomJoinModal<'a> {
     server_url: Url,
     message_field: TextArea<'a>,
     url_field: TextArea<'a>,
-    typing_url: bool,
+    /// Only needed for protected rooms, the saved credentials are used if it's empty
+    password_field: TextArea<'a>,
+    focus: Field,
 }
 
-impl RoomJoinModal<'_> {
+impl<'a> RoomJoinModal<'a> {
     #[must_use]
     pub fn new(server_url: Url) -> Self {
         let url_block = Block::bordered().title("Server url");
         let message_block = Block::bordered().title("Room name");
+        let password_block = Block::bordered().title("Password (optional)");
 
         let mut url_field = text_area();
         url_field.insert_str(&server_url);
@@ -42,26 +65,39 @@ impl RoomJoinModal<'_> {
         message_field.set_block(message_block);
         message_field.set_cursor_line_style(Style::new().not_underlined());
 
-        apply_cursor_style(&mut url_field, &mut message_field, false);
+        let mut password_field = text_area();
+        password_field.set_block(password_block);
+        password_field.set_cursor_line_style(Style::new().not_underlined());
+        password_field.set_mask_char('*');
 
-        Self {
+        let mut modal = Self {
             server_url,
             message_field,
             url_field,
-            typing_url: false,
-        }
+            password_field,
+            focus: Field::Room,
+        };
+        modal.apply_cursor_style();
+
+        modal
     }
 
     fn apply_cursor_style(&mut self) {
-        apply_cursor_style(
-            &mut self.url_field,
-            &mut self.message_field,
-            self.typing_url,
-        );
+        for (field, area) in [
+            (Field::Room, &mut self.message_field),
+            (Field::Password, &mut self.password_field),
+            (Field::Url, &mut self.url_field),
+        ] {
+            area.set_cursor_style(if field == self.focus {
+                FOCUSED_CURSOR_STYLE
+            } else {
+                UNFOCUSED_CURSOR_STYLE
+            });
+        }
     }
 
     fn switch_inputs(&mut self) {
-        if self.typing_url {
+        if self.focus == Field::Url {
             let url_text = self.url_field.lines()[0].clone();
             let url = ServerUrl::from_str(url_text.trim());
             if let Ok(url) = url {
@@ -71,9 +107,25 @@ impl RoomJoinModal<'_> {
             self.url_field.insert_str(&self.server_url);
         }
 
-        self.typing_url = !self.typing_url;
+        self.focus = self.focus.next();
         self.apply_cursor_style();
     }
+
+    fn credentials(&self) -> Option<RoomCredentials> {
+        let password = &self.password_field.lines()[0];
+        (!password.is_empty()).then(|| RoomCredentials {
+            password: Some(password.clone()),
+            invite: None,
+        })
+    }
+
+    const fn focused_field(&mut self) -> &mut TextArea<'a> {
+        match self.focus {
+            Field::Room => &mut self.message_field,
+            Field::Password => &mut self.password_field,
+            Field::Url => &mut self.url_field,
+        }
+    }
 }
 
 impl Component for RoomJoinModal<'_> {
@@ -89,7 +141,7 @@ impl Component for RoomJoinModal<'_> {
             } => {
                 let input = self.message_field.lines()[0].trim();
                 return EventResult::batch([
-                    AppAction::join_room(self.server_url.clone(), input),
+                    AppAction::join_room(self.server_url.clone(), input, self.credentials()),
                     AppAction::pop_component(),
                 ]);
             }
@@ -97,11 +149,7 @@ impl Component for RoomJoinModal<'_> {
                 self.switch_inputs();
             }
             _ => {
-                if self.typing_url {
-                    self.url_field.input(event.clone());
-                } else {
-                    self.message_field.input(event.clone());
-                }
+                self.focused_field().input(event.clone());
             }
         }
 
@@ -111,11 +159,16 @@ impl Component for RoomJoinModal<'_> {
     fn render(&self, f: &mut Frame<'_>, area: Rect, _ctx: &super::AppContext) {
         let layout = Layout::new(
             Direction::Vertical,
-            [Constraint::Length(3), Constraint::Length(3)],
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+            ],
         );
         let area = layout.split(area);
 
         f.render_widget(&self.url_field, area[0]);
         f.render_widget(&self.message_field, area[1]);
+        f.render_widget(&self.password_field, area[2]);
     }
 }
@@ -1,3 +1,4 @@
+use chat_lib::types::{MessageId, Presence};
 use crossterm::event::Event;
 use ratatui::{
     Frame,
@@ -13,18 +14,27 @@ use crate::{
     chat::{draw_room_events, draw_top_bar, top_block},
     components::{
         AppContext, Component, EventResult, log_view::LogView, notification_view::NotificationView,
-        popup::Popup, popup_options::PopupOptions, room_join::RoomJoinModal,
-        room_switch::RoomSwitchModal, screen::Screen, text_popup::TextPopup, user_view::UserView,
+        popup::Popup, popup_options::PopupOptions, reaction_modal::ReactionModal,
+        room_join::RoomJoinModal, room_switch::RoomSwitchModal, screen::Screen,
+        text_popup::TextPopup, user_view::UserView,
     },
     consts::TUI_HELP_TEXT,
     helper::{RoomLocation, text_area},
-    room::RoomState,
+    room::{Room, RoomState},
 };
 
 #[derive(Debug)]
 pub struct Root<'a> {
     message_field: TextArea<'a>,
     active_text_area: Option<TextArea<'a>>,
+    /// The message being edited through the message field
+    editing: Option<MessageId>,
+    /// The message the text in the message field replies to
+    replying_to: Option<MessageId>,
+    /// The message field holds the room's new topic
+    setting_topic: bool,
+    /// The message field holds this client's new custom status
+    setting_status: bool,
     show_sidebar: bool,
 }
 
@@ -55,6 +65,30 @@ fn help_popup() -> TextPopup<'static> {
     })
 }
 
+/// The name of the author of the message, as far as the room knows
+fn author_of(room: &Room, id: MessageId) -> String {
+    room.find_message(id)
+        .and_then(|m| room.users().get(m.get_author()))
+        .map_or("a message".to_string(), |u| u.get_name().to_owned())
+}
+
+/// Who is typing in the room, if anyone
+fn typing_line(room: &Room) -> Option<Line<'static>> {
+    let names = room
+        .typing_users()
+        .iter()
+        .map(|u| u.get_name().to_owned())
+        .collect::<Vec<_>>();
+    let text = match names.as_slice() {
+        [] => return None,
+        [name] => format!("{name} is typing…"),
+        [first, second] => format!("{first} and {second} are typing…"),
+        _ => "Several people are typing…".to_string(),
+    };
+
+    Some(Line::from(text).dark_gray().italic())
+}
+
 fn loc_domain(loc: &RoomLocation) -> String {
     loc.url.host_str().unwrap_or("unknown").to_string()
 }
@@ -75,6 +109,10 @@ impl Root<'_> {
         Self {
             active_text_area: None,
             message_field: text_area(),
+            editing: None,
+            replying_to: None,
+            setting_topic: false,
+            setting_status: false,
             show_sidebar: false,
         }
     }
@@ -137,6 +175,95 @@ impl Root<'_> {
             }
             Input { key: Key::Esc, .. } => {
                 self.exit_username_text_area();
+                self.stop_editing();
+                self.stop_setting_topic();
+                self.stop_setting_status();
+                self.replying_to = None;
+                ctx.current_room_mut_action(|r| {
+                    r.clear_selection();
+                    r.set_dm_target(None);
+                });
+            }
+            Input {
+                key: Key::Up,
+                alt: true,
+                ..
+            } => {
+                ctx.current_room_mut_action(Room::select_previous);
+            }
+            Input {
+                key: Key::Down,
+                alt: true,
+                ..
+            } => {
+                ctx.current_room_mut_action(Room::select_next);
+            }
+            Input {
+                key: Key::Char('e'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                self.start_editing(ctx);
+            }
+            Input {
+                key: Key::Char('x'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                Self::delete_selected(ctx);
+            }
+            Input {
+                key: Key::Char('q'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                self.start_reply(ctx);
+            }
+            Input {
+                key: Key::Char('t'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                ctx.current_room_mut_action(Room::toggle_thread);
+            }
+            Input {
+                key: Key::Char('o'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                self.start_setting_topic(ctx);
+            }
+            Input {
+                key: Key::Char('s'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                self.start_setting_status(ctx);
+            }
+            Input {
+                key: Key::Char('r'),
+                alt: true,
+                ctrl: false,
+                ..
+            } => {
+                if ctx
+                    .current_room()
+                    .is_some_and(|r| r.selected_message().is_some())
+                {
+                    let opts = PopupOptions::new()
+                        .set_vsize(Constraint::Length(5))
+                        .set_name("React to the message");
+                    return EventResult::push_component(Popup::new(
+                        ReactionModal::new().boxed(),
+                        opts,
+                    ));
+                }
             }
             Input {
                 key: Key::Char('l'),
@@ -166,7 +293,7 @@ impl Root<'_> {
             } => {
                 let modal = RoomJoinModal::new(ctx.config.web.url.clone());
                 let opts = PopupOptions::new()
-                    .set_vsize(Constraint::Length(8))
+                    .set_vsize(Constraint::Length(11))
                     .set_name("Join a room");
                 return EventResult::push_component(Popup::new(modal.boxed(), opts));
             }
@@ -219,7 +346,7 @@ impl Root<'_> {
     }
 
     pub fn join_room(&mut self, ctx: &mut AppContext, room_name: &str) {
-        ctx.join_room(ctx.config.web.url.clone(), room_name);
+        ctx.join_room(ctx.config.web.url.clone(), room_name, None);
     }
 
     fn draw_chat(&self, f: &'_ mut Frame, area: Rect, ctx: &AppContext) {
@@ -317,20 +444,16 @@ impl Root<'_> {
 
         let chunks = layout().split(area);
         let mut name = String::from("Not in a room");
+        let mut topic = None;
         let mut can_message = false;
         if let Some(room) = ctx.current_room() {
             can_message = room.can_send_messages();
+            topic = room.topic();
             name = room
                 .self_user()
                 .map_or("Loading...".to_owned(), |u| u.get_name().to_owned());
 
-            draw_room_events(
-                f,
-                chunks[1],
-                &room.events(),
-                room.users(),
-                room.scroll_offset(),
-            );
+            draw_room_events(f, chunks[1], room);
         } else {
             f.render_widget(Clear, chunks[1]);
         }
@@ -338,10 +461,30 @@ impl Root<'_> {
         if let Some(ta) = &self.active_text_area {
             f.render_widget(ta, chunks[0]);
         } else {
-            draw_top_bar(f, chunks[0], &name, ctx);
+            draw_top_bar(f, chunks[0], &name, topic, ctx);
         }
         if can_message {
             f.render_widget(&self.message_field, chunks[2]);
+            if let Some(typing) = ctx.current_room().and_then(typing_line) {
+                // drawn over the bottom border of the message field
+                let typing_area = Rect {
+                    x: chunks[2].x + 1,
+                    y: chunks[2].bottom().saturating_sub(1),
+                    height: 1,
+                    width: chunks[2].width.saturating_sub(2),
+                };
+                f.render_widget(typing, typing_area);
+            }
+            if let Some(title) = self.input_title(ctx) {
+                // drawn over the top border of the message field
+                let title_area = Rect {
+                    x: chunks[2].x + 1,
+                    height: 1,
+                    width: chunks[2].width.saturating_sub(2),
+                    ..chunks[2]
+                };
+                f.render_widget(title, title_area);
+            }
         } else {
             let block = Block::bordered();
             let area = block.inner(chunks[2]);
@@ -360,22 +503,184 @@ impl Root<'_> {
         } else {
             let message = self.message_field.lines()[0].clone();
             self.message_field.clear();
-            ctx.current_room_mut_action(|r| {
-                r.send_text(&message);
-            });
+            if let Some(id) = self.editing.take() {
+                ctx.current_room_mut_action(|r| {
+                    r.edit_message(id, &message);
+                });
+            } else if std::mem::take(&mut self.setting_topic) {
+                ctx.current_room_mut_action(|r| {
+                    r.set_topic(&message);
+                });
+            } else if std::mem::take(&mut self.setting_status) {
+                ctx.current_room_mut_action(|r| {
+                    r.set_status(&message);
+                });
+            } else if let Some(id) = self.replying_to.take() {
+                ctx.current_room_mut_action(|r| {
+                    r.reply(id, &message);
+                });
+            } else {
+                ctx.current_room_mut_action(|r| {
+                    r.send_text(&message);
+                });
+            }
+        }
+    }
+
+    /// Describes where the text in the message field goes, if it's not a plain message
+    fn input_title(&self, ctx: &AppContext) -> Option<Line<'_>> {
+        if self.editing.is_some() {
+            return Some(Line::from("Editing (Esc to cancel)").yellow());
+        }
+        if self.setting_topic {
+            return Some(Line::from("Topic (Esc to cancel)").light_blue());
+        }
+        if self.setting_status {
+            return Some(Line::from("Status (Esc to cancel)").light_blue());
+        }
+
+        let room = ctx.current_room()?;
+        if let Some(target) = room.dm_target() {
+            return Some(
+                Line::from(format!("DM to {} (Esc to cancel)", target.get_name())).magenta(),
+            );
+        }
+        if let Some(id) = self.replying_to {
+            return Some(
+                Line::from(format!(
+                    "Replying to {} (Esc to cancel)",
+                    author_of(room, id)
+                ))
+                .cyan(),
+            );
+        }
+
+        let thread = room.thread()?;
+        Some(
+            Line::from(format!(
+                "Thread of {} (Alt+t to leave)",
+                author_of(room, thread)
+            ))
+            .cyan(),
+        )
+    }
+
+    /// The next submit replies to the selected message
+    fn start_reply(&mut self, ctx: &AppContext) {
+        let Some(id) = ctx
+            .current_room()
+            .and_then(Room::selected_message)
+            .and_then(|m| m.get_id())
+            .copied()
+        else {
+            return;
+        };
+
+        self.stop_editing();
+        self.stop_setting_topic();
+        self.stop_setting_status();
+        self.replying_to = Some(id);
+    }
+
+    /// Puts the selected message into the message field, the next submit edits it
+    fn start_editing(&mut self, ctx: &AppContext) {
+        let Some(room) = ctx.current_room() else {
+            return;
+        };
+        let Some(msg) = room.selected_message() else {
+            return;
+        };
+        if !room.is_own_message(msg) {
+            crate::notif_warn!("You can only edit your own messages");
+            return;
+        }
+
+        self.message_field = text_area();
+        self.message_field.insert_str(msg.get_content());
+        self.editing = msg.get_id().copied();
+        self.setting_topic = false;
+        self.setting_status = false;
+        self.replying_to = None;
+    }
+
+    fn stop_editing(&mut self) {
+        if self.editing.take().is_some() {
+            self.message_field = text_area();
+        }
+    }
+
+    /// Puts the room's topic into the message field, the next submit changes it
+    fn start_setting_topic(&mut self, ctx: &AppContext) {
+        let Some(room) = ctx.current_room() else {
+            return;
+        };
+
+        self.message_field = text_area();
+        self.message_field
+            .insert_str(room.topic().unwrap_or_default());
+        self.setting_topic = true;
+        self.setting_status = false;
+        self.editing = None;
+        self.replying_to = None;
+    }
+
+    fn stop_setting_topic(&mut self) {
+        if std::mem::take(&mut self.setting_topic) {
+            self.message_field = text_area();
         }
     }
 
-    fn forward_input(&mut self, ctx: &AppContext, input: Input) {
+    /// Puts this client's custom status into the message field, the next submit changes it
+    fn start_setting_status(&mut self, ctx: &AppContext) {
+        let Some(room) = ctx.current_room() else {
+            return;
+        };
+
+        self.message_field = text_area();
+        if let Some(Presence::Custom(status)) = room.self_presence() {
+            self.message_field.insert_str(status);
+        }
+        self.setting_status = true;
+        self.setting_topic = false;
+        self.editing = None;
+        self.replying_to = None;
+    }
+
+    fn stop_setting_status(&mut self) {
+        if std::mem::take(&mut self.setting_status) {
+            self.message_field = text_area();
+        }
+    }
+
+    fn delete_selected(ctx: &mut AppContext) {
+        ctx.current_room_mut_action(|r| {
+            let Some(msg) = r.selected_message() else {
+                return;
+            };
+            if !r.is_own_message(msg) {
+                crate::notif_warn!("You can only delete your own messages");
+                return;
+            }
+            if let Some(id) = msg.get_id().copied() {
+                r.delete_message(id);
+            }
+        });
+    }
+
+    fn forward_input(&mut self, ctx: &mut AppContext, input: Input) {
         // Doesn't really make sense to accept input into
         // any of the text area if the user is not in a room
-        if let Some(room) = ctx.current_room()
+        if let Some(room) = ctx.current_room_mut()
             && room.can_send_messages()
         {
             if let Some(active) = &mut self.active_text_area {
                 active.input(input);
             } else {
                 self.message_field.input(input);
+                // edits, topics and statuses aren't announced, they're not new messages
+                if self.editing.is_none() && !self.setting_topic && !self.setting_status {
+                    room.notify_typing(!self.message_field.is_empty());
+                }
             }
         }
     }
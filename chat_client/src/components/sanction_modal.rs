@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use crossterm::event::Event;
+use ratatui::{Frame, layout::Rect, style::Style, widgets::Block};
+use ratatui_textarea::{Input, Key, TextArea};
+use uuid::Uuid;
+
+use crate::{
+    components::{AppContext, Component, EventResult},
+    helper::text_area,
+};
+
+/// The timed sanctions a moderator can hand out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimedSanction {
+    Mute,
+    Ban,
+}
+
+/// Parses durations like `90s`, `30m`, `2h` or `1d`, plain numbers are minutes
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let (amount, unit) = match text.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&text[..i], c),
+        _ => (text, 'm'),
+    };
+    let amount = amount.trim().parse::<u64>().ok()?;
+    let secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(amount.saturating_mul(secs)))
+}
+
+/// Asks how long the user should be muted or banned for
+#[derive(Debug)]
+pub struct SanctionModal<'a> {
+    user: Uuid,
+    sanction: TimedSanction,
+    duration_field: TextArea<'a>,
+}
+
+impl SanctionModal<'_> {
+    #[must_use]
+    pub fn new(user: Uuid, sanction: TimedSanction) -> Self {
+        let title = match sanction {
+            TimedSanction::Mute => "Mute for (e.g. 10m, 2h, 1d; 0 lifts the mute)",
+            TimedSanction::Ban => "Ban for (e.g. 10m, 2h, 1d; 0 lifts the ban)",
+        };
+        let mut duration_field = text_area();
+        duration_field.set_block(Block::bordered().title(title));
+        duration_field.set_cursor_line_style(Style::new().not_underlined());
+
+        Self {
+            user,
+            sanction,
+            duration_field,
+        }
+    }
+}
+
+impl Component for SanctionModal<'_> {
+    fn handle_event(&mut self, event: &Event, ctx: &mut AppContext) -> EventResult {
+        match event.clone().into() {
+            Input {
+                key: Key::Char('m'),
+                ctrl: true,
+                ..
+            }
+            | Input {
+                key: Key::Enter, ..
+            } => {
+                let Some(duration) = parse_duration(&self.duration_field.lines()[0]) else {
+                    crate::notif_warn!("The duration should look like 90s, 30m, 2h or 1d");
+                    return EventResult::consumed();
+                };
+                let (user, sanction) = (self.user, self.sanction);
+                ctx.current_room_mut_action(|r| match sanction {
+                    TimedSanction::Mute => r.mute(user, duration),
+                    TimedSanction::Ban => r.ban(user, duration),
+                });
+                return EventResult::pop_component();
+            }
+            _ => {
+                self.duration_field.input(event.clone());
+            }
+        }
+
+        EventResult::consumed()
+    }
+
+    fn render(&self, f: &mut Frame<'_>, area: Rect, _ctx: &AppContext) {
+        f.render_widget(&self.duration_field, area);
+    }
+}
@@ -1,21 +1,143 @@
+use chat_lib::types::{Presence, User};
 use crossterm::event::Event;
 use ratatui::{
+    layout::Constraint,
     style::Stylize,
     text::{Line, ToSpan},
     widgets::{Clear, Paragraph},
 };
 use ratatui_textarea::{Input, Key};
+use uuid::Uuid;
 
-use crate::components::{AppContext, Component, EventResult};
+use crate::components::{
+    AppContext, Component, EventResult, Popup, PopupOptions, SanctionModal, TimedSanction,
+};
 
 #[derive(Debug)]
 pub struct UserView {
     scroll: u16,
+    selected: usize,
 }
 
 impl UserView {
     pub const fn new() -> Self {
-        Self { scroll: 0 }
+        Self {
+            scroll: 0,
+            selected: 0,
+        }
+    }
+
+    /// The users in the current room, in the order they're displayed
+    fn listed_users(ctx: &AppContext) -> Vec<&User> {
+        let Some(r) = ctx.current_room() else {
+            return Vec::new();
+        };
+        let mut users = r
+            .users()
+            .values()
+            .filter(|u| r.user_in_room(*u.get_id()))
+            .collect::<Vec<_>>();
+        users.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        users
+    }
+
+    fn selected_id(&self, ctx: &AppContext) -> Option<Uuid> {
+        Self::listed_users(ctx)
+            .get(self.selected)
+            .map(|u| *u.get_id())
+    }
+
+    /// Makes the selected user the target of the messages sent in the room
+    fn message_selected(&self, ctx: &mut AppContext) -> EventResult {
+        let Some(id) = self.selected_id(ctx) else {
+            return EventResult::consumed();
+        };
+
+        ctx.current_room_mut_action(|r| {
+            if r.self_user().is_some_and(|u| *u.get_id() == id) {
+                crate::notif_warn!("You can't send direct messages to yourself");
+            } else {
+                r.set_dm_target(Some(id));
+            }
+        });
+
+        EventResult::pop_component()
+    }
+
+    /// The selected user, if this client can sanction them
+    fn sanctionable(&self, ctx: &AppContext) -> Option<Uuid> {
+        let id = self.selected_id(ctx)?;
+        ctx.current_room()?.can_sanction(id).then_some(id)
+    }
+
+    fn kick_selected(&self, ctx: &mut AppContext) {
+        if let Some(id) = self.sanctionable(ctx) {
+            ctx.current_room_mut_action(|r| r.kick(id));
+        }
+    }
+
+    /// Asks how long the selected user should be sanctioned for
+    fn sanction_selected(&self, ctx: &AppContext, sanction: TimedSanction) -> EventResult {
+        let Some(id) = self.sanctionable(ctx) else {
+            return EventResult::consumed();
+        };
+        let name = match sanction {
+            TimedSanction::Mute => "Mute",
+            TimedSanction::Ban => "Ban",
+        };
+        let opts = PopupOptions::new()
+            .set_vsize(Constraint::Length(5))
+            .set_name(name);
+
+        EventResult::push_component(Popup::new(SanctionModal::new(id, sanction).boxed(), opts))
+    }
+
+    /// Appoints the selected user as moderator, or dismisses them if they already are one
+    fn toggle_moderator_selected(&self, ctx: &mut AppContext) {
+        let Some(id) = self.selected_id(ctx) else {
+            return;
+        };
+        ctx.current_room_mut_action(|r| {
+            if r.self_is_owner() && !r.is_owner(id) {
+                let moderator = !r.is_moderator(id);
+                r.set_moderator(id, moderator);
+            }
+        });
+    }
+
+    /// Switches this client's presence between online, busy and away,
+    /// a custom status goes back to online
+    fn cycle_presence(ctx: &mut AppContext) {
+        ctx.current_room_mut_action(|r| {
+            let presence = match r.self_presence() {
+                Some(Presence::Online) => Presence::Busy,
+                Some(Presence::Busy) => Presence::Away,
+                Some(Presence::Away | Presence::Custom(_)) => Presence::Online,
+                None => return,
+            };
+            r.set_presence(presence);
+        });
+    }
+
+    /// The moderation keys this client can use, if any
+    fn moderation_hint(ctx: &AppContext) -> Option<String> {
+        let room = ctx.current_room()?;
+        if room.self_is_owner() {
+            let mode = room
+                .filter()
+                .map_or_else(|| "unknown".to_string(), |f| f.mode.to_string());
+            Some(format!(
+                "k: kick, m: mute, b: ban, o: toggle moderator, f: filter ({mode})"
+            ))
+        } else if room
+            .self_user()
+            .is_some_and(|u| room.is_moderator(*u.get_id()))
+        {
+            Some("k: kick, m: mute, b: ban".to_string())
+        } else {
+            None
+        }
     }
 }
 
@@ -26,8 +148,77 @@ impl Default for UserView {
 }
 
 impl Component for UserView {
-    fn handle_event(&mut self, event: &Event, _ctx: &mut AppContext) -> EventResult {
+    fn handle_event(&mut self, event: &Event, ctx: &mut AppContext) -> EventResult {
         match event.clone().into() {
+            Input { key: Key::Up, .. } => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            Input { key: Key::Down, .. } => {
+                let last = Self::listed_users(ctx).len().saturating_sub(1);
+                self.selected = (self.selected + 1).min(last);
+            }
+            Input {
+                key: Key::Char('m'),
+                ctrl: true,
+                ..
+            }
+            | Input {
+                key: Key::Enter, ..
+            } => {
+                return self.message_selected(ctx);
+            }
+            Input {
+                key: Key::Char('k'),
+                ctrl: false,
+                alt: false,
+                ..
+            } => {
+                self.kick_selected(ctx);
+            }
+            Input {
+                key: Key::Char('m'),
+                ctrl: false,
+                alt: false,
+                ..
+            } => {
+                return self.sanction_selected(ctx, TimedSanction::Mute);
+            }
+            Input {
+                key: Key::Char('b'),
+                ctrl: false,
+                alt: false,
+                ..
+            } => {
+                return self.sanction_selected(ctx, TimedSanction::Ban);
+            }
+            Input {
+                key: Key::Char('o'),
+                ctrl: false,
+                alt: false,
+                ..
+            } => {
+                self.toggle_moderator_selected(ctx);
+            }
+            Input {
+                key: Key::Char('p'),
+                ctrl: false,
+                alt: false,
+                ..
+            } => {
+                Self::cycle_presence(ctx);
+            }
+            Input {
+                key: Key::Char('f'),
+                ctrl: false,
+                alt: false,
+                ..
+            } => {
+                ctx.current_room_mut_action(|r| {
+                    if r.self_is_owner() {
+                        r.cycle_filter_mode();
+                    }
+                });
+            }
             Input {
                 key: Key::Char('d'),
                 ctrl: true,
@@ -63,13 +254,35 @@ impl Component for UserView {
         ctx: &super::AppContext,
     ) {
         let mut lines = Vec::new();
-        if let Some(r) = ctx.current_room() {
-            for (id, usr) in r.users().iter().filter(|(id, _)| r.user_in_room(**id)) {
-                lines.push(Line::from_iter([
+        if let Some(room) = ctx.current_room() {
+            if let Some(hint) = Self::moderation_hint(ctx) {
+                lines.push(Line::from(hint.dark_gray()));
+            }
+            for (i, usr) in Self::listed_users(ctx).into_iter().enumerate() {
+                let id = *usr.get_id();
+                let role = if room.is_owner(id) {
+                    " [owner]"
+                } else if room.is_moderator(id) {
+                    " [mod]"
+                } else {
+                    ""
+                };
+                let presence = match usr.get_presence() {
+                    Presence::Online => String::new(),
+                    presence => format!(" ({presence})"),
+                };
+                let line = Line::from_iter([
                     usr.get_name().blue(),
+                    role.yellow(),
+                    presence.gray(),
                     " ".to_span(),
                     format!("({id})").dark_gray(),
-                ]));
+                ]);
+                lines.push(if i == self.selected {
+                    line.reversed()
+                } else {
+                    line
+                });
             }
             let para = Paragraph::new(lines).scroll((self.scroll, 0));
 
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand};
 
 use crate::helper::ServerUrl;
@@ -18,24 +20,39 @@ pub struct GlobalArgs {
     /// Sets the base server url all the requests will use
     #[arg(long, global = true)]
     pub url: Option<ServerUrl>,
+    /// Trusts this PEM certificate on top of the system's, for a server with a self signed certificate
+    #[arg(long, global = true)]
+    pub ca_cert: Option<PathBuf>,
     /// Sets the room that is joined to by default
     #[arg(short, long, global = true)]
     pub room: Option<String>,
+    /// Joins the default room with this invite code, if the room is protected
+    #[arg(long, global = true)]
+    pub invite: Option<String>,
     /// Prints the default config to stdout
     #[arg(long, global = true)]
     pub default_config: bool,
     /// Deletes the log file before starting the client
     #[arg(short, long, global = true)]
     pub clean: bool,
-    /// Sets the name the client will try to join as
+    /// Sets the name the client will try to join as, or the account name when logging in
     #[arg(short, long, global = true)]
     pub name: Option<String>,
+    /// Joins as a guest even if there's a saved session
+    #[arg(short, long, global = true)]
+    pub guest: bool,
 }
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum ActionType {
     Ls(LsArgs),
     Echo(EchoArgs),
+    /// Registers an account named `--name` and logs in with it
+    Register,
+    /// Logs in to the account named `--name`, the session is saved for the server
+    Login,
+    /// Forgets the saved session
+    Logout,
 }
 
 #[derive(Debug, Clone, Args)]
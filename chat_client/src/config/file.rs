@@ -1,5 +1,6 @@
-use std::str::FromStr;
+use std::{collections::HashMap, path::PathBuf, str::FromStr, time::Duration};
 
+use chat_lib::Encoding;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
@@ -16,11 +17,48 @@ pub struct WebConfig {
     pub url: Url,
     pub default_room: String,
     pub defult_name: Option<String>,
+    /// The preferred wire encoding, json is used if the server doesn't support it
+    #[serde(default)]
+    pub encoding: Encoding,
+    /// A PEM certificate trusted on top of the system's, for a server with a self signed certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+    /// The credentials of the protected rooms on the server at `url`, by room name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rooms: HashMap<String, RoomCredentials>,
+    /// The token of the saved session, if logged in to the server at `url`
+    #[serde(skip)]
+    pub token: Option<String>,
+}
+
+/// What joining a protected room needs, either one is enough
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RoomCredentials {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+}
+
+impl WebConfig {
+    /// The saved credentials of the room, only rooms on the server at `url` have them
+    #[must_use]
+    pub fn credentials(&self, url: &Url, room: &str) -> RoomCredentials {
+        if *url != self.url {
+            return RoomCredentials::default();
+        }
+
+        self.rooms.get(room).cloned().unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatConfig {
     pub buffer_size: usize,
+    /// How long without any input before the user is marked away, 0 disables it
+    #[serde(default = "ChatConfig::default_idle_away")]
+    pub idle_away_secs: u64,
 }
 
 impl Default for AppConfig {
@@ -32,8 +70,15 @@ impl Default for AppConfig {
                     .expect("Default Connection url to be correct"),
                 default_room: String::from("default"),
                 defult_name: None,
+                encoding: Encoding::default(),
+                ca_cert: None,
+                rooms: HashMap::new(),
+                token: None,
+            },
+            chat: ChatConfig {
+                buffer_size: 5_000,
+                idle_away_secs: ChatConfig::default_idle_away(),
             },
-            chat: ChatConfig { buffer_size: 5_000 },
         }
     }
 }
@@ -49,6 +94,18 @@ impl AppConfig {
             self.web.default_room.clone_from(room);
         }
 
+        if let Some(ca_cert) = &args.args.ca_cert {
+            self.web.ca_cert = Some(ca_cert.clone());
+        }
+
+        if let Some(invite) = &args.args.invite {
+            self.web
+                .rooms
+                .entry(self.web.default_room.clone())
+                .or_default()
+                .invite = Some(invite.clone());
+        }
+
         if let Some(name) = &args.args.name {
             self.web.defult_name = Some(name.clone());
         }
@@ -56,3 +113,19 @@ impl AppConfig {
         self
     }
 }
+
+impl ChatConfig {
+    const fn default_idle_away() -> u64 {
+        5 * 60
+    }
+
+    /// How long without any input before the user is marked away, if it's enabled
+    #[must_use]
+    pub const fn idle_away(&self) -> Option<Duration> {
+        if self.idle_away_secs == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.idle_away_secs))
+        }
+    }
+}
@@ -7,9 +7,11 @@
 pub(crate) mod args;
 pub(crate) mod file;
 pub mod logging;
+pub(crate) mod session;
 
 pub use args::*;
 pub use file::*;
+pub use session::*;
 
 use std::{fs, process};
 
@@ -23,7 +25,14 @@ pub fn init() -> (AppConfig, Option<ActionType>) {
     handle_flags(&cli);
 
     let config = read_config().unwrap_or_default();
-    let config = config.merge(&cli);
+    let mut config = config.merge(&cli);
+
+    if !cli.args.guest
+        && let Some(session) = SavedSession::load()
+        && session.url == config.web.url
+    {
+        config.web.token = Some(session.token);
+    }
 
     (config, cli.action)
 }
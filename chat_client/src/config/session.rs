@@ -0,0 +1,53 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use dirs::data_dir;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// A session saved by logging in, only used on the server it was made on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub url: Url,
+    pub name: String,
+    pub token: String,
+}
+
+fn session_path() -> Option<PathBuf> {
+    let mut path = data_dir()?;
+    path.push("rs_chat/session.toml");
+    Some(path)
+}
+
+impl SavedSession {
+    #[must_use]
+    pub fn load() -> Option<Self> {
+        let session = fs::read_to_string(session_path()?).ok()?;
+        toml::from_str(&session).ok()
+    }
+
+    /// # Errors
+    ///
+    /// This function errors if the session file can't be written
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = session_path().context("Couldn't find the data directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Couldn't save the session to {}", path.display()))
+    }
+
+    /// Returns whether there was a session to remove
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the session file exists, but can't be removed
+    pub fn remove() -> anyhow::Result<bool> {
+        let Some(path) = session_path().filter(|path| path.exists()) else {
+            return Ok(false);
+        };
+        fs::remove_file(path)?;
+        Ok(true)
+    }
+}
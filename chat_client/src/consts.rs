@@ -4,6 +4,8 @@ use chat_lib::{Version, text_resource};
 use ratatui::style::Style;
 use reqwest::Client;
 
+use crate::tls;
+
 /// The buffer size for the various channels (mpsc/broadcast)
 pub const CHANNEL_BUFFER_SIZE: usize = 128;
 
@@ -18,22 +20,32 @@ pub const TICK_DURATION: Duration = Duration::from_millis(100);
 /// The wait time when joining with a ws handler
 pub const WS_TIMEOUT_DURATION: Duration = Duration::from_millis(500);
 
+/// The wait time between two attempts to resume a dropped connection
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
 // The duration the notification poller will wait for between polling the pending events
 pub const NOTIFICATION_POLLER_TIMEOUT: Duration = Duration::from_millis(500);
 
 pub const NOTIFICATION_LIFETIME: Duration = Duration::from_mins(5);
 
-/// The duration for which the action is considered 'pending'
-pub const ACTION_LIFETIME: Duration = Duration::from_millis(500);
+/// The duration after which a request without a reply is considered lost
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The minimum time between two typing signals sent to the server
+pub const TYPING_THROTTLE: Duration = Duration::from_secs(3);
+
+/// The duration after which someone is considered to have stopped typing without a new signal
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub const TUI_HELP_TEXT: &str = text_resource!("../const_resources/tui_help.md");
 
-pub static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+/// Trusts the configured ca certificate, so it shouldn't be used before `tls::init`
+pub static CLIENT: LazyLock<Client> = LazyLock::new(tls::http_client);
 
 pub const FOCUSED_CURSOR_STYLE: Style = Style::new().reversed().not_underlined();
 pub const UNFOCUSED_CURSOR_STYLE: Style = Style::new().not_reversed().underlined();
 
-pub const SUPPORTED_API_VERSIONS: &[Version] = &[Version::V1];
+pub const SUPPORTED_API_VERSIONS: &[Version] = &[Version::V1, Version::V2];
 
 #[cfg(test)]
 mod tests {
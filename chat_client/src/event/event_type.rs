@@ -1,6 +1,6 @@
 use chat_lib::prelude::*;
 use ratatui::{
-    style::Style,
+    style::{Style, Stylize},
     text::{Line, Span},
 };
 use uuid::Uuid;
@@ -16,6 +16,8 @@ pub enum EventType {
 #[derive(Debug, Clone)]
 pub struct UserEventType {
     pub display_as_loading: bool,
+    /// Shown before the user, if the server sent a time for the event
+    pub time: Option<String>,
     pub user_uuid: Uuid,
     pub user: Option<User>,
     pub message: String,
@@ -23,7 +25,26 @@ pub struct UserEventType {
     pub message_style: Style,
 }
 
+impl EventType {
+    /// Makes the event stand out, used for the selected event
+    #[must_use]
+    pub fn highlighted(self) -> Self {
+        match self {
+            EventType::Info { message, style } => EventType::Info {
+                message,
+                style: style.reversed(),
+            },
+            EventType::User(user_event) => EventType::User(UserEventType {
+                user_style: user_event.user_style.reversed(),
+                message_style: user_event.message_style.reversed(),
+                ..user_event
+            }),
+        }
+    }
+}
+
 impl UserEventType {
+    /// The width of the time and the user combined
     #[must_use]
     pub fn user_width(&self) -> usize {
         let user_string = match &self.user {
@@ -32,7 +53,11 @@ impl UserEventType {
             None => self.user_uuid.to_string(),
         };
 
-        user_string.chars().count()
+        self.time_width() + user_string.chars().count()
+    }
+
+    fn time_width(&self) -> usize {
+        self.time.as_ref().map_or(0, |t| t.chars().count() + 1)
     }
 
     #[must_use]
@@ -44,7 +69,7 @@ impl UserEventType {
             None if self.display_as_loading => "Loading...".to_string(),
             None => self.user_uuid.to_string(),
         };
-        let user_width = user_string.chars().count();
+        let user_width = self.time_width() + user_string.chars().count();
         let message_chars = self.message.chars().collect::<Vec<_>>();
 
         // the full width of the message
@@ -61,7 +86,12 @@ impl UserEventType {
 
         // process the first row of the message
         let first_message = String::from_iter(message_parts.next().unwrap_or(&[]));
+        let time = self
+            .time
+            .as_ref()
+            .map_or(String::new(), |t| format!("{t} "));
         let first_line = Line::from_iter([
+            Span::from(time).dark_gray(),
             Span::from(user_string).style(self.user_style),
             Span::from(" ".repeat(max_user_width - user_width)).style(self.user_style),
             Span::from(first_message).style(self.message_style),
@@ -1,37 +1,144 @@
 use std::time::Duration;
 
-use chat_lib::prelude::*;
+use chat_lib::{
+    prelude::*,
+    types::{MessageId, Sanction},
+};
+use chrono::{DateTime, Local, Utc};
 use ratatui::style::Style;
 use uuid::Uuid;
 
 use crate::event::{EventType, MessageTrait, UserEventType, UserLocator};
 
+/// Describes the sanction, like "mod muted user until 2025-01-01 12:00"
+fn sanction_message(user: Uuid, by: Uuid, sanction: &Sanction, users: &impl UserLocator) -> String {
+    let name = |id: Uuid| {
+        users
+            .get_user(id)
+            .map_or(id.to_string(), |u| u.get_name().to_owned())
+    };
+    let (user, by) = (name(user), name(by));
+    let at = |t: &DateTime<Utc>| t.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+
+    match sanction {
+        Sanction::Kick => format!("{by} kicked {user}"),
+        Sanction::Mute(until) => format!("{by} muted {user} until {}", at(until)),
+        Sanction::Unmute => format!("{by} unmuted {user}"),
+        Sanction::Ban(until) => format!("{by} banned {user} until {}", at(until)),
+        Sanction::Unban => format!("{by} unbanned {user}"),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum RoomEvent {
     Message(Message),
+    /// What's left of a message after its author deleted it
+    Deleted {
+        id: MessageId,
+        author: Uuid,
+        /// Kept so the thread the message was in stays intact
+        reply_to: Option<MessageId>,
+    },
+    /// A message only the author and `to` can see
+    DirectMessage {
+        to: Uuid,
+        message: Message,
+    },
     UserLeft(Uuid),
     UserJoined(Uuid),
-    UserNameChange { from: String, to: String },
-    Banned { duration: Duration, reason: String },
+    UserNameChange {
+        from: String,
+        to: String,
+    },
+    Banned {
+        duration: Duration,
+        reason: String,
+    },
+    /// A moderator sanctioned the user
+    Sanctioned {
+        user: Uuid,
+        by: Uuid,
+        sanction: Sanction,
+    },
+    /// A message from the server's operators
+    Announcement(String),
+    /// The room's topic changed, `None` if it was removed
+    TopicChanged(Option<String>),
+    /// Marks where the replayed history ends and the live events start
+    HistoryEnd,
 }
 
 impl RoomEvent {
+    /// The id of the message the event is about, if any
+    #[must_use]
+    pub fn message_id(&self) -> Option<&MessageId> {
+        match self {
+            RoomEvent::Message(msg) => msg.get_id(),
+            RoomEvent::Deleted { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The id of the message the event replies to, if any
+    #[must_use]
+    pub fn reply_to(&self) -> Option<&MessageId> {
+        match self {
+            RoomEvent::Message(msg) => msg.get_reply_to(),
+            RoomEvent::Deleted { reply_to, .. } => reply_to.as_ref(),
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn properties(&self, users: &impl UserLocator) -> EventType {
         match self {
             RoomEvent::Message(msg) => EventType::User(UserEventType {
                 display_as_loading: true,
+                time: msg
+                    .get_sent_at()
+                    .map(|t| t.with_timezone(&Local).format("%H:%M").to_string()),
                 user_uuid: *msg.get_author(),
                 user: msg.get_author_from(users).cloned(),
-                message: msg.get_content().to_string(),
+                message: if msg.is_edited() {
+                    format!("{} (edited)", msg.get_content())
+                } else {
+                    msg.get_content().to_string()
+                },
                 user_style: Style::new().cyan(),
                 message_style: Style::new(),
             }),
+            RoomEvent::DirectMessage { to, message } => {
+                let style = Style::new().magenta();
+                let recipient = users
+                    .get_user(*to)
+                    .map_or(to.to_string(), |u| u.get_name().to_owned());
+                EventType::User(UserEventType {
+                    display_as_loading: true,
+                    time: message
+                        .get_sent_at()
+                        .map(|t| t.with_timezone(&Local).format("%H:%M").to_string()),
+                    user_uuid: *message.get_author(),
+                    user: message.get_author_from(users).cloned(),
+                    message: format!("(to {recipient}) {}", message.get_content()),
+                    user_style: style,
+                    message_style: style,
+                })
+            }
+            RoomEvent::Deleted { author, .. } => EventType::User(UserEventType {
+                display_as_loading: true,
+                time: None,
+                user_uuid: *author,
+                user: users.get_user(*author).cloned(),
+                message: "message deleted".to_string(),
+                user_style: Style::new().cyan(),
+                message_style: Style::new().dark_gray().italic(),
+            }),
             RoomEvent::UserLeft(uuid) => {
                 let style = Style::new().red();
                 EventType::User(UserEventType {
                     // this can't be fetched after the user leaves
                     display_as_loading: false,
+                    time: None,
                     user_uuid: *uuid,
                     user: users.get_user(*uuid).cloned(),
                     message: "left the chat".to_string(),
@@ -43,6 +150,7 @@ impl RoomEvent {
                 let style = Style::new().light_green();
                 EventType::User(UserEventType {
                     display_as_loading: true,
+                    time: None,
                     user_uuid: *uuid,
                     user: users.get_user(*uuid).cloned(),
                     message: "joined the chat".to_string(),
@@ -61,6 +169,25 @@ impl RoomEvent {
                 ),
                 style: Style::new().red(),
             },
+            RoomEvent::Sanctioned { user, by, sanction } => EventType::Info {
+                message: sanction_message(*user, *by, sanction, users),
+                style: Style::new().yellow(),
+            },
+            RoomEvent::Announcement(msg) => EventType::Info {
+                message: format!("Announcement: {msg}"),
+                style: Style::new().yellow().bold(),
+            },
+            RoomEvent::TopicChanged(topic) => EventType::Info {
+                message: topic.as_ref().map_or_else(
+                    || "The topic was removed".to_string(),
+                    |topic| format!("The topic is now: {topic}"),
+                ),
+                style: Style::new().light_blue(),
+            },
+            RoomEvent::HistoryEnd => EventType::Info {
+                message: "end of history".to_string(),
+                style: Style::new().dark_gray(),
+            },
         }
     }
 }
@@ -5,7 +5,7 @@ use std::{
 };
 
 use anyhow::anyhow;
-use chat_lib::Version;
+use chat_lib::{Discovery, Encoding, Protocol, Version};
 use ratatui::widgets::{Block, Borders};
 use ratatui_textarea::TextArea;
 use tokio::sync::mpsc::channel;
@@ -13,11 +13,14 @@ use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    config::AppConfig,
-    consts::{CHANNEL_BUFFER_SIZE, CLIENT, FOCUSED_CURSOR_STYLE, UNFOCUSED_CURSOR_STYLE},
+    config::{AppConfig, RoomCredentials},
+    consts::{
+        CHANNEL_BUFFER_SIZE, CLIENT, FOCUSED_CURSOR_STYLE, SUPPORTED_API_VERSIONS,
+        UNFOCUSED_CURSOR_STYLE,
+    },
     requests::room_discovery,
     room::Room,
-    ws_handler::{WsAction, WsEvent, WsHandler},
+    ws_handler::{WsAction, WsEvent, WsHandler, WsRequest},
 };
 
 /// Wrapper around Url that checks if it's http(s)
@@ -93,16 +96,52 @@ pub fn apply_cursor_style(
     }
 }
 
+/// Picks the highest api version supported by both the server and the client
+///
+/// # Errors
+///
+/// This function errors if the server doesn't support any of the versions the client does
+pub fn negotiate_version(discovery: &Discovery) -> anyhow::Result<Version> {
+    Version::negotiate(SUPPORTED_API_VERSIONS, &discovery.supported_api_versions).ok_or_else(|| {
+        anyhow!(
+            "No common api version, the server supports {:?} while the client supports {:?}",
+            discovery.supported_api_versions,
+            SUPPORTED_API_VERSIONS
+        )
+    })
+}
+
+/// Returns the `preferred` encoding if the server supports it, json otherwise
+pub fn negotiate_encoding(discovery: &Discovery, preferred: Encoding) -> Encoding {
+    if discovery.supported_encodings.contains(&preferred) {
+        preferred
+    } else {
+        Encoding::Json
+    }
+}
+
+/// Picks the api version and encoding used to talk to the server
+///
+/// # Errors
+///
+/// This function errors if the server doesn't support any of the versions the client does
+pub fn negotiate_protocol(discovery: &Discovery, preferred: Encoding) -> anyhow::Result<Protocol> {
+    let version = negotiate_version(discovery)?;
+    let encoding = negotiate_encoding(discovery, preferred);
+
+    Ok(Protocol::new(version, encoding))
+}
+
 /// First tries to run a discover on `base_url`,
 ///  and then connect to the web socket via `connect_room_ws` if the server is valid
 ///
 /// # Errors
 ///
 /// This function errors if there was an error during discovery
+/// or if the server doesn't support any api version the client does
 pub async fn connect_room(
     config: &AppConfig,
     base_url: &Url,
-    version: Version,
     room_name: &str,
     name: Option<String>,
 ) -> anyhow::Result<(Room, tokio::task::JoinHandle<()>)> {
@@ -110,19 +149,30 @@ pub async fn connect_room(
 
     log::debug!("{base_url} - {discovery:?}");
 
-    Ok(connect_room_ws(config, base_url, version, room_name, name))
+    let protocol = negotiate_protocol(&discovery, config.web.encoding)?;
+    let credentials = config.web.credentials(base_url, room_name);
+
+    Ok(connect_room_ws(
+        config,
+        base_url,
+        protocol,
+        room_name,
+        name,
+        credentials,
+    ))
 }
 
 /// Connects to a room without checking if `base_url` houses a valid chat server
 pub fn connect_room_ws(
     config: &AppConfig,
     base_url: &Url,
-    version: Version,
+    protocol: Protocol,
     room_name: &str,
     name: Option<String>,
+    credentials: RoomCredentials,
 ) -> (Room, tokio::task::JoinHandle<()>) {
     let (e_tx, e_rx) = channel::<WsEvent>(CHANNEL_BUFFER_SIZE);
-    let (a_tx, a_rx) = sync_channel::<WsAction>(CHANNEL_BUFFER_SIZE);
+    let (a_tx, a_rx) = sync_channel::<WsRequest>(CHANNEL_BUFFER_SIZE);
 
     let config = config.clone();
     let room_string = room_name.to_string();
@@ -134,8 +184,9 @@ pub fn connect_room_ws(
             config.web,
             room_string.clone(),
             base_url,
-            version,
+            protocol,
             name,
+            credentials,
         )
         .await
         .inspect_err(|err| log::error!("Fatal error during websocket connection: {err}"));
@@ -151,10 +202,13 @@ pub fn connect_room_ws(
         log::debug!("Websocket handler for {room_string} ended");
     });
 
-    (Room::new(config.chat, room_name, a_tx, e_rx), ws)
+    let room = Room::new(config.chat, room_name, protocol.version, a_tx, e_rx);
+    (room, ws)
 }
 
 /// returns if the given event satisfies a given action (self id is required for actions related to self)
+///
+/// Only needed for servers that don't echo back the request ids
 pub fn event_satisfies_action(ev: &WsEvent, ac: &WsAction, self_id: Option<Uuid>) -> bool {
     match (ev, ac, self_id) {
         (WsEvent::SelfInfo(_), WsAction::RequestSelf, _)
@@ -166,6 +220,11 @@ pub fn event_satisfies_action(ev: &WsEvent, ac: &WsAction, self_id: Option<Uuid>
         {
             true
         }
+        (WsEvent::PresenceChanged { user, presence }, WsAction::SetPresence(p), Some(self_id))
+            if *user == self_id && presence == p =>
+        {
+            true
+        }
         _ => false,
     }
 }
@@ -174,6 +233,26 @@ pub fn event_satisfies_action(ev: &WsEvent, ac: &WsAction, self_id: Option<Uuid>
 pub fn action_should_buffer(ac: &WsAction) -> bool {
     match ac {
         WsAction::RequestUser(_) | WsAction::RequestAll | WsAction::RequestSelf => true,
-        WsAction::ChangeName(_) | WsAction::Message(_) | WsAction::Quit => false,
+        WsAction::ChangeName(_)
+        | WsAction::Message(_)
+        | WsAction::EditMessage { .. }
+        | WsAction::DeleteMessage(_)
+        | WsAction::DirectMessage { .. }
+        | WsAction::Reply { .. }
+        | WsAction::Typing(_)
+        | WsAction::AddReaction { .. }
+        | WsAction::RemoveReaction { .. }
+        | WsAction::Kick(_)
+        | WsAction::Mute { .. }
+        | WsAction::Ban { .. }
+        | WsAction::SetModerator { .. }
+        | WsAction::SetFilterPolicy(_)
+        | WsAction::SetTopic(_)
+        | WsAction::SetDescription(_)
+        | WsAction::SetPassword(_)
+        | WsAction::SetInviteOnly(_)
+        | WsAction::CreateInvite { .. }
+        | WsAction::SetPresence(_)
+        | WsAction::Quit => false,
     }
 }
@@ -12,6 +12,7 @@ mod notifications;
 mod requests;
 mod room;
 mod task;
+pub mod tls;
 mod ws_handler;
 
 use std::io;
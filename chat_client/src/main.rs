@@ -14,6 +14,7 @@ fn main() -> anyhow::Result<()> {
     // or it will pollute the logs with the help messages
     let (config, cli) = config::init();
     logging::setup()?;
+    chat_client::tls::init(config.web.ca_cert.as_deref())?;
 
     // Unwrapping the runtime initialization so clap can exit without messing with it
     tokio::runtime::Builder::new_multi_thread()
@@ -1,4 +1,11 @@
-use chat_lib::{discovery::Discovery, types::User};
+use anyhow::anyhow;
+use chat_lib::{
+    RoomInfo, Version,
+    auth::{Credentials, Session},
+    discovery::Discovery,
+    room::RoomListing,
+    types::User,
+};
 use reqwest::Client;
 use url::Url;
 
@@ -9,14 +16,27 @@ use url::Url;
 /// # Panics
 ///
 /// This function panics if the url can't be joined
-pub async fn room_ls(client: &Client, url: &Url, room: &str) -> Result<Vec<User>, reqwest::Error> {
+pub async fn room_ls(
+    client: &Client,
+    url: &Url,
+    version: Version,
+    room: &str,
+) -> Result<RoomListing, reqwest::Error> {
     let url = url
-        .join(&format!("room/{room}/ls"))
+        .join(&format!("{version}/room/{room}/ls"))
         .expect("The url should be correct");
 
     log::debug!("Running ls on {url}");
 
-    client.get(url).send().await?.json::<Vec<User>>().await
+    let res = client.get(url).send().await?;
+    match version {
+        // only the users are listed
+        Version::V1 => Ok(RoomListing {
+            info: RoomInfo::new(room.to_string()),
+            users: res.json::<Vec<User>>().await?,
+        }),
+        _ => res.json::<RoomListing>().await,
+    }
 }
 
 /// # Errors
@@ -33,3 +53,38 @@ pub async fn room_discovery(client: &Client, url: &Url) -> Result<Discovery, req
 
     client.get(url).send().await?.json::<Discovery>().await
 }
+
+/// Registers the account, or logs in to it if `register` is false
+///
+/// # Errors
+///
+/// This function errors if the request fails or the server rejects it,
+/// the server's reason is the error message
+///
+/// # Panics
+///
+/// This function panics if the url can't be joined
+pub async fn authenticate(
+    client: &Client,
+    url: &Url,
+    credentials: &Credentials,
+    register: bool,
+) -> anyhow::Result<Session> {
+    let path = if register {
+        "auth/register"
+    } else {
+        "auth/login"
+    };
+    let url = url.join(path).expect("The url should be correct");
+
+    log::debug!("Authenticating at {url}");
+
+    let res = client.post(url).json(credentials).send().await?;
+    if !res.status().is_success() {
+        let status = res.status();
+        let reason = res.text().await.unwrap_or_default();
+        return Err(anyhow!("{status}: {reason}"));
+    }
+
+    Ok(res.json::<Session>().await?)
+}
@@ -5,7 +5,12 @@ use std::{
     time::{Duration, Instant},
 };
 
-use chat_lib::types::{Message, User};
+use chat_lib::{
+    RoomInfo, Version,
+    filter::{FilterMode, FilterPolicy},
+    types::{Message, MessageId, Presence, Reaction, RequestId, User},
+};
+use chrono::{DateTime, Local, Utc};
 use ringbuffer::{AllocRingBuffer, RingBuffer};
 use tokio::sync::mpsc::Receiver;
 use uuid::Uuid;
@@ -13,12 +18,26 @@ use uuid::Uuid;
 use crate::{
     chat::Offset,
     config::ChatConfig,
-    consts::ACTION_LIFETIME,
+    consts::{REQUEST_TIMEOUT, TYPING_THROTTLE, TYPING_TIMEOUT},
     event::{RoomEvent, UserLocator},
     helper::{action_should_buffer, event_satisfies_action},
-    ws_handler::{WsAction, WsEvent},
+    ws_handler::{WsAction, WsEvent, WsRequest},
 };
 
+/// Follows the replies up to the first message of the thread
+fn thread_root(parents: &HashMap<MessageId, MessageId>, id: MessageId) -> MessageId {
+    let mut root = id;
+    // bounded in case the replies somehow form a cycle
+    for _ in 0..=parents.len() {
+        match parents.get(&root) {
+            Some(parent) => root = *parent,
+            None => break,
+        }
+    }
+
+    root
+}
+
 fn rel_to_abs(rel: usize, n: u32) -> u32 {
     #[allow(clippy::cast_possible_truncation)]
     let rel = (rel as u32).saturating_sub(1);
@@ -39,14 +58,34 @@ pub struct Room {
     users: HashMap<Uuid, User>,
     active_users: HashSet<Uuid>,
     scoll_offset: Option<Offset>,
+    /// The message selected for actions like editing
+    selected: Option<MessageId>,
+    /// The user the sent text goes to privately, instead of the whole room
+    dm_target: Option<Uuid>,
+    /// The users who are typing and when they last said so
+    typing: HashMap<Uuid, Instant>,
+    /// When this client last told the server it's typing
+    typing_sent_at: Option<Instant>,
+    reactions: HashMap<MessageId, Vec<Reaction>>,
+    /// The first message of the thread being viewed, only its events are shown
+    thread: Option<MessageId>,
+    owner: Option<Uuid>,
+    moderators: HashSet<Uuid>,
+    filter: Option<FilterPolicy>,
+    info: Option<RoomInfo>,
     name: String,
-    tx: SyncSender<WsAction>,
+    /// The api version of the connection, only V1 doesn't echo the request ids back
+    version: Version,
+    /// Whether the user is idle, applied once the server says who this client is
+    idle: bool,
+    tx: SyncSender<WsRequest>,
     rx: Receiver<WsEvent>,
     pending_requests: VecDeque<WsAction>,
     timeout_until: Option<Instant>,
-    /// # Action queue
-    /// Where the key is the action and the value is when it was sent
-    active_requests: HashMap<WsAction, Instant>,
+    next_request_id: RequestId,
+    /// # In-flight requests
+    /// Where the key is the request id and the value is the action and when it was sent
+    active_requests: HashMap<RequestId, (WsAction, Instant)>,
     state: RoomState,
 
     #[allow(unused)]
@@ -66,7 +105,8 @@ impl Room {
     pub fn new(
         config: ChatConfig,
         name: &str,
-        tx: SyncSender<WsAction>,
+        version: Version,
+        tx: SyncSender<WsRequest>,
         rx: Receiver<WsEvent>,
     ) -> Self {
         Self {
@@ -76,8 +116,21 @@ impl Room {
             users: HashMap::new(),
             self_id: None,
             scoll_offset: None,
+            selected: None,
+            dm_target: None,
+            typing: HashMap::new(),
+            typing_sent_at: None,
+            reactions: HashMap::new(),
+            thread: None,
+            owner: None,
+            moderators: HashSet::new(),
+            filter: None,
+            info: None,
             name: name.to_string(),
+            version,
+            idle: false,
             pending_requests: VecDeque::new(),
+            next_request_id: 0,
             active_requests: HashMap::new(),
             state: RoomState::Pending,
             timeout_until: None,
@@ -110,8 +163,114 @@ impl Room {
         self.scoll_offset
     }
 
+    pub fn selected(&self) -> Option<&MessageId> {
+        self.selected.as_ref()
+    }
+
+    /// The selected message, if it still exists
+    pub fn selected_message(&self) -> Option<&Message> {
+        self.find_message(self.selected?)
+    }
+
+    pub fn find_message(&self, id: MessageId) -> Option<&Message> {
+        self.events.iter().find_map(|ev| match ev {
+            RoomEvent::Message(msg) if msg.get_id() == Some(&id) => Some(msg),
+            _ => None,
+        })
+    }
+
+    /// The event of the message, which is either the message or its tombstone
+    pub fn find_message_event(&self, id: MessageId) -> Option<&RoomEvent> {
+        self.events.iter().find(|ev| ev.message_id() == Some(&id))
+    }
+
+    /// The first message of the thread being viewed
+    pub fn thread(&self) -> Option<MessageId> {
+        self.thread
+    }
+
+    /// Shows only the thread of the selected message, or everything if a thread is already shown
+    pub fn toggle_thread(&mut self) {
+        self.thread = if self.thread.is_some() {
+            None
+        } else {
+            let Some(id) = self.selected else {
+                return;
+            };
+            Some(thread_root(&self.parents(), id))
+        };
+        self.selected = None;
+        self.scoll_offset = None;
+    }
+
+    /// Which message each message replies to
+    fn parents(&self) -> HashMap<MessageId, MessageId> {
+        self.events
+            .iter()
+            .filter_map(|ev| Some((*ev.message_id()?, *ev.reply_to()?)))
+            .collect()
+    }
+
+    /// The events that are shown, which are only the ones of the thread if one is viewed
+    fn visible_events(&self) -> Vec<&RoomEvent> {
+        let Some(root) = self.thread else {
+            return self.events.iter().collect();
+        };
+
+        let parents = self.parents();
+        self.events
+            .iter()
+            .filter(|ev| {
+                ev.message_id()
+                    .is_some_and(|id| thread_root(&parents, *id) == root)
+            })
+            .collect()
+    }
+
+    fn visible_len(&self) -> usize {
+        if self.thread.is_some() {
+            self.visible_events().len()
+        } else {
+            self.events.len()
+        }
+    }
+
+    /// Whether the message was sent by this client
+    pub fn is_own_message(&self, msg: &Message) -> bool {
+        self.self_id == Some(*msg.get_author())
+    }
+
+    pub fn reactions(&self) -> &HashMap<MessageId, Vec<Reaction>> {
+        &self.reactions
+    }
+
+    /// Adds the reaction to the selected message, or removes it if it was already added
+    pub fn toggle_reaction(&mut self, reaction: &str) {
+        let reaction = reaction.trim();
+        let Some(id) = self.selected_message().and_then(|m| m.get_id()).copied() else {
+            return;
+        };
+        if reaction.is_empty() {
+            return;
+        }
+
+        let reacted = self.self_id.is_some_and(|self_id| {
+            self.reactions.get(&id).is_some_and(|reactions| {
+                reactions
+                    .iter()
+                    .any(|r| r.get_reaction() == reaction && r.get_users().contains(&self_id))
+            })
+        });
+        let reaction = reaction.to_string();
+        if reacted {
+            self.send_action(WsAction::RemoveReaction { id, reaction });
+        } else {
+            self.send_action(WsAction::AddReaction { id, reaction });
+        }
+    }
+
     pub fn events(&self) -> Vec<RoomEvent> {
-        self.events.to_vec()
+        self.visible_events().into_iter().cloned().collect()
     }
 
     pub fn self_user(&self) -> Option<&User> {
@@ -124,13 +283,16 @@ impl Room {
 
     pub fn poll_pending_events(&mut self) {
         while let Ok(event) = self.rx.try_recv() {
-            self.active_requests
-                .retain(|k, _| !event_satisfies_action(&event, k, self.self_id));
+            // newer versions reply with the request id, which settles the request exactly
+            if self.version == Version::V1 {
+                self.active_requests
+                    .retain(|_, (action, _)| !event_satisfies_action(&event, action, self.self_id));
+            }
             self.handle_event(event);
         }
 
         self.active_requests
-            .retain(|_, v| v.elapsed() < ACTION_LIFETIME);
+            .retain(|_, (_, sent_at)| sent_at.elapsed() < REQUEST_TIMEOUT);
     }
 
     pub fn sync(&mut self) {
@@ -161,13 +323,69 @@ impl Room {
         self.send_action(WsAction::Quit);
     }
 
+    /// Sends the text to the room, or privately to the dm target if there is one
     pub fn send_text(&mut self, text: &str) {
         let text = text.trim();
-        if text.chars().count() > 0 {
+        if text.chars().count() == 0 {
+            return;
+        }
+
+        // the server stops the typing indicator on its own once the message arrives
+        self.typing_sent_at = None;
+        if let Some(to) = self.dm_target {
+            self.send_action(WsAction::DirectMessage {
+                to,
+                content: text.to_string(),
+            });
+        } else if let Some(to) = self.thread {
+            self.send_action(WsAction::Reply {
+                to,
+                content: text.to_string(),
+            });
+        } else {
             self.send_action(WsAction::Message(text.to_string()));
         }
     }
 
+    /// Tells the server whether this client is typing, throttled to not flood the room
+    pub fn notify_typing(&mut self, typing: bool) {
+        // the room shouldn't know about direct messages being written
+        let typing = typing && self.dm_target.is_none();
+        match (typing, self.typing_sent_at) {
+            (true, Some(t)) if t.elapsed() < TYPING_THROTTLE => {}
+            (true, _) => {
+                self.typing_sent_at = Some(Instant::now());
+                self.send_action(WsAction::Typing(true));
+            }
+            (false, Some(_)) => {
+                self.typing_sent_at = None;
+                self.send_action(WsAction::Typing(false));
+            }
+            (false, None) => {}
+        }
+    }
+
+    /// Everyone else who is typing right now
+    pub fn typing_users(&self) -> Vec<&User> {
+        let mut users = self
+            .typing
+            .iter()
+            .filter(|(id, t)| Some(**id) != self.self_id && t.elapsed() < TYPING_TIMEOUT)
+            .filter_map(|(id, _)| self.get_user(*id))
+            .collect::<Vec<_>>();
+        users.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        users
+    }
+
+    pub fn dm_target(&self) -> Option<&User> {
+        self.dm_target.and_then(|id| self.get_user(id))
+    }
+
+    pub fn set_dm_target(&mut self, id: Option<Uuid>) {
+        self.dm_target = id;
+    }
+
     pub fn change_name(&mut self, name: &str) {
         let name = name.trim();
         if name.chars().count() > 0 {
@@ -175,6 +393,220 @@ impl Room {
         }
     }
 
+    pub fn edit_message(&mut self, id: MessageId, content: &str) {
+        let content = content.trim();
+        if content.chars().count() > 0 {
+            self.send_action(WsAction::EditMessage {
+                id,
+                content: content.to_string(),
+            });
+        }
+    }
+
+    pub fn reply(&mut self, to: MessageId, content: &str) {
+        let content = content.trim();
+        if content.chars().count() > 0 {
+            self.typing_sent_at = None;
+            self.send_action(WsAction::Reply {
+                to,
+                content: content.to_string(),
+            });
+        }
+    }
+
+    pub fn delete_message(&mut self, id: MessageId) {
+        self.send_action(WsAction::DeleteMessage(id));
+    }
+
+    pub fn is_owner(&self, id: Uuid) -> bool {
+        self.owner == Some(id)
+    }
+
+    pub fn is_moderator(&self, id: Uuid) -> bool {
+        self.is_owner(id) || self.moderators.contains(&id)
+    }
+
+    /// Whether this client can sanction the user, following the same rules as the server
+    pub fn can_sanction(&self, id: Uuid) -> bool {
+        self.self_id.is_some_and(|self_id| {
+            self_id != id
+                && self.is_moderator(self_id)
+                && !self.is_owner(id)
+                && (self.is_owner(self_id) || !self.is_moderator(id))
+        })
+    }
+
+    /// Whether this client owns the room, only the owner can appoint moderators
+    pub fn self_is_owner(&self) -> bool {
+        self.self_id.is_some_and(|id| self.is_owner(id))
+    }
+
+    pub fn kick(&mut self, user: Uuid) {
+        self.send_action(WsAction::Kick(user));
+    }
+
+    /// Mutes the user for the duration, a zero duration lifts the mute
+    pub fn mute(&mut self, user: Uuid, duration: Duration) {
+        self.send_action(WsAction::Mute {
+            user,
+            duration: duration.as_secs(),
+        });
+    }
+
+    /// Bans the user for the duration, a zero duration lifts the ban
+    pub fn ban(&mut self, user: Uuid, duration: Duration) {
+        self.send_action(WsAction::Ban {
+            user,
+            duration: duration.as_secs(),
+        });
+    }
+
+    pub fn set_moderator(&mut self, user: Uuid, moderator: bool) {
+        self.send_action(WsAction::SetModerator { user, moderator });
+    }
+
+    /// How the room filters messages, if the server told it already
+    pub fn filter(&self) -> Option<&FilterPolicy> {
+        self.filter.as_ref()
+    }
+
+    /// What the room is about, if the server told it already
+    pub fn info(&self) -> Option<&RoomInfo> {
+        self.info.as_ref()
+    }
+
+    pub fn topic(&self) -> Option<&str> {
+        self.info.as_ref()?.topic.as_deref()
+    }
+
+    /// Changes the room's topic, an empty one removes it
+    pub fn set_topic(&mut self, topic: &str) {
+        self.send_action(WsAction::SetTopic(topic.trim().to_string()));
+    }
+
+    /// The presence of this client's user, if the server told it already
+    pub fn self_presence(&self) -> Option<&Presence> {
+        self.self_user().map(User::get_presence)
+    }
+
+    pub fn set_presence(&mut self, presence: Presence) {
+        self.send_action(WsAction::SetPresence(presence));
+    }
+
+    /// Marks the user away while they're idle and online again once they're back,
+    /// a presence they chose is kept
+    pub fn set_idle(&mut self, idle: bool) {
+        self.idle = idle;
+        let (from, to) = if idle {
+            (Presence::Online, Presence::Away)
+        } else {
+            (Presence::Away, Presence::Online)
+        };
+        if self.self_presence() == Some(&from) {
+            self.set_presence(to);
+        }
+    }
+
+    /// Sets a custom status, an empty one goes back to online
+    pub fn set_status(&mut self, status: &str) {
+        let status = status.trim();
+        if status.is_empty() {
+            self.set_presence(Presence::Online);
+        } else {
+            self.set_presence(Presence::Custom(status.to_string()));
+        }
+    }
+
+    /// Protects the room with the password, an empty one opens the room again
+    pub fn set_password(&mut self, password: &str) {
+        self.send_action(WsAction::SetPassword(password.to_string()));
+    }
+
+    pub fn set_invite_only(&mut self, invite_only: bool) {
+        self.send_action(WsAction::SetInviteOnly(invite_only));
+    }
+
+    /// Creates an invite code valid for the duration, the server tells it back
+    pub fn create_invite(&mut self, duration: Duration) {
+        self.send_action(WsAction::CreateInvite {
+            duration: duration.as_secs(),
+        });
+    }
+
+    /// Switches the room's filter to the next mode, keeping its word lists
+    pub fn cycle_filter_mode(&mut self) {
+        let Some(policy) = self.filter.clone() else {
+            return;
+        };
+        let mode = match policy.mode {
+            FilterMode::Censor => FilterMode::Reject,
+            FilterMode::Reject => FilterMode::Off,
+            FilterMode::Off => FilterMode::Censor,
+        };
+        self.send_action(WsAction::SetFilterPolicy(policy.with_mode(mode)));
+    }
+
+    /// Selects the message before the selected one, or the last message if nothing is selected
+    pub fn select_previous(&mut self) {
+        let ids = self.message_ids();
+        let previous = match self.selected {
+            Some(id) => ids
+                .iter()
+                .position(|i| *i == id)
+                .and_then(|pos| pos.checked_sub(1))
+                .map_or(Some(id), |pos| ids.get(pos).copied()),
+            None => ids.last().copied(),
+        };
+        self.select(previous);
+    }
+
+    /// Selects the message after the selected one, going past the last one clears the selection
+    pub fn select_next(&mut self) {
+        let Some(id) = self.selected else {
+            return;
+        };
+        let ids = self.message_ids();
+        let next = ids
+            .iter()
+            .position(|i| *i == id)
+            .and_then(|pos| ids.get(pos + 1))
+            .copied();
+        self.select(next);
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected = None;
+    }
+
+    /// Selects the message and scrolls so it's the last one shown
+    fn select(&mut self, id: Option<MessageId>) {
+        self.selected = id;
+
+        let Some(id) = id else {
+            self.scoll_offset = None;
+            return;
+        };
+        let after = self
+            .visible_events()
+            .iter()
+            .rev()
+            .position(|ev| ev.message_id() == Some(&id));
+        #[allow(clippy::cast_possible_truncation)]
+        if let Some(after) = after {
+            self.scoll_offset = NonZero::new(after as u32).map(Offset::Relative);
+        }
+    }
+
+    fn message_ids(&self) -> Vec<MessageId> {
+        self.visible_events()
+            .into_iter()
+            .filter_map(|ev| match ev {
+                RoomEvent::Message(msg) => msg.get_id().copied(),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn scroll_up(&mut self) {
         match self.scoll_offset {
             None => {
@@ -189,7 +621,7 @@ impl Room {
             #[allow(clippy::cast_possible_truncation)]
             Some(Offset::Relative(n)) => {
                 let offset = n.saturating_add(1);
-                self.scoll_offset = NonZero::new(self.events.len() as u32)
+                self.scoll_offset = NonZero::new(self.visible_len() as u32)
                     .map(|ev| offset.min(ev))
                     .map(Offset::Relative);
             }
@@ -201,7 +633,7 @@ impl Room {
             None => {}
             #[allow(clippy::cast_possible_truncation)]
             Some(Offset::Absolute(n)) => {
-                let offset = n.saturating_add(1).min(self.events.len() as u32);
+                let offset = n.saturating_add(1).min(self.visible_len() as u32);
                 self.scoll_offset = Some(Offset::Absolute(offset));
             }
             Some(Offset::Relative(n)) => {
@@ -220,14 +652,14 @@ impl Room {
         match self.scoll_offset {
             Some(offset) => match offset {
                 Offset::Absolute(n) => {
-                    let rel = abs_to_rel(self.events.len(), n);
+                    let rel = abs_to_rel(self.visible_len(), n);
                     match rel {
                         Some(rel) => self.scoll_offset = Some(Offset::Relative(rel)),
                         None => self.scoll_offset = None,
                     }
                 }
                 Offset::Relative(n) => {
-                    let event_count = rel_to_abs(self.events.len(), n.get());
+                    let event_count = rel_to_abs(self.visible_len(), n.get());
                     let abs = event_count - n.get().min(event_count);
                     if abs == 0 {
                         self.scoll_offset = None;
@@ -238,7 +670,7 @@ impl Room {
             },
             None => {
                 self.scoll_offset = Some(Offset::Absolute(
-                    (self.events.len() as u32).saturating_sub(1),
+                    (self.visible_len() as u32).saturating_sub(1),
                 ));
             }
         }
@@ -260,6 +692,28 @@ impl Room {
             WsEvent::Message(message) => {
                 self.add_message(message);
             }
+            WsEvent::ReactionsChanged { id, reactions } => {
+                self.set_reactions(id, reactions);
+            }
+            WsEvent::UserTyping { user, typing } => {
+                if typing {
+                    self.typing.insert(user, Instant::now());
+                } else {
+                    self.typing.remove(&user);
+                }
+            }
+            WsEvent::DirectMessage { to, message } => {
+                self.add_event(RoomEvent::DirectMessage { to, message });
+            }
+            WsEvent::MessageEdited(message) => {
+                self.replace_message(message);
+            }
+            WsEvent::MessageDeleted(id) => {
+                self.delete_local_message(id);
+            }
+            WsEvent::History { users, events } => {
+                self.replay_history(users, events);
+            }
             WsEvent::Quit => {
                 self.quit();
             }
@@ -289,6 +743,40 @@ impl Room {
                 let room_name = &self.name;
                 crate::notif_warn!("room({room_name}): {err}");
             }
+            WsEvent::Replied(id) => {
+                self.active_requests.remove(&id);
+            }
+            WsEvent::ConnectionLost => {
+                let room_name = &self.name;
+                crate::notif_warn!("room({room_name}): Connection lost, reconnecting");
+            }
+            WsEvent::Reconnected => {
+                let room_name = &self.name;
+                crate::notif_info!("room({room_name}): Reconnected");
+            }
+            WsEvent::Moderators { owner, moderators } => {
+                self.owner = owner;
+                self.moderators = moderators.into_iter().collect();
+            }
+            WsEvent::Sanctioned { user, by, sanction } => {
+                self.add_event(RoomEvent::Sanctioned { user, by, sanction });
+            }
+            WsEvent::Announcement(msg) => {
+                self.add_event(RoomEvent::Announcement(msg));
+            }
+            WsEvent::FilterPolicy(policy) => {
+                self.filter = Some(policy);
+            }
+            WsEvent::RoomInfo(info) => {
+                self.set_info(info);
+            }
+            WsEvent::InviteCreated { code, expires_at } => self.show_invite(&code, expires_at),
+            WsEvent::PresenceChanged { user, presence } => self.change_presence(user, presence),
+            WsEvent::Muted(until) => {
+                let room_name = &self.name;
+                let until = until.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+                crate::notif_warn!("room({room_name}): You're muted until {until}");
+            }
         }
     }
 
@@ -325,19 +813,22 @@ impl Room {
     }
 
     // TODO: Handle the errors gracefully
-    /// returns true if the action was sent,
-    /// false if the action is already in the queue
+    /// Sends the action, unless it should be buffered and the same action is already in-flight
     fn send_action(&mut self, action: WsAction) {
         let buffer = action_should_buffer(&action);
 
-        if !buffer || !self.active_requests.contains_key(&action) {
-            log::debug!("Sending action {action:?}");
-            let _ = self.tx.send(action.clone());
+        if buffer && self.active_requests.values().any(|(a, _)| *a == action) {
+            return;
+        }
 
-            if buffer {
-                let now = Instant::now();
-                self.active_requests.insert(action, now);
-            }
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+
+        log::debug!("Sending action {action:?} as request {id}");
+        let _ = self.tx.send(WsRequest::new(Some(id), action.clone()));
+
+        if buffer {
+            self.active_requests.insert(id, (action, Instant::now()));
         }
     }
 
@@ -349,8 +840,48 @@ impl Room {
         );
     }
 
+    /// Adds the events that happened before joining,
+    /// the users they reference are known but not considered in the room
+    fn replay_history(&mut self, users: Vec<User>, events: Vec<WsEvent>) {
+        if events.is_empty() {
+            return;
+        }
+
+        for user in users {
+            self.users.entry(*user.get_id()).or_insert(user);
+        }
+
+        for event in events {
+            match event {
+                WsEvent::Message(message) => self.add_message(message),
+                WsEvent::UserAdd(user) => self.add_event(RoomEvent::UserJoined(*user.get_id())),
+                WsEvent::UserRemove(id) => self.add_event(RoomEvent::UserLeft(id)),
+                WsEvent::ReactionsChanged { id, reactions } => self.set_reactions(id, reactions),
+                ev => log::warn!("Unexpected event in the history: {ev:?}"),
+            }
+        }
+
+        self.add_event(RoomEvent::HistoryEnd);
+    }
+
+    fn show_invite(&self, code: &str, expires_at: DateTime<Utc>) {
+        let room_name = &self.name;
+        let expires_at = expires_at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        crate::notif_info!("room({room_name}): Invite code {code}, valid until {expires_at}");
+    }
+
+    fn set_info(&mut self, info: RoomInfo) {
+        // the first one is just what the room was like when joining
+        if let Some(old) = &self.info
+            && old.topic != info.topic
+        {
+            self.add_event(RoomEvent::TopicChanged(info.topic.clone()));
+        }
+        self.info = Some(info);
+    }
+
     fn add_user(&mut self, user: User) -> bool {
-        if self.users.contains_key(user.get_id()) {
+        if self.active_users.contains(user.get_id()) {
             true
         } else {
             self.add_event(RoomEvent::UserJoined(*user.get_id()));
@@ -370,13 +901,31 @@ impl Room {
 
     fn remove_user(&mut self, id: Uuid) {
         // do not remove the user to keep all the references alive
-        self.active_users.remove(&id);
+        if !self.active_users.remove(&id) {
+            // a resumed session replayed a leave that was already seen
+            return;
+        }
+        self.typing.remove(&id);
+        if self.dm_target == Some(id) {
+            self.dm_target = None;
+        }
         self.add_event(RoomEvent::UserLeft(id));
     }
 
     fn set_self(&mut self, usr: User) {
+        let known = self.self_id.is_some();
         self.self_id = Some(*usr.get_id());
         self.set_user(usr);
+        // the user went idle before the room knew who they are
+        if !known && self.idle {
+            self.set_idle(true);
+        }
+    }
+
+    fn change_presence(&mut self, id: Uuid, presence: Presence) {
+        if let Some(user) = self.users.get_mut(&id) {
+            user.set_presence(presence);
+        }
     }
 
     fn change_user_name(&mut self, usr: &User) {
@@ -394,10 +943,58 @@ impl Room {
             .and_modify(|u| u.set_name(usr.get_name().to_string()));
     }
 
+    /// Adds the message, unless a resumed session replayed one that's already there
     fn add_message(&mut self, msg: Message) {
+        if let Some(id) = msg.get_id()
+            && self.message_event_mut(*id).is_some()
+        {
+            return;
+        }
         self.add_event(msg);
     }
 
+    /// Replaces the message with the same id in place
+    fn replace_message(&mut self, message: Message) {
+        let Some(id) = message.get_id().copied() else {
+            return;
+        };
+        if let Some(ev) = self.message_event_mut(id) {
+            *ev = RoomEvent::Message(message);
+        }
+    }
+
+    fn set_reactions(&mut self, id: MessageId, reactions: Vec<Reaction>) {
+        if reactions.is_empty() {
+            self.reactions.remove(&id);
+        } else {
+            self.reactions.insert(id, reactions);
+        }
+    }
+
+    /// Replaces the message with a tombstone
+    fn delete_local_message(&mut self, id: MessageId) {
+        self.reactions.remove(&id);
+        if self.selected == Some(id) {
+            self.selected = None;
+        }
+
+        if let Some(ev) = self.message_event_mut(id)
+            && let RoomEvent::Message(msg) = ev
+        {
+            *ev = RoomEvent::Deleted {
+                id,
+                author: *msg.get_author(),
+                reply_to: msg.get_reply_to().copied(),
+            };
+        }
+    }
+
+    fn message_event_mut(&mut self, id: MessageId) -> Option<&mut RoomEvent> {
+        self.events
+            .iter_mut()
+            .find(|ev| matches!(ev, RoomEvent::Message(msg) if msg.get_id() == Some(&id)))
+    }
+
     fn add_event(&mut self, ev: impl Into<RoomEvent>) {
         self.events.enqueue(ev.into());
     }
@@ -0,0 +1,88 @@
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::Context;
+use reqwest::{Certificate, Client};
+use rustls::{
+    ClientConfig,
+    crypto::aws_lc_rs,
+    pki_types::{CertificateDer, pem::PemObject},
+};
+use rustls_platform_verifier::Verifier;
+use tokio_tungstenite::Connector;
+
+/// The certificates trusted on top of the system's, and the websocket tls config that trusts them
+struct ExtraRoots {
+    certs: Vec<CertificateDer<'static>>,
+    ws: Arc<ClientConfig>,
+}
+
+static EXTRA_ROOTS: OnceLock<ExtraRoots> = OnceLock::new();
+
+/// Sets up the crypto of the tls connections,
+/// the PEM certificates in `ca_cert` are trusted on top of the system's,
+/// so a server with a self signed certificate can be used
+///
+/// # Errors
+///
+/// This function errors if the certificates can't be read
+pub fn init(ca_cert: Option<&Path>) -> anyhow::Result<()> {
+    // the websocket's default tls config needs a process wide provider
+    let _ = aws_lc_rs::default_provider().install_default();
+
+    let Some(path) = ca_cert else {
+        return Ok(());
+    };
+
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .with_context(|| format!("Couldn't read the ca certificate {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("There are no certificates in {}", path.display());
+    }
+
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let verifier = Verifier::new_with_extra_roots(certs.clone(), provider.clone())?;
+    let ws = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    let _ = EXTRA_ROOTS.set(ExtraRoots {
+        certs,
+        ws: Arc::new(ws),
+    });
+
+    Ok(())
+}
+
+/// The client all the http requests are sent with
+pub(crate) fn http_client() -> Client {
+    let Some(roots) = EXTRA_ROOTS.get() else {
+        return Client::new();
+    };
+
+    roots
+        .certs
+        .iter()
+        .filter_map(|cert| Certificate::from_der(cert).ok())
+        .fold(
+            Client::builder(),
+            reqwest::ClientBuilder::add_root_certificate,
+        )
+        .build()
+        .unwrap_or_else(|err| {
+            log::error!("Couldn't trust the ca certificate for http requests: {err}");
+            Client::new()
+        })
+}
+
+/// The tls connector of the websocket, the system's certificates are used if it's `None`
+pub(crate) fn ws_connector() -> Option<Connector> {
+    EXTRA_ROOTS
+        .get()
+        .map(|roots| Connector::Rustls(roots.ws.clone()))
+}
@@ -1,19 +1,33 @@
 use std::{
     sync::mpsc::{Receiver, TryRecvError},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, anyhow};
-use chat_lib::{Version, prelude::*, ws_connection::WsConnection};
+use chat_lib::{
+    Encoding, Protocol, RoomInfo,
+    auth::{RESUME_AFTER_HEADER, RESUME_TOKEN_HEADER, ROOM_INVITE_HEADER, ROOM_PASSWORD_HEADER},
+    filter::FilterPolicy,
+    prelude::*,
+    types::{MessageId, Presence, Reaction, RequestId, Sanction},
+    ws_connection::WsConnection,
+};
+use chrono::{DateTime, Utc};
 use futures::{SinkExt, StreamExt};
 use tokio::sync::mpsc::Sender;
-use tokio_tungstenite::{connect_async, tungstenite};
-use url::{Url, form_urlencoded};
+use tokio_tungstenite::{
+    connect_async_tls_with_config,
+    tungstenite::{
+        self, client::IntoClientRequest, handshake::client::Request, http::header::AUTHORIZATION,
+    },
+};
+use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    config::file::WebConfig,
-    consts::{TICK_DURATION, WS_TIMEOUT_DURATION},
+    config::{RoomCredentials, WebConfig},
+    consts::{RECONNECT_INTERVAL, TICK_DURATION, WS_TIMEOUT_DURATION},
+    tls,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -25,6 +39,27 @@ pub enum WsEvent {
     UserChange(User),
     UserRemove(Uuid),
     Message(Message),
+    MessageEdited(Message),
+    MessageDeleted(MessageId),
+    /// A message only the author and `to` can see
+    DirectMessage {
+        to: Uuid,
+        message: Message,
+    },
+    UserTyping {
+        user: Uuid,
+        typing: bool,
+    },
+    /// All the reactions of the message
+    ReactionsChanged {
+        id: MessageId,
+        reactions: Vec<Reaction>,
+    },
+    /// Events that happened before joining, with the users they reference
+    History {
+        users: Vec<User>,
+        events: Vec<WsEvent>,
+    },
     Banned(Duration, String),
     /// The amount of timeout added in seconds
     TimeoutAdded(u64),
@@ -34,6 +69,42 @@ pub enum WsEvent {
     SoftError(String),
     /// A fatal error
     FatalError(String),
+    /// The server finished replying to the request with the given id,
+    /// sent after the events the reply caused
+    Replied(RequestId),
+    /// The connection dropped, the handler tries to resume the session
+    ConnectionLost,
+    /// The session was resumed after the connection dropped,
+    /// the messages sent meanwhile are replayed
+    Reconnected,
+    /// Who can moderate the room
+    Moderators {
+        owner: Option<Uuid>,
+        moderators: Vec<Uuid>,
+    },
+    /// A moderator sanctioned the user
+    Sanctioned {
+        user: Uuid,
+        by: Uuid,
+        sanction: Sanction,
+    },
+    /// This client can't send anything to the room until the time
+    Muted(DateTime<Utc>),
+    /// How the room filters messages
+    FilterPolicy(FilterPolicy),
+    /// A message from the server's operators
+    Announcement(String),
+    /// What the room is about
+    RoomInfo(RoomInfo),
+    /// A code that lets anyone join the room until it expires
+    InviteCreated {
+        code: String,
+        expires_at: DateTime<Utc>,
+    },
+    PresenceChanged {
+        user: Uuid,
+        presence: Presence,
+    },
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -43,19 +114,95 @@ pub enum WsAction {
     RequestUser(Uuid),
     RequestAll,
     RequestSelf,
+    EditMessage {
+        id: MessageId,
+        content: String,
+    },
+    DeleteMessage(MessageId),
+    DirectMessage {
+        to: Uuid,
+        content: String,
+    },
+    Reply {
+        to: MessageId,
+        content: String,
+    },
+    Typing(bool),
+    AddReaction {
+        id: MessageId,
+        reaction: String,
+    },
+    RemoveReaction {
+        id: MessageId,
+        reaction: String,
+    },
+    Kick(Uuid),
+    /// Mutes the user for `duration` seconds, 0 lifts the mute
+    Mute {
+        user: Uuid,
+        duration: u64,
+    },
+    /// Bans the user for `duration` seconds, 0 lifts the ban
+    Ban {
+        user: Uuid,
+        duration: u64,
+    },
+    SetModerator {
+        user: Uuid,
+        moderator: bool,
+    },
+    SetFilterPolicy(FilterPolicy),
+    /// Changes the room's topic, an empty one removes it
+    SetTopic(String),
+    /// Changes the room's description, an empty one removes it
+    SetDescription(String),
+    /// Protects the room with the password, an empty one opens the room again
+    SetPassword(String),
+    SetInviteOnly(bool),
+    /// Creates an invite valid for `duration` seconds
+    CreateInvite {
+        duration: u64,
+    },
+    SetPresence(Presence),
     Quit,
 }
 
+/// An action with the id the server echoes back once it replied to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsRequest {
+    pub id: Option<RequestId>,
+    pub action: WsAction,
+}
+
+impl WsRequest {
+    #[must_use]
+    pub const fn new(id: Option<RequestId>, action: WsAction) -> Self {
+        Self { id, action }
+    }
+}
+
+/// What's needed to come back as the same user after the connection drops
+#[derive(Debug, Clone)]
+struct Resume {
+    token: String,
+    grace: Duration,
+}
+
 /// The type that stands between the server and the client,
 /// handling the communication using types `WsEvent` and `WsAction`
 /// needs to be closed manually
 #[derive(Debug)]
 pub struct WsHandler {
-    #[allow(unused)]
     config: WebConfig,
+    /// The url of the room, as it was joined
+    url: Url,
     stream: WsConnection,
+    encoding: Encoding,
+    resume: Option<Resume>,
+    /// The last message of the room this handler got, a resumed session continues after it
+    last_seen: Option<MessageId>,
     tx: Sender<WsEvent>,
-    rx: Receiver<WsAction>,
+    rx: Receiver<WsRequest>,
 }
 
 impl WsHandler {
@@ -66,14 +213,16 @@ impl WsHandler {
     /// # Panics
     ///
     /// This function panics if any of the default values are incorrect
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         tx: Sender<WsEvent>,
-        rx: Receiver<WsAction>,
+        rx: Receiver<WsRequest>,
         config: WebConfig,
         room: String,
         mut url: Url,
-        version: Version,
+        protocol: Protocol,
         initial_name: Option<String>,
+        credentials: RoomCredentials,
     ) -> anyhow::Result<Self> {
         // TODO: Better error reporting/handling instread of just using anyhow
         if url.scheme() == "https" {
@@ -82,18 +231,23 @@ impl WsHandler {
             url.set_scheme("ws").expect("The url should be correct");
         }
 
+        let Protocol { version, encoding } = protocol;
         let mut url = url
             .join(&format!("{version}/room/{room}"))
             .context("Couldn't parse url string")?;
 
         if let Some(name) = initial_name {
-            let name = form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>();
-            url.set_query(Some(&format!("name={name}")));
+            url.query_pairs_mut().append_pair("name", &name);
+        }
+        if encoding != Encoding::default() {
+            url.query_pairs_mut()
+                .append_pair("encoding", &encoding.to_string());
         }
 
-        log::debug!("Trying to connect to websocket {url}");
+        log::debug!("Trying to connect to websocket {}", url.path());
 
-        let stream = Self::connect_websocket(&url).await;
+        let request = Self::room_request(&url, config.token.as_deref(), &credentials)?;
+        let stream = Self::connect_websocket(request).await;
 
         if let Err(err) = &stream {
             let _ = tx.send(WsEvent::FatalError(err.to_string())).await;
@@ -104,16 +258,58 @@ impl WsHandler {
 
         Ok(Self {
             config,
+            url,
             stream,
+            encoding,
+            resume: None,
+            last_seen: None,
             tx,
             rx,
         })
     }
 
-    async fn connect_websocket(cfg: &Url) -> anyhow::Result<WsConnection> {
+    /// The request that joins the room,
+    /// the room's credentials are sent in headers so they aren't logged
+    fn room_request(
+        cfg: &Url,
+        token: Option<&str>,
+        credentials: &RoomCredentials,
+    ) -> anyhow::Result<Request> {
+        let mut request = cfg.as_str().into_client_request()?;
+        let headers = request.headers_mut();
+        if let Some(token) = token {
+            headers.insert(AUTHORIZATION, format!("Bearer {token}").parse()?);
+        }
+        if let Some(password) = &credentials.password {
+            headers.insert(ROOM_PASSWORD_HEADER, password.parse()?);
+        }
+        if let Some(invite) = &credentials.invite {
+            headers.insert(ROOM_INVITE_HEADER, invite.parse()?);
+        }
+
+        Ok(request)
+    }
+
+    /// The request that resumes the dropped session,
+    /// the server replays the messages after the last one this handler got
+    fn resume_request(&self, token: &str) -> anyhow::Result<Request> {
+        // resuming doesn't need the room's credentials again
+        let credentials = RoomCredentials::default();
+        let mut request =
+            Self::room_request(&self.url, self.config.token.as_deref(), &credentials)?;
+        let headers = request.headers_mut();
+        headers.insert(RESUME_TOKEN_HEADER, token.parse()?);
+        if let Some(id) = self.last_seen {
+            headers.insert(RESUME_AFTER_HEADER, id.to_string().parse()?);
+        }
+
+        Ok(request)
+    }
+
+    async fn connect_websocket(request: Request) -> anyhow::Result<WsConnection> {
         tokio::select! {
-            conn = connect_async(cfg.to_string()) => {
-                let (stream, _res) = conn?;
+            conn = connect_async_tls_with_config(request, None, false, tls::ws_connector()) => {
+                let (stream, _res) = conn.map_err(refusal)?;
                 Ok(WsConnection::from(stream))
             }
             () = tokio::time::sleep(WS_TIMEOUT_DURATION) => {
@@ -127,7 +323,13 @@ impl WsHandler {
 
         tokio::select! {
             res = self.handle_stream() => {
-                let res = res.inspect_err(|err| log::error!("{err}")).unwrap_or(true);
+                let res = match res {
+                    Ok(quit) => quit,
+                    Err(err) => {
+                        log::error!("{err}");
+                        !self.reconnect().await
+                    }
+                };
                 should_quit = should_quit || res;
             }
             () = tokio::time::sleep(TICK_DURATION / 2) => {
@@ -137,6 +339,51 @@ impl WsHandler {
         should_quit
     }
 
+    /// Tries to resume the session after the connection dropped,
+    /// returns whether it succeeded
+    async fn reconnect(&mut self) -> bool {
+        let Some(Resume { token, grace }) = self.resume.take() else {
+            return false;
+        };
+        self.send_event(WsEvent::ConnectionLost).await;
+
+        let deadline = Instant::now() + grace;
+
+        while Instant::now() < deadline {
+            let request = match self.resume_request(&token) {
+                Ok(request) => request,
+                Err(err) => {
+                    log::error!("Couldn't build the resume request: {err}");
+                    break;
+                }
+            };
+            match Self::connect_websocket(request).await {
+                Ok(stream) => {
+                    log::info!("Resumed the session");
+                    self.stream = stream;
+                    self.send_event(WsEvent::Reconnected).await;
+                    return true;
+                }
+                Err(err) => {
+                    log::warn!("Couldn't resume the session: {err}");
+                    // the server is up, but the session is gone
+                    if let Some(tungstenite::Error::Http(res)) = err.downcast_ref()
+                        && res.status().is_client_error()
+                    {
+                        break;
+                    }
+                }
+            }
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+
+        self.send_event(WsEvent::FatalError(
+            "Lost the connection to the server".to_string(),
+        ))
+        .await;
+        false
+    }
+
     pub async fn close(&mut self) {
         log::info!("Closing Ws stream");
         let _ = self.tx.send(WsEvent::Quit).await;
@@ -152,10 +399,17 @@ impl WsHandler {
             .context("stream resolved to None")??;
 
         match msg {
-            tungstenite::Message::Text(txt) => {
-                self.handle_message(txt.as_ref()).await?;
+            tungstenite::Message::Text(_) | tungstenite::Message::Binary(_) => {
+                self.handle_message(&msg).await?;
             }
-            tungstenite::Message::Close(_) => {
+            tungstenite::Message::Close(frame) => {
+                if let Some(frame) = frame.filter(|frame| !frame.reason.is_empty()) {
+                    self.send_event(WsEvent::FatalError(format!(
+                        "The server closed the connection: {}",
+                        frame.reason
+                    )))
+                    .await;
+                }
                 return Ok(true);
             }
             _ => {
@@ -197,110 +451,222 @@ impl WsHandler {
         should_exit
     }
 
-    async fn handle_action(&mut self, msg: &WsAction) -> anyhow::Result<bool> {
-        match msg {
-            WsAction::Message(msg) => {
-                self.stream
-                    .send(tungstenite::Message::Text(
-                        ClientMessage::SendMessage(msg.clone()).as_json().into(),
-                    ))
-                    .await?;
-            }
+    async fn handle_action(&mut self, request: &WsRequest) -> anyhow::Result<bool> {
+        let msg = match &request.action {
+            WsAction::Message(msg) => ClientMessage::SendMessage(msg.clone()),
             WsAction::Quit => {
                 self.stream.flush().await?;
                 return Ok(true);
             }
-            WsAction::ChangeName(name) => {
-                self.stream
-                    .send(tungstenite::Message::Text(
-                        ClientMessage::ChangeUserName(name.clone()).as_json().into(),
-                    ))
-                    .await?;
-            }
-            WsAction::RequestUser(uuid) => {
-                self.stream
-                    .send(tungstenite::Message::Text(
-                        ClientMessage::GetUserData(*uuid).as_json().into(),
-                    ))
-                    .await?;
-            }
-            WsAction::RequestSelf => {
-                self.stream
-                    .send(tungstenite::Message::Text(
-                        ClientMessage::GetSelf.as_json().into(),
-                    ))
-                    .await?;
-            }
-            WsAction::RequestAll => {
-                self.stream
-                    .send(tungstenite::Message::Text(
-                        ClientMessage::GetAllUserData.as_json().into(),
-                    ))
-                    .await?;
+            WsAction::ChangeName(name) => ClientMessage::ChangeUserName(name.clone()),
+            WsAction::RequestUser(uuid) => ClientMessage::GetUserData(*uuid),
+            WsAction::RequestSelf => ClientMessage::GetSelf,
+            WsAction::RequestAll => ClientMessage::GetAllUserData,
+            WsAction::EditMessage { id, content } => ClientMessage::EditMessage {
+                id: *id,
+                content: content.clone(),
+            },
+            WsAction::DeleteMessage(id) => ClientMessage::DeleteMessage(*id),
+            WsAction::DirectMessage { to, content } => ClientMessage::SendDirectMessage {
+                to: *to,
+                content: content.clone(),
+            },
+            WsAction::Reply { to, content } => ClientMessage::SendReply {
+                reply_to: *to,
+                content: content.clone(),
+            },
+            WsAction::Typing(typing) => ClientMessage::Typing(*typing),
+            WsAction::AddReaction { id, reaction } => ClientMessage::AddReaction {
+                id: *id,
+                reaction: reaction.clone(),
+            },
+            WsAction::RemoveReaction { id, reaction } => ClientMessage::RemoveReaction {
+                id: *id,
+                reaction: reaction.clone(),
+            },
+            WsAction::Kick(user) => ClientMessage::Kick(*user),
+            WsAction::Mute { user, duration } => ClientMessage::Mute {
+                user: *user,
+                duration: *duration,
+            },
+            WsAction::Ban { user, duration } => ClientMessage::Ban {
+                user: *user,
+                duration: *duration,
+            },
+            WsAction::SetModerator { user, moderator } => ClientMessage::SetModerator {
+                user: *user,
+                moderator: *moderator,
+            },
+            WsAction::SetFilterPolicy(policy) => ClientMessage::SetFilterPolicy(policy.clone()),
+            WsAction::SetTopic(topic) => ClientMessage::SetTopic(topic.clone()),
+            WsAction::SetDescription(description) => {
+                ClientMessage::SetDescription(description.clone())
             }
-        }
+            WsAction::SetPassword(password) => ClientMessage::SetPassword(password.clone()),
+            WsAction::SetInviteOnly(invite_only) => ClientMessage::SetInviteOnly(*invite_only),
+            WsAction::CreateInvite { duration } => ClientMessage::CreateInvite {
+                duration: *duration,
+            },
+            WsAction::SetPresence(presence) => ClientMessage::SetPresence(presence.clone()),
+        };
+
+        self.send(ClientRequest::new(request.id, msg)).await?;
+
         Ok(false)
     }
 
-    async fn handle_message(&mut self, txt: &str) -> anyhow::Result<()> {
-        let msg = serde_json::from_str::<ServerMessage>(txt).map_err(|err| {
-            anyhow!("Server trying to send unsupported object or plaint text: {err} : {txt}")
-        })?;
+    async fn send(&mut self, request: ClientRequest) -> anyhow::Result<()> {
+        self.stream.send(request.encode(self.encoding)).await
+    }
 
-        log::debug!("Server Message: {msg:?}");
+    async fn handle_message(&mut self, frame: &tungstenite::Message) -> anyhow::Result<()> {
+        let ServerResponse { reply_to, message } =
+            ServerResponse::decode(frame).map_err(|err| {
+                anyhow!("Server trying to send unsupported object or plaint text: {err} : {frame}")
+            })?;
 
-        match msg {
-            ServerMessage::NewMessage(message) => {
-                self.send_event(WsEvent::Message(message)).await;
-            }
-            ServerMessage::UserLeft(user) => {
-                self.send_event(WsEvent::UserRemove(*user.get_id())).await;
-            }
-            ServerMessage::UserJoined(user) => {
-                self.send_event(WsEvent::UserAdd(user)).await;
-            }
-            ServerMessage::UserNameChange(user) => {
-                self.send_event(WsEvent::UserChange(user)).await;
-            }
-            ServerMessage::SelfData(user) => {
-                self.send_event(WsEvent::SelfInfo(user)).await;
-            }
-            ServerMessage::UserData(user) => {
-                self.send_event(WsEvent::UserInfo(user)).await;
-            }
-            ServerMessage::Banned { duration, reason } => {
-                self.send_event(WsEvent::Banned(duration, reason)).await;
-            }
-            ServerMessage::AllUsers(users) => {
-                self.send_event(WsEvent::AllUserInfo(users)).await;
-            }
-            ServerMessage::TimeoutAdded(secs) => {
-                self.send_event(WsEvent::TimeoutAdded(secs)).await;
-            }
-            ServerMessage::InvalidUser(id) => {
-                self.send_event(WsEvent::SoftError(format!(
-                    "Tried to get a user that doesn't exist: {id}"
-                )))
-                .await;
-            }
-            ServerMessage::UnsupportedMessage(err) | ServerMessage::NameTooLong(err) => {
-                self.send_event(WsEvent::SoftError(err)).await;
-            }
-            ServerMessage::NameInappropriate => {
-                self.send_event(WsEvent::SoftError(
-                    "Tried to change name to an inappropriate one".to_string(),
-                ))
-                .await;
-            }
-            ServerMessage::Heartbeat => {
-                // Nothing needs to be done
-            }
+        self.handle_server_message(message).await;
+
+        if let Some(id) = reply_to {
+            self.send_event(WsEvent::Replied(id)).await;
         }
 
         Ok(())
     }
 
+    /// Remembers the newest message of the room the server sent
+    fn note_seen(&mut self, msg: &ServerMessage) {
+        let latest = match msg {
+            ServerMessage::NewMessage(message) => message.get_id(),
+            ServerMessage::History { events, .. } => events.iter().rev().find_map(|ev| match ev {
+                ServerMessage::NewMessage(message) => message.get_id(),
+                _ => None,
+            }),
+            _ => None,
+        };
+        if let Some(id) = latest {
+            self.last_seen = Some(*id);
+        }
+    }
+
+    async fn handle_server_message(&mut self, msg: ServerMessage) {
+        log::debug!("Server Message: {msg:?}");
+
+        self.note_seen(&msg);
+        if let ServerMessage::ResumeToken { token, grace } = msg {
+            self.resume = Some(Resume {
+                token,
+                grace: Duration::from_secs(grace),
+            });
+            return;
+        }
+
+        if let Some(event) = server_event(msg) {
+            self.send_event(event).await;
+        }
+    }
+
     async fn send_event(&mut self, event: WsEvent) {
         let _ = self.tx.send(event).await;
     }
 }
+
+/// Keeps the reason the server gave for refusing the connection, like a wrong room password
+fn refusal(err: tungstenite::Error) -> anyhow::Error {
+    let reason = match &err {
+        tungstenite::Error::Http(res) => res
+            .body()
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .filter(|reason| !reason.is_empty())
+            .map(|reason| format!("{}: {reason}", res.status())),
+        _ => None,
+    };
+
+    match reason {
+        Some(reason) => anyhow::Error::from(err).context(reason),
+        None => err.into(),
+    }
+}
+
+/// Maps a server message to the event it causes, if any
+fn server_event(msg: ServerMessage) -> Option<WsEvent> {
+    let event = match msg {
+        ServerMessage::NewMessage(message) => WsEvent::Message(message),
+        ServerMessage::UserLeft(user) => WsEvent::UserRemove(*user.get_id()),
+        ServerMessage::UserJoined(user) => WsEvent::UserAdd(user),
+        ServerMessage::UserNameChange(user) => WsEvent::UserChange(user),
+        ServerMessage::SelfData(user) => WsEvent::SelfInfo(user),
+        ServerMessage::UserData(user) => WsEvent::UserInfo(user),
+        ServerMessage::Banned { duration, reason } => WsEvent::Banned(duration, reason),
+        ServerMessage::AllUsers(users) => WsEvent::AllUserInfo(users),
+        ServerMessage::TimeoutAdded(secs) => WsEvent::TimeoutAdded(secs),
+        ServerMessage::InvalidUser(id) => {
+            WsEvent::SoftError(format!("Tried to get a user that doesn't exist: {id}"))
+        }
+        ServerMessage::UnsupportedMessage(err) | ServerMessage::NameTooLong(err) => {
+            WsEvent::SoftError(err)
+        }
+        ServerMessage::NameInappropriate => {
+            WsEvent::SoftError("Tried to change name to an inappropriate one".to_string())
+        }
+        ServerMessage::NameTaken(name) => {
+            WsEvent::SoftError(format!("The name {name} belongs to a registered account"))
+        }
+        ServerMessage::MessageEdited(message) => WsEvent::MessageEdited(message),
+        ServerMessage::MessageDeleted(id) => WsEvent::MessageDeleted(id),
+        ServerMessage::DirectMessage { to, message } => WsEvent::DirectMessage { to, message },
+        ServerMessage::UserTyping { user, typing } => WsEvent::UserTyping { user, typing },
+        ServerMessage::ReactionsChanged { id, reactions } => {
+            WsEvent::ReactionsChanged { id, reactions }
+        }
+        ServerMessage::InvalidReaction(reaction) => WsEvent::SoftError(format!(
+            "Reactions have to be a single word of at most {MAX_REACTION_LENGTH} characters: {reaction}"
+        )),
+        ServerMessage::InvalidMessage(id) => WsEvent::SoftError(format!(
+            "Tried to change a message that doesn't exist or is too old: {id}"
+        )),
+        ServerMessage::NotAuthor(_) => {
+            WsEvent::SoftError("Only the author can change a message".to_string())
+        }
+        ServerMessage::Moderators { owner, moderators } => {
+            WsEvent::Moderators { owner, moderators }
+        }
+        ServerMessage::Sanctioned { user, by, sanction } => {
+            WsEvent::Sanctioned { user, by, sanction }
+        }
+        ServerMessage::Muted(until) => WsEvent::Muted(until),
+        ServerMessage::ModerationDenied(_) => WsEvent::SoftError(
+            "Only moderators can do that, and only the owner can moderate moderators".to_string(),
+        ),
+        ServerMessage::FilterPolicy(policy) => WsEvent::FilterPolicy(policy),
+        ServerMessage::Announcement(msg) => WsEvent::Announcement(msg),
+        ServerMessage::RoomInfo(info) => WsEvent::RoomInfo(info),
+        ServerMessage::InviteCreated { code, expires_at } => {
+            WsEvent::InviteCreated { code, expires_at }
+        }
+        ServerMessage::PresenceChanged { user, presence } => {
+            WsEvent::PresenceChanged { user, presence }
+        }
+        ServerMessage::InvalidPresence(_) => WsEvent::SoftError(format!(
+            "Statuses can be at most {MAX_STATUS_LENGTH} characters, and can't be empty or inappropriate"
+        )),
+        ServerMessage::InvalidRoomInfo(_) => WsEvent::SoftError(format!(
+            "Topics can be at most {MAX_TOPIC_LENGTH} and descriptions {MAX_DESCRIPTION_LENGTH} characters, and can't be inappropriate"
+        )),
+        ServerMessage::MessageRejected => {
+            WsEvent::SoftError("The room's filter rejected the message".to_string())
+        }
+        ServerMessage::FilterPinned => WsEvent::SoftError(
+            "The server sets the filter of this room, it can't be changed".to_string(),
+        ),
+        ServerMessage::History { users, events } => WsEvent::History {
+            users,
+            events: events.into_iter().filter_map(server_event).collect(),
+        },
+        // Nothing needs to be done, or the handler already did it
+        ServerMessage::Heartbeat | ServerMessage::ResumeToken { .. } => return None,
+    };
+
+    Some(event)
+}
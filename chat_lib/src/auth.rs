@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::User;
+
+/// The header the password of a protected room is sent in when joining it,
+/// kept out of the url so it doesn't end up in logs
+pub const ROOM_PASSWORD_HEADER: &str = "x-room-password";
+
+/// The header an invite code is sent in when joining a protected room
+pub const ROOM_INVITE_HEADER: &str = "x-room-invite";
+
+/// The header the resume token of a dropped connection is sent in to come back as its user
+pub const RESUME_TOKEN_HEADER: &str = "x-resume-token";
+
+/// The header with the id of the last message the dropped connection got,
+/// everything after it in the room's history is replayed
+pub const RESUME_AFTER_HEADER: &str = "x-resume-after";
+
+/// The body of the register and login requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub name: String,
+    pub password: String,
+}
+
+/// The reply to a successful register or login request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Sent as a bearer token when joining a room to join as `user`
+    pub token: String,
+    /// The account's user, its id is the same in every room and connection
+    pub user: User,
+}
@@ -1,2 +1,17 @@
 /// Max length of name in utf8 characters
 pub const MAX_NAME_LENGTH: usize = 30;
+
+/// Max length of a reaction in utf8 characters
+pub const MAX_REACTION_LENGTH: usize = 16;
+
+/// Min length of an account's password in utf8 characters
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Max length of a room's topic in utf8 characters
+pub const MAX_TOPIC_LENGTH: usize = 100;
+
+/// Max length of a custom presence status in utf8 characters
+pub const MAX_STATUS_LENGTH: usize = 64;
+
+/// Max length of a room's description in utf8 characters
+pub const MAX_DESCRIPTION_LENGTH: usize = 500;
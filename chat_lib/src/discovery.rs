@@ -1,8 +1,20 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{Encoding, RoomInfo};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Discovery {
     pub server_version: semver::Version,
     pub available_rooms: Vec<String>,
+    /// What the available rooms are about, older servers only send their names
+    #[serde(default)]
+    pub rooms: Vec<RoomInfo>,
     pub supported_api_versions: Vec<crate::Version>,
+    /// Older servers don't send this, but they all support json
+    #[serde(default = "default_encodings")]
+    pub supported_encodings: Vec<Encoding>,
+}
+
+fn default_encodings() -> Vec<Encoding> {
+    vec![Encoding::Json]
 }
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use strum::Display;
+
+/// The wire encoding of the messages,
+/// chosen by the client when connecting to a room
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Display)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Text frames containing json
+    #[default]
+    Json,
+    /// Binary frames containing `MessagePack`
+    #[serde(rename = "msgpack")]
+    #[strum(serialize = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    /// # Errors
+    ///
+    /// This function errors if the value can't be serialized
+    pub fn to_bytes<T: Serialize>(self, value: &T) -> anyhow::Result<Vec<u8>> {
+        let bytes = match self {
+            Encoding::Json => serde_json::to_vec(value)?,
+            // the named variant is needed for the optional fields to work
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)?,
+        };
+
+        Ok(bytes)
+    }
+
+    /// # Errors
+    ///
+    /// This function errors if the bytes aren't a valid `T` in this encoding
+    pub fn from_bytes<T: DeserializeOwned>(self, bytes: &[u8]) -> anyhow::Result<T> {
+        let value = match self {
+            Encoding::Json => serde_json::from_slice(bytes)?,
+            Encoding::MessagePack => rmp_serde::from_slice(bytes)?,
+        };
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        ClientMessage, ClientRequest, Message, ServerMessage, ServerResponse, User, types::Sanction,
+    };
+
+    #[test]
+    fn msgpack_round_trip() -> anyhow::Result<()> {
+        let id = Uuid::new_v4();
+        let messages = [
+            ServerMessage::NewMessage(Message::stamped(id, "Hello".to_string())),
+            ServerMessage::NewMessage(Message::new(id, "Hello".to_string())),
+            ServerMessage::AllUsers(vec![User::new(id, "name".to_string())]),
+            ServerMessage::Heartbeat,
+            ServerMessage::Sanctioned {
+                user: id,
+                by: Uuid::new_v4(),
+                sanction: Sanction::Mute(chrono::Utc::now()),
+            },
+        ];
+
+        for msg in messages {
+            let bytes = Encoding::MessagePack.to_bytes(&msg)?;
+            let decoded = Encoding::MessagePack.from_bytes::<ServerMessage>(&bytes)?;
+            assert_eq!(msg.as_json(), decoded.as_json());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn msgpack_flattened_round_trip() -> anyhow::Result<()> {
+        let request = ClientRequest::new(Some(3), ClientMessage::GetSelf);
+        let bytes = Encoding::MessagePack.to_bytes(&request)?;
+        let decoded = Encoding::MessagePack.from_bytes::<ClientRequest>(&bytes)?;
+        assert_eq!(decoded.request_id, Some(3));
+        assert!(matches!(decoded.message, ClientMessage::GetSelf));
+
+        let response = ServerResponse::new(None, ServerMessage::Heartbeat);
+        let bytes = Encoding::MessagePack.to_bytes(&response)?;
+        let decoded = Encoding::MessagePack.from_bytes::<ServerResponse>(&bytes)?;
+        assert_eq!(decoded.reply_to, None);
+        assert!(matches!(decoded.message, ServerMessage::Heartbeat));
+
+        Ok(())
+    }
+}
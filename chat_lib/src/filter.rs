@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+/// What a room does with inappropriate messages
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Display)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMode {
+    /// The inappropriate parts are replaced with `*`
+    #[default]
+    Censor,
+    /// The whole message is refused
+    Reject,
+    /// Nothing is filtered
+    Off,
+}
+
+/// How a room filters what's said in it, set in the server config or by the room's owner
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct FilterPolicy {
+    pub mode: FilterMode,
+    /// Words that get through even if the filter would catch them
+    pub allow: Vec<String>,
+    /// Words that are caught even if the filter would let them through
+    pub deny: Vec<String>,
+}
+
+impl FilterPolicy {
+    #[must_use]
+    pub fn with_mode(self, mode: FilterMode) -> Self {
+        Self { mode, ..self }
+    }
+}
@@ -6,14 +6,22 @@ pub mod ws_connection;
 #[cfg(feature = "ws_conn")]
 pub mod ws_mock;
 
+pub mod auth;
 pub mod consts;
 pub mod discovery;
+pub mod encoding;
+pub mod filter;
 pub mod prelude;
+pub mod protocol;
+pub mod room;
 pub mod types;
 pub mod version;
 
 pub use discovery::Discovery;
-pub use types::{ClientMessage, Message, ServerMessage, User};
+pub use encoding::Encoding;
+pub use protocol::Protocol;
+pub use room::RoomInfo;
+pub use types::{ClientMessage, ClientRequest, Message, ServerMessage, ServerResponse, User};
 pub use version::Version;
 
 #[cfg(feature = "ws_conn")]
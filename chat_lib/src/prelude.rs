@@ -1,4 +1,7 @@
-pub use crate::{ClientMessage, Discovery, Message, ServerMessage, User, consts::*};
+pub use crate::{
+    ClientMessage, ClientRequest, Discovery, Message, ServerMessage, ServerResponse, User,
+    consts::*,
+};
 
 #[cfg(feature = "ws_conn")]
 pub use crate::WsConnection;
@@ -0,0 +1,15 @@
+use crate::{Encoding, Version};
+
+/// How the messages are represented on a single connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protocol {
+    pub version: Version,
+    pub encoding: Encoding,
+}
+
+impl Protocol {
+    #[must_use]
+    pub const fn new(version: Version, encoding: Encoding) -> Self {
+        Self { version, encoding }
+    }
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::Display;
+
+use crate::User;
+
+/// Who can join a room, set by the room's owner
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash, Display)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// Anyone can join
+    #[default]
+    Open,
+    /// Joining needs the room's password or an invite code
+    Password,
+    /// Joining needs an invite code
+    InviteOnly,
+}
+
+impl Access {
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        matches!(self, Access::Open)
+    }
+}
+
+/// What a room is about and where it came from
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct RoomInfo {
+    pub name: String,
+    /// A short line about what's discussed right now, set by the room's moderators
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// A longer text about what the room is for, set by the room's moderators
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Unknown for rooms created before the server kept track of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// The first user who joined the room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<User>,
+    /// Moderators can always join, whatever the access is
+    #[serde(default, skip_serializing_if = "Access::is_open")]
+    pub access: Access,
+    /// Whether the room is kept when everyone leaves, other rooms are removed when they're empty
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub persistent: bool,
+}
+
+/// A room and everyone in it, as listed by the server since [`crate::Version::V2`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomListing {
+    pub info: RoomInfo,
+    pub users: Vec<User>,
+}
+
+impl RoomInfo {
+    #[must_use]
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Self::default()
+        }
+    }
+}
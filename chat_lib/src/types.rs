@@ -1,23 +1,86 @@
 use std::{sync::Arc, time::Duration};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use strum::Display;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::{Version, filter::FilterPolicy, room::RoomInfo};
+
 // use crate::ratatui_span::FindUser;
 
 pub type Sync<T> = Arc<Mutex<T>>;
 
+/// Server assigned, time ordered (`UUIDv7`) identifier of a message
+pub type MessageId = Uuid;
+
+/// Client chosen identifier of a request, only unique per connection
+pub type RequestId = u64;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct User {
     id: Uuid,
     name: String,
+    /// Left out while the user is [`Presence::Online`]
+    #[serde(default, skip_serializing_if = "Presence::is_online")]
+    presence: Presence,
+}
+
+/// Whether the user is around to read the room, set by the user's client
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Hash, PartialEq, Eq, Display)]
+#[strum(serialize_all = "snake_case")]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Presence {
+    #[default]
+    Online,
+    /// Set by the client after a while without input
+    Away,
+    /// Around, but doesn't want to be bothered
+    Busy,
+    /// A short status of the user's own
+    #[strum(to_string = "{0}")]
+    Custom(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct Message {
+    /// Only present since [`Version::V2`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<MessageId>,
     from: Uuid,
     content: String,
+    /// The time the server received the message,
+    /// only present since [`Version::V2`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at: Option<DateTime<Utc>>,
+    /// The time of the last edit, if the message was edited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    edited_at: Option<DateTime<Utc>>,
+    /// The message this one replies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reply_to: Option<MessageId>,
+}
+
+/// Everyone who reacted to a message with the same reaction
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct Reaction {
+    reaction: String,
+    users: Vec<Uuid>,
+}
+
+/// What a moderator did to a user of the room
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Sanction {
+    /// Removed from the room, but can join again
+    Kick,
+    /// Can't send anything to the room until the time
+    Mute(DateTime<Utc>),
+    Unmute,
+    /// Removed from the room and can't join again until the time
+    Ban(DateTime<Utc>),
+    Unban,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +106,85 @@ pub enum ServerMessage {
     TimeoutAdded(u64),
     /// Only here so you don't get randomly disconnected
     Heartbeat,
+    /// The recent events of the room, replayed to a new connection before the live ones,
+    /// `users` contains everyone the events refer to
+    History {
+        users: Vec<User>,
+        events: Vec<ServerMessage>,
+    },
+    /// The message with the same id was changed by its author
+    MessageEdited(Message),
+    /// The message with the id was deleted by its author
+    MessageDeleted(MessageId),
+    /// The message doesn't exist or is too old to be changed
+    InvalidMessage(MessageId),
+    /// Only the author of the message can change it
+    NotAuthor(MessageId),
+    /// A message only sent to the author and `to`
+    DirectMessage {
+        to: Uuid,
+        message: Message,
+    },
+    /// The user started or stopped typing, a typing user keeps sending it periodically
+    UserTyping {
+        user: Uuid,
+        typing: bool,
+    },
+    /// All the reactions of the message after one of them changed
+    ReactionsChanged {
+        id: MessageId,
+        reactions: Vec<Reaction>,
+    },
+    /// The reaction is empty, too long or inappropriate
+    InvalidReaction(String),
+    /// The name belongs to a registered account
+    NameTaken(String),
+    /// Lets the connection come back as the same user after it drops,
+    /// by joining with the token in the `x-resume-token` header within `grace` seconds
+    ResumeToken {
+        token: String,
+        grace: u64,
+    },
+    /// Who can moderate the room, sent after joining and whenever it changes
+    Moderators {
+        owner: Option<Uuid>,
+        moderators: Vec<Uuid>,
+    },
+    /// A moderator sanctioned the user, kicked and banned users are disconnected after it
+    Sanctioned {
+        user: Uuid,
+        by: Uuid,
+        sanction: Sanction,
+    },
+    /// The user can't send anything to the room until the time
+    Muted(DateTime<Utc>),
+    /// Only moderators can sanction, and only the owner can sanction or appoint moderators
+    ModerationDenied(Uuid),
+    /// How the room filters messages, sent after joining and whenever it changes
+    FilterPolicy(FilterPolicy),
+    /// The room's filter rejects the message, so it wasn't sent
+    MessageRejected,
+    /// The server config sets the room's filter policy, so the owner can't change it
+    FilterPinned,
+    /// A message from the server's operators
+    Announcement(String),
+    /// What the room is about, sent after joining and whenever it changes
+    RoomInfo(RoomInfo),
+    /// The topic or description is too long or inappropriate
+    InvalidRoomInfo(String),
+    /// A code that lets anyone join the room until it expires,
+    /// only sent to the owner who asked for it
+    InviteCreated {
+        code: String,
+        expires_at: DateTime<Utc>,
+    },
+    /// The user changed their presence
+    PresenceChanged {
+        user: Uuid,
+        presence: Presence,
+    },
+    /// The custom status is empty, too long or inappropriate
+    InvalidPresence(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +195,113 @@ pub enum ClientMessage {
     GetUserData(Uuid),
     GetAllUserData,
     GetSelf,
+    EditMessage {
+        id: MessageId,
+        content: String,
+    },
+    DeleteMessage(MessageId),
+    SendDirectMessage {
+        to: Uuid,
+        content: String,
+    },
+    /// Whether the user is typing, should be throttled by the client
+    Typing(bool),
+    /// Sends a message replying to the message with the id
+    SendReply {
+        reply_to: MessageId,
+        content: String,
+    },
+    AddReaction {
+        id: MessageId,
+        reaction: String,
+    },
+    RemoveReaction {
+        id: MessageId,
+        reaction: String,
+    },
+    /// Removes the user from the room, only for moderators
+    Kick(Uuid),
+    /// Stops the user from sending anything for `duration` seconds, 0 lifts the mute,
+    /// only for moderators
+    Mute {
+        user: Uuid,
+        duration: u64,
+    },
+    /// Removes the user and stops them from joining for `duration` seconds, 0 lifts the ban,
+    /// only for moderators
+    ///
+    /// Guests get a new id every time they join, so the address they joined from is banned instead
+    Ban {
+        user: Uuid,
+        duration: u64,
+    },
+    /// Makes the user a moderator of the room or takes it away, only for the owner
+    SetModerator {
+        user: Uuid,
+        moderator: bool,
+    },
+    /// Replaces how the room filters messages, only for the owner
+    SetFilterPolicy(FilterPolicy),
+    /// Changes the room's topic, an empty one removes it,
+    /// only for moderators if the room has an owner
+    SetTopic(String),
+    /// Changes the room's description, an empty one removes it,
+    /// only for moderators if the room has an owner
+    SetDescription(String),
+    /// Makes joining the room need the password or an invite code,
+    /// an empty one opens the room again, only for the owner
+    SetPassword(String),
+    /// Makes joining the room need an invite code, or opens the room again,
+    /// only for the owner
+    SetInviteOnly(bool),
+    /// Creates a code that lets anyone join the room for `duration` seconds,
+    /// only for the owner
+    ///
+    /// Opening the room again revokes all of its invites
+    CreateInvite {
+        duration: u64,
+    },
+    /// Changes the presence of the user, it's [`Presence::Online`] after joining
+    SetPresence(Presence),
+}
+
+/// A [`ClientMessage`] with an optional id, which the server echoes back in its replies
+///
+/// Serializes the same way as the bare message if there's no id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<RequestId>,
+    #[serde(flatten)]
+    pub message: ClientMessage,
+}
+
+/// A [`ServerMessage`] that may be the reply to a [`ClientRequest`]
+///
+/// Serializes the same way as the bare message if it's not a reply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<RequestId>,
+    #[serde(flatten)]
+    pub message: ServerMessage,
+}
+
+impl ClientRequest {
+    #[must_use]
+    pub const fn new(request_id: Option<RequestId>, message: ClientMessage) -> Self {
+        Self {
+            request_id,
+            message,
+        }
+    }
+}
+
+impl ServerResponse {
+    #[must_use]
+    pub const fn new(reply_to: Option<RequestId>, message: ServerMessage) -> Self {
+        Self { reply_to, message }
+    }
 }
 
 impl ServerMessage {
@@ -64,17 +313,69 @@ impl ServerMessage {
         serde_json::to_string(self).expect("Serialize implementation failed")
     }
 
+    /// The user the message is about, if any
     #[must_use]
-    pub fn is_user(&self, id: Uuid) -> bool {
+    pub fn user_id(&self) -> Option<Uuid> {
         match self {
-            ServerMessage::NewMessage(message) => *message.get_author() == id,
+            ServerMessage::NewMessage(message)
+            | ServerMessage::MessageEdited(message)
+            | ServerMessage::DirectMessage { message, .. } => Some(*message.get_author()),
             ServerMessage::UserNameChange(user)
             | ServerMessage::UserJoined(user)
             | ServerMessage::UserLeft(user)
             | ServerMessage::UserData(user)
-            | ServerMessage::SelfData(user) => *user.get_id() == id,
-            ServerMessage::InvalidUser(uuid) => *uuid == id,
-            _ => false,
+            | ServerMessage::SelfData(user) => Some(*user.get_id()),
+            ServerMessage::InvalidUser(uuid)
+            | ServerMessage::UserTyping { user: uuid, .. }
+            | ServerMessage::PresenceChanged { user: uuid, .. }
+            | ServerMessage::Sanctioned { user: uuid, .. } => Some(*uuid),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn is_user(&self, id: Uuid) -> bool {
+        self.user_id() == Some(id)
+    }
+
+    /// Converts the message into the shape clients using `version` expect
+    ///
+    /// Returns `None` if the message can't be represented in `version` at all
+    #[must_use]
+    pub fn into_version(self, version: Version) -> Option<Self> {
+        match version {
+            Version::V1 => match self {
+                ServerMessage::NewMessage(message) => {
+                    Some(ServerMessage::NewMessage(message.without_stamp()))
+                }
+                // the closest thing V1 has for a rejected name
+                ServerMessage::NameTaken(_) => Some(ServerMessage::NameInappropriate),
+                ServerMessage::History { .. }
+                | ServerMessage::MessageEdited(_)
+                | ServerMessage::MessageDeleted(_)
+                | ServerMessage::InvalidMessage(_)
+                | ServerMessage::NotAuthor(_)
+                | ServerMessage::DirectMessage { .. }
+                | ServerMessage::UserTyping { .. }
+                | ServerMessage::ReactionsChanged { .. }
+                | ServerMessage::InvalidReaction(_)
+                | ServerMessage::ResumeToken { .. }
+                | ServerMessage::Moderators { .. }
+                | ServerMessage::Sanctioned { .. }
+                | ServerMessage::Muted(_)
+                | ServerMessage::ModerationDenied(_)
+                | ServerMessage::FilterPolicy(_)
+                | ServerMessage::MessageRejected
+                | ServerMessage::FilterPinned
+                | ServerMessage::Announcement(_)
+                | ServerMessage::RoomInfo(_)
+                | ServerMessage::InvalidRoomInfo(_)
+                | ServerMessage::InviteCreated { .. }
+                | ServerMessage::PresenceChanged { .. }
+                | ServerMessage::InvalidPresence(_) => None,
+                msg => Some(msg),
+            },
+            Version::V2 | Version::V3 => Some(self),
         }
     }
 }
@@ -92,7 +393,11 @@ impl ClientMessage {
 impl User {
     #[must_use]
     pub const fn new(id: Uuid, name: String) -> Self {
-        Self { id, name }
+        Self {
+            id,
+            name,
+            presence: Presence::Online,
+        }
     }
 
     #[must_use]
@@ -108,12 +413,125 @@ impl User {
     pub fn set_name(&mut self, name: String) {
         self.name = name;
     }
+
+    #[must_use]
+    pub const fn get_presence(&self) -> &Presence {
+        &self.presence
+    }
+
+    pub fn set_presence(&mut self, presence: Presence) {
+        self.presence = presence;
+    }
+}
+
+impl Presence {
+    #[must_use]
+    pub const fn is_online(&self) -> bool {
+        matches!(self, Presence::Online)
+    }
+}
+
+impl Reaction {
+    #[must_use]
+    pub const fn new(reaction: String, users: Vec<Uuid>) -> Self {
+        Self { reaction, users }
+    }
+
+    #[must_use]
+    pub fn get_reaction(&self) -> &str {
+        &self.reaction
+    }
+
+    #[must_use]
+    pub fn get_users(&self) -> &[Uuid] {
+        &self.users
+    }
+
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.users.len()
+    }
 }
 
 impl Message {
+    /// Creates a message without an id or timestamp, as used by [`Version::V1`]
     #[must_use]
     pub const fn new(from: Uuid, content: String) -> Self {
-        Self { from, content }
+        Self {
+            id: None,
+            from,
+            content,
+            sent_at: None,
+            edited_at: None,
+            reply_to: None,
+        }
+    }
+
+    /// Creates a message with a fresh id and the current time,
+    /// should only be called by the server
+    #[must_use]
+    pub fn stamped(from: Uuid, content: String) -> Self {
+        Self {
+            id: Some(Uuid::now_v7()),
+            from,
+            content,
+            sent_at: Some(Utc::now()),
+            edited_at: None,
+            reply_to: None,
+        }
+    }
+
+    /// Makes the message a reply to the message with the id
+    #[must_use]
+    pub fn replying_to(self, reply_to: MessageId) -> Self {
+        Self {
+            reply_to: Some(reply_to),
+            ..self
+        }
+    }
+
+    /// Removes everything [`Version::V1`] doesn't know about, like the server assigned id and timestamps
+    #[must_use]
+    pub fn without_stamp(self) -> Self {
+        Self {
+            id: None,
+            sent_at: None,
+            edited_at: None,
+            reply_to: None,
+            ..self
+        }
+    }
+
+    /// Replaces the content and marks the message as edited now,
+    /// should only be called by the server
+    pub fn edit(&mut self, content: String) {
+        self.content = content;
+        self.edited_at = Some(Utc::now());
+    }
+
+    #[must_use]
+    pub fn get_edited_at(&self) -> Option<&DateTime<Utc>> {
+        self.edited_at.as_ref()
+    }
+
+    #[must_use]
+    pub fn get_reply_to(&self) -> Option<&MessageId> {
+        self.reply_to.as_ref()
+    }
+
+    #[must_use]
+    pub fn is_edited(&self) -> bool {
+        self.edited_at.is_some()
+    }
+
+    #[must_use]
+    pub fn get_id(&self) -> Option<&MessageId> {
+        self.id.as_ref()
+    }
+
+    #[must_use]
+    pub fn get_sent_at(&self) -> Option<&DateTime<Utc>> {
+        self.sent_at.as_ref()
     }
 
     #[must_use]
@@ -126,3 +544,62 @@ impl Message {
         &self.from
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_messages_have_no_stamp() {
+        let msg = ServerMessage::NewMessage(
+            Message::stamped(Uuid::new_v4(), "hi".to_string()).replying_to(Uuid::now_v7()),
+        );
+
+        let v1 = msg
+            .clone()
+            .into_version(Version::V1)
+            .expect("V1 has messages");
+        let ServerMessage::NewMessage(v1) = v1 else {
+            panic!("The message type shouldn't change");
+        };
+        assert!(v1.get_id().is_none());
+        assert!(v1.get_sent_at().is_none());
+        assert!(v1.get_reply_to().is_none());
+
+        let v2 = msg.into_version(Version::V2).expect("V2 has messages");
+        let ServerMessage::NewMessage(v2) = v2 else {
+            panic!("The message type shouldn't change");
+        };
+        assert!(v2.get_id().is_some());
+        assert!(v2.get_sent_at().is_some());
+        assert!(v2.get_reply_to().is_some());
+    }
+
+    #[test]
+    fn requests_without_id_are_bare_messages() -> anyhow::Result<()> {
+        let bare = ClientMessage::GetUserData(Uuid::new_v4());
+        let request = ClientRequest::new(None, bare.clone());
+        assert_eq!(serde_json::to_string(&request)?, bare.as_json());
+
+        let parsed = serde_json::from_str::<ClientRequest>(&bare.as_json())?;
+        assert_eq!(parsed.request_id, None);
+
+        let request = ClientRequest::new(Some(7), bare);
+        let parsed = serde_json::from_str::<ClientRequest>(&serde_json::to_string(&request)?)?;
+        assert_eq!(parsed.request_id, Some(7));
+
+        Ok(())
+    }
+
+    #[test]
+    fn online_users_have_no_presence() -> anyhow::Result<()> {
+        let mut user = User::new(Uuid::new_v4(), "name".to_string());
+        assert!(!serde_json::to_string(&user)?.contains("presence"));
+
+        user.set_presence(Presence::Custom("lunch".to_string()));
+        let parsed = serde_json::from_str::<User>(&serde_json::to_string(&user)?)?;
+        assert_eq!(parsed, user);
+
+        Ok(())
+    }
+}
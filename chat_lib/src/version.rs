@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Display,
+)]
 #[strum(serialize_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
 pub enum Version {
@@ -9,3 +11,31 @@ pub enum Version {
     V2,
     V3,
 }
+
+impl Version {
+    /// Picks the highest version both sides support
+    ///
+    /// Returns `None` if there's no overlap
+    #[must_use]
+    pub fn negotiate(ours: &[Version], theirs: &[Version]) -> Option<Version> {
+        ours.iter().filter(|v| theirs.contains(v)).max().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_picks_highest_common() {
+        let ours = [Version::V1, Version::V2];
+
+        assert_eq!(
+            Version::negotiate(&ours, &[Version::V2, Version::V1, Version::V3]),
+            Some(Version::V2)
+        );
+        assert_eq!(Version::negotiate(&ours, &[Version::V1]), Some(Version::V1));
+        assert_eq!(Version::negotiate(&ours, &[Version::V3]), None);
+        assert_eq!(Version::negotiate(&ours, &[]), None);
+    }
+}
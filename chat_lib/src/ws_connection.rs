@@ -14,13 +14,14 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
 #[cfg(feature = "server")]
 use axum::extract::ws::{CloseFrame as AxumFrame, Message as AxumMessage, WebSocket};
-#[cfg(feature = "server")]
-use tokio_tungstenite::tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
 
 #[cfg(feature = "client")]
 pub use tokio_tungstenite::tungstenite::Error;
 
-pub use tokio_tungstenite::tungstenite::Message;
+pub use tokio_tungstenite::tungstenite::{
+    Message,
+    protocol::{CloseFrame, frame::coding::CloseCode},
+};
 
 #[derive(Debug)]
 pub enum WsConnection {
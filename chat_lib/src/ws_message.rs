@@ -1,4 +1,9 @@
-use crate::types::{ClientMessage, ServerMessage};
+use anyhow::anyhow;
+
+use crate::{
+    Encoding,
+    types::{ClientMessage, ClientRequest, ServerMessage, ServerResponse},
+};
 use tokio_tungstenite::tungstenite::Message;
 
 #[cfg(feature = "server")]
@@ -25,6 +30,18 @@ impl ClientMessage {
     pub fn as_wsmsg(&self) -> Message {
         Message::text(self.as_json())
     }
+
+    #[must_use]
+    pub fn encode(&self, encoding: Encoding) -> Message {
+        encode(self, encoding)
+    }
+
+    /// # Errors
+    ///
+    /// This function errors if the frame isn't a valid message
+    pub fn decode(msg: &Message) -> anyhow::Result<Self> {
+        decode(msg)
+    }
 }
 
 impl ServerMessage {
@@ -32,4 +49,71 @@ impl ServerMessage {
     pub fn as_wsmsg(&self) -> Message {
         Message::text(self.as_json())
     }
+
+    #[must_use]
+    pub fn encode(&self, encoding: Encoding) -> Message {
+        encode(self, encoding)
+    }
+
+    /// # Errors
+    ///
+    /// This function errors if the frame isn't a valid message
+    pub fn decode(msg: &Message) -> anyhow::Result<Self> {
+        decode(msg)
+    }
+}
+
+impl ClientRequest {
+    #[must_use]
+    pub fn encode(&self, encoding: Encoding) -> Message {
+        encode(self, encoding)
+    }
+
+    /// # Errors
+    ///
+    /// This function errors if the frame isn't a valid request
+    pub fn decode(msg: &Message) -> anyhow::Result<Self> {
+        decode(msg)
+    }
+}
+
+impl ServerResponse {
+    #[must_use]
+    pub fn encode(&self, encoding: Encoding) -> Message {
+        encode(self, encoding)
+    }
+
+    /// # Errors
+    ///
+    /// This function errors if the frame isn't a valid response
+    pub fn decode(msg: &Message) -> anyhow::Result<Self> {
+        decode(msg)
+    }
+}
+
+/// Json is sent as a text frame, while `MessagePack` as a binary one
+///
+/// # Panics
+///
+/// Panics if there's an error during serde serialization
+fn encode<T: serde::Serialize>(value: &T, encoding: Encoding) -> Message {
+    let bytes = encoding
+        .to_bytes(value)
+        .expect("Serialize implementation failed");
+
+    match encoding {
+        Encoding::Json => {
+            Message::text(String::from_utf8(bytes).expect("Json should always be valid utf8"))
+        }
+        Encoding::MessagePack => Message::binary(bytes),
+    }
+}
+
+/// The encoding is decided by the frame type, so either side can always fall back to json
+fn decode<T: serde::de::DeserializeOwned>(msg: &Message) -> anyhow::Result<T> {
+    match msg {
+        Message::Text(txt) => Encoding::Json.from_bytes(txt.as_bytes()),
+        Message::Binary(bytes) => Encoding::MessagePack.from_bytes(bytes),
+        _ => Err(anyhow!("Only text and binary frames can contain messages")),
+    }
 }
@@ -0,0 +1,273 @@
+use std::sync::PoisonError;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Request, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, post},
+};
+use chat_lib::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    app_error::AppError,
+    ws::{remove_if_empty, room::RoomComponents},
+};
+
+/// The reason sent in the close frame of a connection the operator disconnects
+const DISCONNECT_REASON: &str = "Disconnected by the server operator";
+/// The reason sent in the close frame of every connection in a room the operator closes
+pub const CLOSE_REASON: &str = "The room was closed by the server operator";
+
+/// An active room and how many are in it
+#[derive(Debug, Serialize)]
+pub struct RoomSummary {
+    pub name: String,
+    pub users: usize,
+    /// The users whose connection dropped and who are waiting to resume it
+    pub suspended: usize,
+}
+
+/// A user in a room and the state of its connection
+#[derive(Debug, Serialize)]
+pub struct ConnectionSummary {
+    pub user: User,
+    pub suspended: bool,
+}
+
+/// A message from the operators, sent to one room or all of them
+#[derive(Debug, Deserialize)]
+pub struct Announcement {
+    pub message: String,
+    /// Every active room gets it if it's not set
+    pub room: Option<String>,
+}
+
+/// How many rooms or users an operation reached
+#[derive(Debug, Serialize)]
+pub struct Reached {
+    pub count: usize,
+}
+
+pub fn paths(state: AppState) -> Router {
+    Router::new()
+        .route("/admin/rooms", get(rooms))
+        .route("/admin/rooms/{room}", get(connections).delete(close_room))
+        .route("/admin/rooms/{room}/users/{id}", delete(disconnect))
+        .route("/admin/announce", post(announce))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+/// Lets the request through only if it has the admin token as a bearer token
+pub async fn authorize(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(expected) = state
+        .config
+        .admin
+        .token
+        .as_deref()
+        .filter(|t| !t.is_empty())
+    else {
+        return Err(AppError::forbidden("The admin api is disabled"));
+    };
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::unauthorized("The admin token is missing"))?;
+
+    // the digests are compared, so the time it takes doesn't tell how much of the token matched
+    if Sha256::digest(token.trim()) != Sha256::digest(expected) {
+        log::warn!("Rejected an admin request with a wrong token");
+        return Err(AppError::unauthorized("Wrong admin token"));
+    }
+
+    Ok(next.run(request).await)
+}
+
+async fn find_room(state: &AppState, name: &str) -> Result<RoomComponents, AppError> {
+    let room_components = state
+        .components
+        .lock()
+        .await
+        .get(name)
+        .cloned()
+        .ok_or_else(|| AppError::not_found(format!("Room {name} isn't active")))?;
+    let room_components = room_components.lock().await;
+
+    Ok(RoomComponents {
+        room: room_components.room.clone(),
+        tx: room_components.tx.clone(),
+    })
+}
+
+/// GET /admin/rooms
+pub async fn rooms(State(state): State<AppState>) -> Json<Vec<RoomSummary>> {
+    let components = state
+        .components
+        .lock()
+        .await
+        .iter()
+        .map(|(name, components)| (name.clone(), components.clone()))
+        .collect::<Vec<_>>();
+
+    let mut rooms = Vec::with_capacity(components.len());
+    for (name, components) in components {
+        let room = components.lock().await.room.clone();
+        let room = room.lock().await;
+        rooms.push(RoomSummary {
+            name,
+            users: room.user_count(),
+            suspended: room.suspended_count(),
+        });
+    }
+    rooms.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(rooms)
+}
+
+/// GET /admin/rooms/{room}
+pub async fn connections(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<ConnectionSummary>>, AppError> {
+    let RoomComponents { room, .. } = find_room(&state, &name).await?;
+    let room = room.lock().await;
+    let connections = room
+        .get_all_users()
+        .into_iter()
+        .map(|user| ConnectionSummary {
+            suspended: room.is_suspended(user.get_id()),
+            user,
+        })
+        .collect();
+
+    Ok(Json(connections))
+}
+
+/// DELETE /admin/rooms/{room}/users/{id}
+pub async fn disconnect(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, Uuid)>,
+) -> Result<StatusCode, AppError> {
+    let RoomComponents { room, tx } = find_room(&state, &name).await?;
+    {
+        let mut room = room.lock().await;
+        if !room.close_connection(&id, DISCONNECT_REASON) {
+            // a suspended connection isn't there to disconnect itself
+            let user = room
+                .remove_suspended(&id)
+                .ok_or_else(|| AppError::not_found(format!("User {id} isn't in room {name}")))?;
+            let msg = ServerMessage::UserLeft(user);
+            room.record(&msg);
+            let _ = tx.send(msg);
+        }
+    }
+    log::info!("The operator disconnected user {id} from room {name}");
+    remove_if_empty(&state, &name, &room).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /admin/rooms/{room}
+///
+/// Disconnects everyone and forgets the room with its history,
+/// nobody can join it again until the server restarts
+pub async fn close_room(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Reached>, AppError> {
+    let RoomComponents { room, tx } = {
+        let mut components = state.components.lock().await;
+        let room_components = components
+            .remove(&name)
+            .ok_or_else(|| AppError::not_found(format!("Room {name} isn't active")))?;
+        state
+            .closed_rooms
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.clone());
+        let room_components = room_components.lock().await;
+        // closed before the lock is released, so the joins already on their way are refused
+        room_components.room.lock().await.close();
+
+        RoomComponents {
+            room: room_components.room.clone(),
+            tx: room_components.tx.clone(),
+        }
+    };
+    let count = {
+        let mut room = room.lock().await;
+        let users = room.get_all_users();
+        for user in &users {
+            if !room.close_connection(user.get_id(), CLOSE_REASON)
+                && let Some(user) = room.remove_suspended(user.get_id())
+            {
+                let _ = tx.send(ServerMessage::UserLeft(user));
+            }
+        }
+
+        users.len()
+    };
+
+    let storage = state.storage.clone();
+    let stored = name.clone();
+    match tokio::task::spawn_blocking(move || storage.remove_room(&stored)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => log::error!("Couldn't remove room {name} from the storage: {err}"),
+        Err(err) => log::error!("Removing room {name} from the storage panicked: {err}"),
+    }
+    log::info!("The operator closed room {name}, disconnecting {count} users");
+
+    Ok(Json(Reached { count }))
+}
+
+/// POST /admin/announce
+pub async fn announce(
+    State(state): State<AppState>,
+    Json(Announcement { message, room }): Json<Announcement>,
+) -> Result<Json<Reached>, AppError> {
+    let message = message.trim().to_string();
+    if message.is_empty() {
+        return Err(AppError::bad_request("The announcement is empty"));
+    }
+
+    let senders = if let Some(name) = &room {
+        vec![find_room(&state, name).await?.tx]
+    } else {
+        let components = state
+            .components
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut senders = Vec::with_capacity(components.len());
+        for components in components {
+            senders.push(components.lock().await.tx.clone());
+        }
+        senders
+    };
+
+    for tx in &senders {
+        let _ = tx.send(ServerMessage::Announcement(message.clone()));
+    }
+    log::info!(
+        "The operator announced to {} rooms: {message}",
+        senders.len()
+    );
+
+    Ok(Json(Reached {
+        count: senders.len(),
+    }))
+}
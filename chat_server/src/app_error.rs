@@ -3,9 +3,20 @@ use axum::{
     response::{IntoResponse, Response},
 };
 
+#[derive(Debug)]
 pub enum AppError {
     BadRequest(String),
     ServerError(String),
+    /// The request needs a valid session
+    Unauthorized(String),
+    /// The request isn't allowed for anyone
+    Forbidden(String),
+    /// The thing the request is about doesn't exist
+    NotFound(String),
+    /// The client sent too many requests, it should try again later
+    TooManyRequests(String),
+    /// The server can't take the request right now, for example because it's shutting down
+    Unavailable(String),
 }
 
 impl IntoResponse for AppError {
@@ -16,6 +27,11 @@ impl IntoResponse for AppError {
         let res = match self {
             AppError::BadRequest(_) => res.status(400),
             AppError::ServerError(_) => res.status(500),
+            AppError::Unauthorized(_) => res.status(401),
+            AppError::Forbidden(_) => res.status(403),
+            AppError::NotFound(_) => res.status(404),
+            AppError::TooManyRequests(_) => res.status(429),
+            AppError::Unavailable(_) => res.status(503),
         };
 
         res.body(Body::new(msg.to_string()))
@@ -35,9 +51,40 @@ impl AppError {
         Self::ServerError(msg.to_string())
     }
 
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn unauthorized(msg: impl ToString) -> Self {
+        Self::Unauthorized(msg.to_string())
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn forbidden(msg: impl ToString) -> Self {
+        Self::Forbidden(msg.to_string())
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn not_found(msg: impl ToString) -> Self {
+        Self::NotFound(msg.to_string())
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn too_many_requests(msg: impl ToString) -> Self {
+        Self::TooManyRequests(msg.to_string())
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn unavailable(msg: impl ToString) -> Self {
+        Self::Unavailable(msg.to_string())
+    }
+
     fn message(&self) -> &str {
         match self {
-            AppError::BadRequest(msg) | AppError::ServerError(msg) => msg,
+            AppError::BadRequest(msg)
+            | AppError::ServerError(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::TooManyRequests(msg)
+            | AppError::Unavailable(msg) => msg,
         }
     }
 }
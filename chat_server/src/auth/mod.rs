@@ -0,0 +1,179 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, header::AUTHORIZATION},
+    routing::post,
+};
+use chat_lib::{
+    auth::{Credentials, Session},
+    prelude::*,
+};
+use rustrict::CensorStr;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    app_error::AppError,
+    clients::{admit, client_ip},
+    metrics::PasswordTarget,
+    storage::{AccountRecord, SyncStorage},
+};
+
+pub(crate) mod password;
+mod token;
+
+pub use token::TokenKey;
+
+pub fn paths(state: AppState) -> Router {
+    Router::new()
+        .route("/auth/register", post(register))
+        .route("/auth/login", post(login))
+        .with_state(state)
+}
+
+/// POST /auth/register
+///
+/// Registering counts against the ip's limits like logging in, as hashing the password is costly,
+/// a name that's already registered counts as a failed entry
+pub async fn register(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(Credentials { name, password }): Json<Credentials>,
+) -> Result<Json<Session>, AppError> {
+    if !state.config.auth.allow_registration {
+        return Err(AppError::forbidden("Registration is disabled"));
+    }
+    let ip = client_ip(peer.ip(), &headers, &state.config.trusted_proxies);
+    let (_client, penalties) = admit(&state, ip)?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "The name has to be between 1 and {MAX_NAME_LENGTH} characters"
+        )));
+    }
+    if name.is_inappropriate() {
+        return Err(AppError::bad_request("Inappropriate name"));
+    }
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "The password has to be at least {MIN_PASSWORD_LENGTH} characters"
+        )));
+    }
+
+    let storage = state.storage.clone();
+    let (account, created) = tokio::task::spawn_blocking(move || {
+        let account = AccountRecord {
+            id: Uuid::new_v4(),
+            name,
+            password_hash: password::hash(&password)?,
+        };
+        let created = storage.create_account(&account)?;
+        anyhow::Ok((account, created))
+    })
+    .await
+    .map_err(AppError::server_error)?
+    .map_err(AppError::server_error)?;
+
+    if !created {
+        penalties.lock().fail_entry(&state.config.limits);
+        return Err(AppError::bad_request("The name is already registered"));
+    }
+    log::info!("Registered account {} ({})", account.name, account.id);
+
+    Ok(Json(session(&state, account)))
+}
+
+/// POST /auth/login
+///
+/// Failed logins count against the ip, like wrong room passwords, without muting its chat
+pub async fn login(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(Credentials { name, password }): Json<Credentials>,
+) -> Result<Json<Session>, AppError> {
+    let ip = client_ip(peer.ip(), &headers, &state.config.trusted_proxies);
+    let (_client, penalties) = admit(&state, ip)?;
+
+    let storage = state.storage.clone();
+    let (account, matches) = tokio::task::spawn_blocking(move || {
+        let account = storage.load_account(name.trim())?;
+        // unknown names take as long as wrong passwords, so they can't be told apart
+        let matches = if let Some(account) = &account {
+            password::verify(&password, &account.password_hash)
+        } else {
+            password::verify_dummy(&password);
+            false
+        };
+        anyhow::Ok((account, matches))
+    })
+    .await
+    .map_err(AppError::server_error)?
+    .map_err(AppError::server_error)?;
+
+    match account {
+        Some(account) if matches => Ok(Json(session(&state, account))),
+        _ => {
+            state.metrics.wrong_password(PasswordTarget::Account);
+            penalties.lock().fail_entry(&state.config.limits);
+            Err(AppError::unauthorized("Wrong name or password"))
+        }
+    }
+}
+
+fn session(state: &AppState, account: AccountRecord) -> Session {
+    let user = User::new(account.id, account.name);
+    Session {
+        token: state.tokens.issue(&user),
+        user,
+    }
+}
+
+/// The account the request is made by, `None` for guests
+///
+/// The token is taken from the `Authorization: Bearer` header, or from `query_token`
+/// for clients that can't set headers on a websocket upgrade
+///
+/// # Errors
+///
+/// This function errors if there's a token, but it's invalid or expired
+pub fn authenticate(
+    headers: &HeaderMap,
+    query_token: Option<&str>,
+    tokens: &TokenKey,
+) -> Result<Option<User>, AppError> {
+    let header_token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let Some(token) = header_token.or(query_token) else {
+        return Ok(None);
+    };
+
+    tokens
+        .verify(token.trim())
+        .map(Some)
+        .ok_or_else(|| AppError::unauthorized("The session token is invalid or expired"))
+}
+
+/// Whether the name belongs to an account other than `user`'s, guests can't use those names
+pub async fn is_name_reserved(storage: &SyncStorage, name: &str, user: Option<&Uuid>) -> bool {
+    let storage = storage.clone();
+    let trimmed = name.trim().to_string();
+    let account = tokio::task::spawn_blocking(move || storage.load_account(&trimmed)).await;
+    match account
+        .map_err(anyhow::Error::from)
+        .and_then(|account| account)
+    {
+        Ok(account) => account.is_some_and(|account| Some(&account.id) != user),
+        Err(err) => {
+            log::error!("Couldn't look up account {name}: {err}");
+            false
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::LazyLock;
+
+use argon2::{
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+
+/// Hashes the password with a fresh salt into a PHC string
+///
+/// # Errors
+///
+/// This function errors if argon2 fails
+pub fn hash(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| anyhow::anyhow!("Couldn't hash password: {err}"))?;
+
+    Ok(hash.to_string())
+}
+
+/// Whether the password matches the PHC string made by [`hash`]
+#[must_use]
+pub fn verify(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// A hash no password is checked against on purpose,
+/// verifying against it for unknown names makes them take as long as known ones
+static DUMMY_HASH: LazyLock<Option<String>> = LazyLock::new(|| hash("not a password").ok());
+
+/// Verifies the password against nothing, as slow as [`verify`]
+pub fn verify_dummy(password: &str) {
+    if let Some(hash) = DUMMY_HASH.as_deref() {
+        let _ = verify(password, hash);
+    }
+}
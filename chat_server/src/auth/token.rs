@@ -0,0 +1,93 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chat_lib::prelude::*;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a session token vouches for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Claims {
+    id: Uuid,
+    name: String,
+    /// Unix timestamp in seconds after which the token isn't accepted
+    exp: u64,
+}
+
+/// Signs and verifies session tokens, a token is `base64(claims).base64(signature)`
+pub struct TokenKey {
+    key: Vec<u8>,
+    lifetime: Duration,
+}
+
+impl std::fmt::Debug for TokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<TokenKey>")
+    }
+}
+
+impl TokenKey {
+    /// Creates a key from the secret, or a random one if there's none or it's empty,
+    /// tokens signed with a random key are only valid until the server restarts
+    #[must_use]
+    pub fn new(secret: Option<&str>, lifetime: Duration) -> Self {
+        let key = if let Some(secret) = secret.filter(|secret| !secret.is_empty()) {
+            secret.as_bytes().to_vec()
+        } else {
+            log::warn!("No token secret is configured, sessions will end when the server stops");
+            let mut key = vec![0; 32];
+            OsRng.fill_bytes(&mut key);
+            key
+        };
+
+        Self { key, lifetime }
+    }
+
+    #[must_use]
+    pub fn issue(&self, user: &User) -> String {
+        let exp = now().saturating_add(self.lifetime.as_secs());
+        let claims = Claims {
+            id: *user.get_id(),
+            name: user.get_name().to_string(),
+            exp,
+        };
+        let claims =
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("Serialize shouldn't fail"));
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&claims).finalize().into_bytes());
+
+        format!("{claims}.{signature}")
+    }
+
+    /// Returns the user of the token, if it's signed by this key and hasn't expired
+    #[must_use]
+    pub fn verify(&self, token: &str) -> Option<User> {
+        let (claims, signature) = token.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(claims).verify_slice(&signature).ok()?;
+
+        let claims = URL_SAFE_NO_PAD.decode(claims).ok()?;
+        let claims = serde_json::from_slice::<Claims>(&claims).ok()?;
+        if claims.exp < now() {
+            return None;
+        }
+
+        Some(User::new(claims.id, claims.name))
+    }
+
+    fn mac(&self, claims: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        mac.update(claims.as_bytes());
+        mac
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
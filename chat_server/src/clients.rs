@@ -0,0 +1,431 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+use axum::http::HeaderMap;
+use rustrict::Context;
+
+use crate::{AppState, app_error::AppError, config::LimitsConfig};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The address the request comes from,
+/// taken from `X-Forwarded-For` if it came through one of the trusted proxies
+///
+/// The header is read from the right, the first address that isn't a trusted proxy is the client,
+/// anything left of it could have been made up by the client
+#[must_use]
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let peer = peer.to_canonical();
+    if !trusted_proxies.contains(&peer) {
+        return peer;
+    }
+
+    let forwarded = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+
+    let mut client = peer;
+    for hop in forwarded.into_iter().rev() {
+        let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip.to_canonical();
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+    }
+
+    client
+}
+
+/// What a strike ended in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strike {
+    TimedOut,
+    /// The ip went over the strike limit, its connection should be closed
+    StruckOut,
+}
+
+/// Counts the connection against the ip, if the ip is within its limits
+///
+/// # Errors
+///
+/// This function errors if the ip is over one of its limits
+pub fn admit(state: &AppState, ip: IpAddr) -> Result<(ClientGuard, SharedPenalties), AppError> {
+    state
+        .clients
+        .connect(ip, &state.config.limits)
+        .map_err(|refusal| {
+            log::info!("Refused a connection from {ip}: {refusal:?}");
+            AppError::too_many_requests(refusal.message())
+        })
+}
+
+/// The rate limit and filter state of an ip, shared by all of its connections,
+/// so reconnecting doesn't clear a timeout
+#[derive(Debug)]
+pub struct Penalties {
+    ctx: Context,
+    /// When the recent messages were sent, to check the message limit
+    message_times: VecDeque<Instant>,
+    strikes: usize,
+    last_strike: Option<Instant>,
+    end_of_timeout: Option<Instant>,
+    /// Wrong passwords and taken names, counted apart from the strikes so they don't mute the chat
+    failed_entries: usize,
+    last_failed_entry: Option<Instant>,
+}
+
+impl Default for Penalties {
+    fn default() -> Self {
+        Self {
+            ctx: Context::new(),
+            message_times: VecDeque::new(),
+            strikes: 0,
+            last_strike: None,
+            end_of_timeout: None,
+            failed_entries: 0,
+            last_failed_entry: None,
+        }
+    }
+}
+
+impl Penalties {
+    #[must_use]
+    pub const fn ctx(&self) -> &Context {
+        &self.ctx
+    }
+
+    pub const fn ctx_mut(&mut self) -> &mut Context {
+        &mut self.ctx
+    }
+
+    pub fn count_message(&mut self) {
+        self.message_times.push_back(Instant::now());
+    }
+
+    pub fn can_send_message(&mut self, limits: &LimitsConfig) -> bool {
+        if let Some(t) = self.end_of_timeout {
+            if t < Instant::now() {
+                self.end_of_timeout = None;
+            } else {
+                return false;
+            }
+        }
+
+        let window = limits.timeout_window();
+        self.message_times.retain(|i| i.elapsed() < window);
+
+        self.message_times.len() < limits.message_limit
+    }
+
+    /// Adds a strike, and a timeout unless the ip went over the strike limit
+    pub fn strike(&mut self, limits: &LimitsConfig) -> Strike {
+        self.forget_old_strikes(limits);
+        self.strikes += 1;
+        self.last_strike = Some(Instant::now());
+
+        if self.strikes > limits.max_strikes {
+            return Strike::StruckOut;
+        }
+
+        let duration = limits.timeout_duration();
+        if let Some(t) = self.end_of_timeout {
+            self.end_of_timeout = Some(t + duration);
+        } else {
+            self.end_of_timeout = Some(Instant::now() + duration);
+        }
+
+        Strike::TimedOut
+    }
+
+    /// Whether the ip went over the strike limit and hasn't waited it out yet
+    pub fn is_struck_out(&mut self, limits: &LimitsConfig) -> bool {
+        self.forget_old_strikes(limits);
+        self.strikes > limits.max_strikes
+    }
+
+    /// Counts a failed login, registration or room password,
+    /// too many of them lock the ip out the same way too many strikes do
+    pub fn fail_entry(&mut self, limits: &LimitsConfig) {
+        self.forget_old_failed_entries(limits);
+        self.failed_entries += 1;
+        self.last_failed_entry = Some(Instant::now());
+    }
+
+    /// Whether the ip failed too many entries recently
+    pub fn is_locked_out(&mut self, limits: &LimitsConfig) -> bool {
+        self.forget_old_failed_entries(limits);
+        self.failed_entries > limits.max_strikes
+    }
+
+    fn forget_old_failed_entries(&mut self, limits: &LimitsConfig) {
+        if self
+            .last_failed_entry
+            .is_some_and(|t| t.elapsed() >= limits.strike_reset())
+        {
+            self.failed_entries = 0;
+            self.last_failed_entry = None;
+        }
+    }
+
+    fn forget_old_strikes(&mut self, limits: &LimitsConfig) {
+        if self
+            .last_strike
+            .is_some_and(|t| t.elapsed() >= limits.strike_reset())
+        {
+            self.strikes = 0;
+            self.last_strike = None;
+        }
+    }
+
+    /// Whether there's nothing left worth remembering about the ip
+    fn is_idle(&self, limits: &LimitsConfig) -> bool {
+        let now = Instant::now();
+        self.last_strike
+            .is_none_or(|t| t.elapsed() >= limits.strike_reset())
+            && self
+                .last_failed_entry
+                .is_none_or(|t| t.elapsed() >= limits.strike_reset())
+            && self.end_of_timeout.is_none_or(|t| t < now)
+            && self
+                .message_times
+                .back()
+                .is_none_or(|t| t.elapsed() >= limits.timeout_window())
+    }
+}
+
+/// The penalties of an ip, a std mutex, as it's never held across an await
+#[derive(Debug, Clone, Default)]
+pub struct SharedPenalties(Arc<Mutex<Penalties>>);
+
+impl SharedPenalties {
+    pub fn lock(&self) -> MutexGuard<'_, Penalties> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Why a connection attempt is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    TooManyConnections,
+    TooManyUpgrades,
+    /// The ip went over the strike limit recently
+    StruckOut,
+    /// The ip got too many passwords wrong recently
+    LockedOut,
+}
+
+impl Refusal {
+    #[must_use]
+    pub const fn message(self) -> &'static str {
+        match self {
+            Refusal::TooManyConnections => "Too many connections from this address",
+            Refusal::TooManyUpgrades => "Too many connection attempts, try again later",
+            Refusal::StruckOut => "Too many strikes, try again later",
+            Refusal::LockedOut => "Too many failed attempts, try again later",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Client {
+    connections: usize,
+    /// When the recent connection attempts were made
+    upgrades: VecDeque<Instant>,
+    penalties: SharedPenalties,
+}
+
+type Clients = Arc<Mutex<HashMap<IpAddr, Client>>>;
+
+fn lock(clients: &Clients) -> MutexGuard<'_, HashMap<IpAddr, Client>> {
+    clients.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keeps the connection counted against its ip until it's dropped
+#[derive(Debug)]
+pub struct ClientGuard {
+    clients: Clients,
+    ip: IpAddr,
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        if let Some(client) = lock(&self.clients).get_mut(&self.ip) {
+            client.connections = client.connections.saturating_sub(1);
+        }
+    }
+}
+
+/// The connections and penalties of every client ip
+#[derive(Debug, Clone, Default)]
+pub struct ClientTracker {
+    /// A std mutex, so the guards can let go of their connection when they're dropped
+    clients: Clients,
+}
+
+impl ClientTracker {
+    /// Counts the connection attempt of the ip,
+    /// returns the guard that keeps the connection counted and the ip's penalties
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the ip is over one of its limits
+    pub fn connect(
+        &self,
+        ip: IpAddr,
+        limits: &LimitsConfig,
+    ) -> Result<(ClientGuard, SharedPenalties), Refusal> {
+        let mut clients = lock(&self.clients);
+        Self::prune(&mut clients, limits);
+        let client = clients.entry(ip).or_default();
+
+        let window = limits.upgrade_window();
+        client.upgrades.retain(|i| i.elapsed() < window);
+        // refused attempts aren't recorded, so waiting out the window is enough to get back in
+        if limits.upgrade_limit != 0 && client.upgrades.len() >= limits.upgrade_limit {
+            return Err(Refusal::TooManyUpgrades);
+        }
+        if limits.max_connections_per_ip != 0 && client.connections >= limits.max_connections_per_ip
+        {
+            return Err(Refusal::TooManyConnections);
+        }
+        if client.penalties.lock().is_struck_out(limits) {
+            return Err(Refusal::StruckOut);
+        }
+        if client.penalties.lock().is_locked_out(limits) {
+            return Err(Refusal::LockedOut);
+        }
+
+        client.upgrades.push_back(Instant::now());
+
+        client.connections += 1;
+        let guard = ClientGuard {
+            clients: self.clients.clone(),
+            ip,
+        };
+
+        Ok((guard, client.penalties.clone()))
+    }
+
+    /// Forgets the ips that have nothing left to remember,
+    /// penalties held by a connection or a suspended session are kept
+    fn prune(clients: &mut HashMap<IpAddr, Client>, limits: &LimitsConfig) {
+        let window = limits.upgrade_window();
+        clients.retain(|_, client| {
+            client.connections > 0
+                || client.upgrades.back().is_some_and(|i| i.elapsed() < window)
+                || Arc::strong_count(&client.penalties.0) > 1
+                || !client.penalties.lock().is_idle(limits)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+
+    #[test]
+    fn failed_entries_dont_mute_the_chat() {
+        let limits = LimitsConfig::default();
+        let mut penalties = Penalties::default();
+        penalties.fail_entry(&limits);
+
+        assert!(penalties.can_send_message(&limits));
+        assert!(!penalties.is_locked_out(&limits));
+    }
+
+    #[test]
+    fn too_many_failed_entries_lock_the_ip_out() {
+        let limits = LimitsConfig::default();
+        let tracker = ClientTracker::default();
+        let (_guard, penalties) = tracker
+            .connect(IP, &limits)
+            .expect("The first attempt is fine");
+        for _ in 0..=limits.max_strikes {
+            penalties.lock().fail_entry(&limits);
+        }
+
+        assert_eq!(
+            tracker.connect(IP, &limits).map(|_| ()),
+            Err(Refusal::LockedOut)
+        );
+    }
+
+    #[test]
+    fn refused_attempts_dont_fill_the_upgrade_window() {
+        let limits = LimitsConfig {
+            upgrade_limit: 2,
+            max_connections_per_ip: 0,
+            ..LimitsConfig::default()
+        };
+        let tracker = ClientTracker::default();
+        for _ in 0..limits.upgrade_limit {
+            assert!(tracker.connect(IP, &limits).is_ok());
+        }
+        for _ in 0..5 {
+            assert_eq!(
+                tracker.connect(IP, &limits).map(|_| ()),
+                Err(Refusal::TooManyUpgrades)
+            );
+        }
+
+        let upgrades = lock(&tracker.clients)[&IP].upgrades.len();
+        assert_eq!(upgrades, limits.upgrade_limit);
+    }
+
+    const PROXY: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 9));
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_FORWARDED_FOR,
+            value.parse().expect("The header value should be valid"),
+        );
+
+        headers
+    }
+
+    #[test]
+    fn untrusted_peers_cant_forward_an_address() {
+        let headers = forwarded_for("203.0.113.9");
+
+        assert_eq!(client_ip(IP, &headers, &[PROXY]), IP);
+    }
+
+    #[test]
+    fn trusted_proxies_forward_the_client_address() {
+        let headers = forwarded_for("203.0.113.9");
+
+        assert_eq!(client_ip(PROXY, &headers, &[PROXY]), CLIENT);
+        // the peer of a dual stack listener is an ipv4 mapped address
+        let mapped = IpAddr::V6(std::net::Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped());
+        assert_eq!(client_ip(mapped, &headers, &[PROXY]), CLIENT);
+    }
+
+    #[test]
+    fn addresses_left_of_the_client_are_ignored() {
+        let inner = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+        let headers = forwarded_for("198.51.100.1, 203.0.113.9, 10.0.0.2");
+
+        assert_eq!(client_ip(PROXY, &headers, &[PROXY, inner]), CLIENT);
+    }
+
+    #[test]
+    fn unreadable_hops_stop_at_the_last_trusted_proxy() {
+        let headers = forwarded_for("203.0.113.9, not an address");
+
+        assert_eq!(client_ip(PROXY, &headers, &[PROXY]), PROXY);
+        assert_eq!(client_ip(PROXY, &HeaderMap::new(), &[PROXY]), PROXY);
+    }
+}
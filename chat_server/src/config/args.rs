@@ -0,0 +1,32 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Reads the config from this file instead of the default location
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Sets the address the server listens on
+    #[arg(short, long)]
+    pub bind: Option<SocketAddr>,
+    /// Serves https and wss with this PEM certificate chain, needs `--tls-key`
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM private key of `--tls-cert`
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// Sets the directory of the room database
+    #[arg(long)]
+    pub data: Option<PathBuf>,
+    /// Keeps the rooms only in memory, they are lost when the server stops
+    #[arg(long, conflicts_with = "data")]
+    pub in_memory: bool,
+    /// Sets the amount of events a room replays to new connections
+    #[arg(long)]
+    pub history_size: Option<usize>,
+    /// Prints the default config to stdout
+    #[arg(long)]
+    pub default_config: bool,
+}
@@ -0,0 +1,378 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    num::NonZero,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::bail;
+use chat_lib::filter::FilterPolicy;
+use dirs::{data_dir, home_dir};
+use rustrict::{ContextProcessingOptions, ContextRateLimitOptions};
+use serde::{Deserialize, Serialize};
+
+use super::args::Cli;
+
+use crate::consts::MAX_HISTORY_SIZE;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// The address the server listens on
+    pub bind: SocketAddr,
+    /// The reverse proxies whose `X-Forwarded-For` header is believed,
+    /// requests from anywhere else are limited by the address they come from
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Serves https and wss with this certificate, plain http is served if it's not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// How long the connections get to close when the server shuts down
+    pub shutdown_timeout_secs: u64,
+    /// The rooms that are open from the start and kept when everyone leaves them
+    pub rooms: Vec<String>,
+    pub limits: LimitsConfig,
+    pub filter: FilterConfig,
+    pub storage: StorageConfig,
+    pub auth: AuthConfig,
+    pub admin: AdminConfig,
+    pub metrics: MetricsConfig,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Max amount of messages a connection can send in a timeout window
+    pub message_limit: usize,
+    pub timeout_window_secs: u64,
+    /// The timeout added when a connection goes over the message limit
+    pub timeout_duration_secs: u64,
+    /// The amount of timeouts after which the connection is closed,
+    /// the strikes are counted for every connection of the ip together,
+    /// it's also how many failed logins and room passwords an ip can have before it's locked out
+    pub max_strikes: usize,
+    /// How long an ip has to go without a strike for its strikes to be forgotten
+    pub strike_reset_secs: u64,
+    /// Max amount of open connections from an ip, 0 means no limit
+    pub max_connections_per_ip: usize,
+    /// Max amount of connection attempts from an ip in an upgrade window, 0 means no limit
+    pub upgrade_limit: usize,
+    pub upgrade_window_secs: u64,
+    pub heartbeat_frequency_secs: u64,
+    /// The amount of messages a room buffers for its slowest connection
+    pub broadcast_buffer_size: usize,
+    /// The amount of events a room keeps to replay for new connections
+    pub history_size: usize,
+    /// How long a dropped connection's user stays in the room waiting for it to resume,
+    /// 0 disables resuming
+    pub resume_grace_secs: u64,
+}
+
+/// The options of the profanity filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Messages get cut to this many characters, 0 means no limit
+    pub character_limit: usize,
+    /// The minimum time between messages before the filter starts to restrict the user
+    pub rate_limit_ms: u64,
+    /// The amount of messages allowed beyond the rate limit
+    pub rate_limit_burst: u8,
+    /// The policy of the rooms without their own
+    pub policy: FilterPolicy,
+    /// The policies of specific rooms by name, their owners can't change them
+    pub rooms: HashMap<String, FilterPolicy>,
+}
+
+/// The options of the registered accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Whether new accounts can be registered
+    pub allow_registration: bool,
+    /// The secret session tokens are signed with,
+    /// a random one is used if it's not set or empty, so sessions end when the server stops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_secret: Option<String>,
+    /// How long a session token is valid after logging in
+    pub token_lifetime_secs: u64,
+}
+
+/// The certificate the server terminates tls with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// The certificate chain in PEM format
+    pub cert: PathBuf,
+    /// The private key of the certificate in PEM format
+    pub key: PathBuf,
+    /// How often the files are checked for a renewed certificate, 0 disables checking,
+    /// the certificate is also reloaded on SIGHUP
+    #[serde(default = "TlsConfig::default_reload_interval")]
+    pub reload_interval_secs: u64,
+}
+
+/// The options of the `/admin` api
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// The bearer token the admin requests have to send, the api is disabled if it's not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// The options of the `/metrics` endpoint
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Whether `/metrics` is served, only with the admin token, as the room names show up in it
+    pub enabled: bool,
+}
+
+/// Where the rooms and their history are kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum StorageConfig {
+    /// Everything is lost when the server stops
+    Memory,
+    /// An embedded database in the given directory
+    Sled { path: PathBuf },
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8000)),
+            trusted_proxies: Vec::new(),
+            tls: None,
+            shutdown_timeout_secs: 10,
+            rooms: Vec::new(),
+            limits: LimitsConfig::default(),
+            filter: FilterConfig::default(),
+            storage: StorageConfig::default(),
+            auth: AuthConfig::default(),
+            admin: AdminConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            allow_registration: true,
+            token_secret: None,
+            token_lifetime_secs: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            message_limit: 64,
+            timeout_window_secs: 2,
+            timeout_duration_secs: 10,
+            max_strikes: 10,
+            strike_reset_secs: 10 * 60,
+            max_connections_per_ip: 16,
+            upgrade_limit: 20,
+            upgrade_window_secs: 60,
+            heartbeat_frequency_secs: 30,
+            broadcast_buffer_size: 32,
+            history_size: 100,
+            resume_grace_secs: 30,
+        }
+    }
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            character_limit: 200,
+            rate_limit_ms: 500,
+            rate_limit_burst: 5,
+            policy: FilterPolicy::default(),
+            rooms: HashMap::new(),
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self::Sled {
+            path: default_data_path(),
+        }
+    }
+}
+
+fn default_data_path() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("data/rs_chat_server")
+    } else {
+        data_dir()
+            .map(|dir| dir.join("rs_chat/server_data"))
+            .or_else(|| home_dir().map(|dir| dir.join(".rs_chat/server_data")))
+            .unwrap_or_else(|| PathBuf::from("rs_chat_server_data"))
+    }
+}
+
+impl ServerConfig {
+    #[must_use]
+    pub fn merge(mut self, args: &Cli) -> Self {
+        if let Some(bind) = args.bind {
+            self.bind = bind;
+        }
+
+        if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+            self.tls = Some(TlsConfig {
+                cert: cert.clone(),
+                key: key.clone(),
+                reload_interval_secs: self
+                    .tls
+                    .as_ref()
+                    .map_or_else(TlsConfig::default_reload_interval, |tls| {
+                        tls.reload_interval_secs
+                    }),
+            });
+        }
+
+        if args.in_memory {
+            self.storage = StorageConfig::Memory;
+        } else if let Some(path) = &args.data {
+            self.storage = StorageConfig::Sled { path: path.clone() };
+        }
+
+        if let Some(history_size) = args.history_size {
+            self.limits.history_size = history_size;
+        }
+
+        self
+    }
+}
+
+impl ServerConfig {
+    /// Checks the values the server can't run with
+    ///
+    /// # Errors
+    ///
+    /// This function errors if one of the limits is out of range
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let limits = &self.limits;
+        if limits.broadcast_buffer_size == 0 {
+            bail!("limits.broadcast_buffer_size has to be at least 1");
+        }
+        if limits.heartbeat_frequency_secs == 0 {
+            bail!("limits.heartbeat_frequency_secs has to be at least 1");
+        }
+        if limits.history_size > MAX_HISTORY_SIZE {
+            bail!("limits.history_size can't be more than {MAX_HISTORY_SIZE}");
+        }
+
+        Ok(())
+    }
+
+    #[must_use]
+    pub const fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+impl TlsConfig {
+    const fn default_reload_interval() -> u64 {
+        60 * 60
+    }
+
+    #[must_use]
+    pub const fn reload_interval(&self) -> Duration {
+        Duration::from_secs(self.reload_interval_secs)
+    }
+}
+
+impl AuthConfig {
+    #[must_use]
+    pub const fn token_lifetime(&self) -> Duration {
+        Duration::from_secs(self.token_lifetime_secs)
+    }
+}
+
+impl LimitsConfig {
+    #[must_use]
+    pub const fn timeout_window(&self) -> Duration {
+        Duration::from_secs(self.timeout_window_secs)
+    }
+
+    #[must_use]
+    pub const fn timeout_duration(&self) -> Duration {
+        Duration::from_secs(self.timeout_duration_secs)
+    }
+
+    #[must_use]
+    pub const fn strike_reset(&self) -> Duration {
+        Duration::from_secs(self.strike_reset_secs)
+    }
+
+    #[must_use]
+    pub const fn upgrade_window(&self) -> Duration {
+        Duration::from_secs(self.upgrade_window_secs)
+    }
+
+    #[must_use]
+    pub const fn resume_grace(&self) -> Duration {
+        Duration::from_secs(self.resume_grace_secs)
+    }
+
+    #[must_use]
+    pub const fn heartbeat_frequency(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_frequency_secs)
+    }
+}
+
+impl FilterConfig {
+    #[must_use]
+    pub fn context_options(&self) -> ContextProcessingOptions {
+        ContextProcessingOptions {
+            block_if_muted: false,
+            block_if_empty: false,
+            block_if_severely_inappropriate: true,
+            rate_limit: Some(ContextRateLimitOptions {
+                limit: Duration::from_millis(self.rate_limit_ms),
+                burst: self.rate_limit_burst,
+                ..Default::default()
+            }),
+            trim_whitespace: true,
+            character_limit: NonZero::new(self.character_limit),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(ServerConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn unusable_limits_are_refused() {
+        let mut config = ServerConfig::default();
+        config.limits.broadcast_buffer_size = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.limits.heartbeat_frequency_secs = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.limits.history_size = MAX_HISTORY_SIZE + 1;
+        assert!(config.validate().is_err());
+    }
+}
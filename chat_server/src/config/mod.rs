@@ -0,0 +1,71 @@
+#![allow(
+    clippy::print_stdout,
+    reason = "This runs before the logger is initialized"
+)]
+
+pub(crate) mod args;
+pub(crate) mod file;
+
+pub use args::*;
+pub use file::*;
+
+use std::{fs, io::ErrorKind, path::PathBuf, process};
+
+use anyhow::Context;
+use clap::Parser;
+use dirs::config_dir;
+
+/// Reads the config file and applies the command line arguments on top of it
+///
+/// # Errors
+///
+/// This function errors if the config file exists, but can't be read or parsed,
+/// or if the resulting config isn't valid
+pub fn init() -> anyhow::Result<ServerConfig> {
+    let cli = Cli::parse();
+    handle_flags(&cli);
+
+    let config = read_config(cli.config.as_ref())?
+        .unwrap_or_default()
+        .merge(&cli);
+    config.validate().context("Invalid config")?;
+
+    Ok(config)
+}
+
+/// Returns `None` if the default config file doesn't exist,
+/// a path given in the arguments has to exist
+fn read_config(path: Option<&PathBuf>) -> anyhow::Result<Option<ServerConfig>> {
+    let config_path = if let Some(path) = path {
+        path.clone()
+    } else {
+        let Some(mut dir) = config_dir() else {
+            return Ok(None);
+        };
+        dir.push("rs_chat/server.toml");
+        dir
+    };
+
+    let config = match fs::read_to_string(&config_path) {
+        Ok(config) => config,
+        Err(err) if err.kind() == ErrorKind::NotFound && path.is_none() => return Ok(None),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Couldn't read config {}", config_path.display()));
+        }
+    };
+
+    toml::from_str(&config)
+        .with_context(|| format!("Couldn't parse config {}", config_path.display()))
+        .map(Some)
+}
+
+/// Executes the trivial things and possibly exits
+fn handle_flags(cfg: &Cli) {
+    if cfg.default_config {
+        let default_cfg =
+            toml::to_string_pretty(&ServerConfig::default()).expect("Serializer shouldn't fail");
+        println!("{default_cfg}");
+        process::exit(0);
+    }
+}
@@ -1,16 +1,28 @@
 use std::time::Duration;
 
-pub const BROADCAST_BUFFER_SIZE: usize = 32;
+use chat_lib::Version;
 
-// Max amount of messages in a `TIMEOUT_WINDOW`
-pub const MESSAGE_LIMIT: usize = 64;
+pub const MAX_ROOM_LENGTH: usize = 25;
 
-pub const TIMEOUT_WINDOW: Duration = Duration::from_secs(2);
+/// The most words a room's filter allow or deny list can have
+pub const MAX_FILTER_WORDS: usize = 256;
+/// Max length of a word in a filter list in utf8 characters, longer ones are dropped
+pub const MAX_FILTER_WORD_LENGTH: usize = 32;
 
-pub const TIMEOUT_DURATION: Duration = Duration::from_secs(10);
+/// The longest a mute or ban can last, longer ones are shortened to it
+pub const MAX_SANCTION_SECS: u64 = 365 * 24 * 60 * 60;
 
-pub const HEARTBEAT_FREQUENCY: Duration = Duration::from_secs(30);
+/// The longest an invite to a room is valid, longer ones are shortened to it
+pub const MAX_INVITE_SECS: u64 = 30 * 24 * 60 * 60;
 
-pub const MAX_STRIKES: usize = 10;
+/// The most events a room can keep in its history, bigger configured sizes are refused
+pub const MAX_HISTORY_SIZE: usize = 10_000;
 
-pub const MAX_ROOM_LENGTH: usize = 25;
+/// How long after a change a room is saved, the changes made meanwhile are saved with it
+pub const ROOM_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// The least time between two typing signals of a connection the room hears about
+pub const TYPING_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The api versions the server can talk, [`Version::V1`] is kept for older clients
+pub const SUPPORTED_API_VERSIONS: &[Version] = &[Version::V1, Version::V2];
@@ -0,0 +1,255 @@
+use std::num::NonZero;
+
+use chat_lib::filter::{FilterMode, FilterPolicy};
+use rustrict::{BlockReason, CensorStr, Context, ContextProcessingOptions};
+
+use crate::consts::{MAX_FILTER_WORD_LENGTH, MAX_FILTER_WORDS};
+
+/// Why a message didn't get through the filter
+#[derive(Debug)]
+pub enum Rejection {
+    /// The user got restricted, for example for going over the rate limit
+    Blocked(BlockReason),
+    /// The room rejects inappropriate messages instead of censoring them
+    Inappropriate,
+}
+
+/// A message that got through the filter
+#[derive(Debug)]
+pub struct Filtered {
+    pub text: String,
+    /// Whether any of it was replaced with `*`
+    pub censored: bool,
+}
+
+/// The form the word lists are compared in, without surrounding punctuation and lowercase
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Splits the token into the word and the whitespace after it
+fn split_word(token: &str) -> (&str, &str) {
+    let word = token.trim_end();
+    (word, &token[word.len()..])
+}
+
+/// Replaces the word with `*`, keeping the punctuation around it
+fn censor(word: &str) -> String {
+    let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let start = word.find(core).unwrap_or_default();
+    let end = start + core.len();
+
+    format!(
+        "{}{}{}",
+        &word[..start],
+        "*".repeat(core.chars().count()),
+        &word[end..]
+    )
+}
+
+/// What an allowed word is replaced with while the rest of the text is checked,
+/// so the filter can't catch it
+fn mask(word: &str) -> String {
+    "a".repeat(word.chars().count())
+}
+
+fn truncate(txt: &str, limit: Option<NonZero<usize>>) -> &str {
+    match limit.and_then(|limit| txt.char_indices().nth(limit.get())) {
+        Some((end, _)) => &txt[..end],
+        None => txt,
+    }
+}
+
+/// Cleans up the word lists, so they can be compared against the words of the messages
+#[must_use]
+pub fn normalize_policy(policy: FilterPolicy) -> FilterPolicy {
+    let clean = |words: Vec<String>| {
+        let mut words = words
+            .iter()
+            .map(|word| normalize(word))
+            .filter(|word| !word.is_empty() && word.chars().count() <= MAX_FILTER_WORD_LENGTH)
+            .collect::<Vec<_>>();
+        words.sort();
+        words.dedup();
+        words.truncate(MAX_FILTER_WORDS);
+        words
+    };
+
+    FilterPolicy {
+        mode: policy.mode,
+        allow: clean(policy.allow),
+        deny: clean(policy.deny),
+    }
+}
+
+/// Hides the allowed words and censors the denied ones,
+/// returns the text and whether any word was denied
+fn apply_lists(policy: &FilterPolicy, tokens: &[&str]) -> (String, bool) {
+    let mut denied = false;
+    let masked = tokens
+        .iter()
+        .map(|token| {
+            let (word, space) = split_word(token);
+            let normalized = normalize(word);
+            if policy.deny.contains(&normalized) {
+                denied = true;
+                format!("{}{space}", censor(word))
+            } else if policy.allow.contains(&normalized) {
+                format!("{}{space}", mask(word))
+            } else {
+                (*token).to_string()
+            }
+        })
+        .collect();
+
+    (masked, denied)
+}
+
+/// Whether the short text, like a name or a reaction, breaks the policy
+#[must_use]
+pub fn is_inappropriate(policy: &FilterPolicy, txt: &str) -> bool {
+    if policy.mode == FilterMode::Off {
+        return false;
+    }
+
+    let tokens = txt.split_inclusive(char::is_whitespace).collect::<Vec<_>>();
+    let (masked, denied) = apply_lists(policy, &tokens);
+
+    denied || masked.is_inappropriate()
+}
+
+/// Runs the message through the policy, returns what should be sent and if it was censored
+///
+/// # Errors
+///
+/// This function errors if the user is restricted or the room rejects the message
+pub fn filter_message(
+    policy: &FilterPolicy,
+    ctx: &mut Context,
+    opts: &ContextProcessingOptions,
+    txt: &str,
+) -> Result<Filtered, Rejection> {
+    let txt = txt.trim();
+    if policy.mode == FilterMode::Off {
+        return Ok(Filtered {
+            text: truncate(txt, opts.character_limit).to_string(),
+            censored: false,
+        });
+    }
+
+    let tokens = txt.split_inclusive(char::is_whitespace).collect::<Vec<_>>();
+    let (masked, denied) = apply_lists(policy, &tokens);
+    if denied && policy.mode == FilterMode::Reject {
+        return Err(Rejection::Inappropriate);
+    }
+
+    let censored = ctx
+        .process_with_options(masked.clone(), opts)
+        .map_err(Rejection::Blocked)?;
+    let caught = censored != truncate(&masked, opts.character_limit).trim();
+    if caught && policy.mode == FilterMode::Reject {
+        return Err(Rejection::Inappropriate);
+    }
+
+    // puts the allowed words back where they were left untouched
+    let unmasked = censored
+        .split_inclusive(char::is_whitespace)
+        .enumerate()
+        .map(|(i, token)| {
+            let (word, space) = split_word(token);
+            match tokens.get(i).map(|original| split_word(original).0) {
+                Some(original)
+                    if word == mask(original) && policy.allow.contains(&normalize(original)) =>
+                {
+                    format!("{original}{space}")
+                }
+                _ => token.to_string(),
+            }
+        })
+        .collect();
+
+    Ok(Filtered {
+        text: unmasked,
+        censored: denied || caught,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(mode: FilterMode, allow: &[&str], deny: &[&str]) -> FilterPolicy {
+        normalize_policy(FilterPolicy {
+            mode,
+            allow: allow.iter().map(ToString::to_string).collect(),
+            deny: deny.iter().map(ToString::to_string).collect(),
+        })
+    }
+
+    fn filter(policy: &FilterPolicy, txt: &str) -> Result<Filtered, Rejection> {
+        filter_message(
+            policy,
+            &mut Context::new(),
+            &ContextProcessingOptions::default(),
+            txt,
+        )
+    }
+
+    #[test]
+    fn word_lists_are_cleaned_up() {
+        let long = "a".repeat(MAX_FILTER_WORD_LENGTH + 1);
+        let policy = policy(
+            FilterMode::Censor,
+            &["Pear!", "pear", "", "..."],
+            &[&long, "Apple"],
+        );
+
+        assert_eq!(policy.allow, ["pear"]);
+        assert_eq!(policy.deny, ["apple"]);
+    }
+
+    #[test]
+    fn denied_words_are_censored() {
+        let policy = policy(FilterMode::Censor, &[], &["apple"]);
+        let filtered = filter(&policy, "I like Apple, a lot").expect("The message should pass");
+
+        assert_eq!(filtered.text, "I like *****, a lot");
+        assert!(filtered.censored);
+        assert!(is_inappropriate(&policy, "apple"));
+    }
+
+    #[test]
+    fn denied_words_are_rejected_in_reject_mode() {
+        let policy = policy(FilterMode::Reject, &[], &["apple"]);
+
+        assert!(matches!(
+            filter(&policy, "I like apples and apple"),
+            Err(Rejection::Inappropriate)
+        ));
+        assert!(filter(&policy, "I like apples").is_ok());
+    }
+
+    #[test]
+    fn nothing_is_filtered_when_the_filter_is_off() {
+        let policy = policy(FilterMode::Off, &[], &["apple"]);
+        let filtered = filter(&policy, "apple").expect("The message should pass");
+
+        assert_eq!(filtered.text, "apple");
+        assert!(!filtered.censored);
+        assert!(!is_inappropriate(&policy, "apple"));
+    }
+
+    #[test]
+    fn allowed_words_are_left_alone() {
+        let caught =
+            filter(&FilterPolicy::default(), "what the fuck").expect("The message should pass");
+        assert!(caught.censored);
+
+        let policy = policy(FilterMode::Censor, &["fuck"], &[]);
+        let filtered = filter(&policy, "what the fuck").expect("The message should pass");
+
+        assert_eq!(filtered.text, "what the fuck");
+        assert!(!filtered.censored);
+    }
+}
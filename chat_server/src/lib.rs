@@ -1,48 +1,185 @@
-use std::time::Duration;
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use axum::{
     BoxError, Router,
     error_handling::HandleErrorLayer,
-    http::{StatusCode, Uri},
+    http::{Request, StatusCode, Uri},
 };
+use tokio::{net::TcpListener, time::Instant};
+use tokio_util::task::TaskTracker;
 use tower::ServiceBuilder;
 use tower_http::trace::{self, TraceLayer};
-use tracing::Level;
+use tracing::{Level, Span};
 
-use crate::ws::SyncRoomComponents;
+use crate::{
+    auth::TokenKey, clients::ClientTracker, config::ServerConfig, metrics::Metrics,
+    shutdown::ShutdownSignal, storage::SyncStorage, ws::SyncRoomComponents,
+};
 
 #[derive(Clone)]
 struct AppState {
     components: SyncRoomComponents,
+    storage: SyncStorage,
+    config: Arc<ServerConfig>,
+    tokens: Arc<TokenKey>,
+    /// Every websocket connection, so the shutdown can wait for them to close
+    connections: TaskTracker,
+    shutdown: ShutdownSignal,
+    metrics: Arc<Metrics>,
+    /// The connections and penalties of every client ip
+    clients: ClientTracker,
+    /// The rooms the operator closed, nobody joins them until the server restarts,
+    /// only changed while holding the lock on the components
+    closed_rooms: Arc<Mutex<HashSet<String>>>,
 }
 
+impl AppState {
+    fn new(
+        storage: SyncStorage,
+        config: ServerConfig,
+        connections: TaskTracker,
+        shutdown: ShutdownSignal,
+    ) -> Self {
+        let tokens = TokenKey::new(
+            config.auth.token_secret.as_deref(),
+            config.auth.token_lifetime(),
+        );
+
+        Self {
+            components: ws::open_rooms(&storage, &config),
+            storage,
+            config: Arc::new(config),
+            tokens: Arc::new(tokens),
+            connections,
+            shutdown,
+            metrics: Arc::default(),
+            clients: ClientTracker::default(),
+            closed_rooms: Arc::default(),
+        }
+    }
+
+    /// A server with nothing saved and the default config, that never shuts down
+    #[cfg(test)]
+    fn test() -> Self {
+        use futures::{FutureExt, future};
+
+        Self::new(
+            Arc::new(storage::MemoryStorage::default()),
+            ServerConfig::default(),
+            TaskTracker::new(),
+            future::pending().boxed().shared(),
+        )
+    }
+}
+
+mod admin;
 mod app_error;
-mod config;
+mod auth;
+mod clients;
+pub mod config;
 mod consts;
+mod filter;
 pub mod limited_string;
+mod metrics;
+pub mod shutdown;
+pub mod storage;
+mod tls;
 pub mod ws;
 
-pub fn app() -> Router {
-    Router::new().merge(ws::paths()).fallback(fallback).layer(
-        ServiceBuilder::new()
-            .layer(
-                TraceLayer::new_for_http()
-                    .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
-                    .on_response(trace::DefaultOnResponse::new().level(Level::INFO))
-                    .on_failure(trace::DefaultOnFailure::new().level(Level::ERROR)),
-            )
-            .layer(HandleErrorLayer::new(|error: BoxError| async move {
-                if error.is::<tower::timeout::error::Elapsed>() {
-                    Ok(StatusCode::REQUEST_TIMEOUT)
-                } else {
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Unhandled internal error: {error}"),
-                    ))
-                }
-            }))
-            .timeout(Duration::from_secs(10))
-            .into_inner(),
+/// Serves the app until the shutdown signal,
+/// then waits for the connections to close, saves the rooms and flushes the storage
+///
+/// # Errors
+///
+/// This function errors if the storage can't be opened or flushed, or the server fails
+pub async fn serve(listener: TcpListener, config: ServerConfig) -> anyhow::Result<()> {
+    let storage = storage::open(&config.storage)?;
+    let shutdown = shutdown::signal();
+    let connections = TaskTracker::new();
+    let timeout = config.shutdown_timeout();
+    let tls = config.tls.clone();
+
+    let state = AppState::new(
+        storage.clone(),
+        config,
+        connections.clone(),
+        shutdown.clone(),
+    );
+    let components = state.components.clone();
+
+    let app = app(state).into_make_service_with_connect_info::<SocketAddr>();
+    // the tls server already spent part of the shutdown timeout on its own connections
+    let deadline = if let Some(tls) = &tls {
+        tls::serve(listener, app, tls, timeout, shutdown).await?
+    } else {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        Instant::now() + timeout
+    };
+
+    log::info!("Waiting for {} connections to close", connections.len());
+    connections.close();
+    if tokio::time::timeout_at(deadline, connections.wait())
+        .await
+        .is_err()
+    {
+        log::warn!(
+            "{} connections didn't close in {}s",
+            connections.len(),
+            timeout.as_secs()
+        );
+    }
+
+    ws::save_rooms(&components).await;
+    storage.flush()?;
+    log::warn!("Server stopped");
+
+    Ok(())
+}
+
+fn app(state: AppState) -> Router {
+    Router::new()
+        .merge(ws::paths(state.clone()))
+        .merge(auth::paths(state.clone()))
+        .merge(admin::paths(state.clone()))
+        .merge(metrics::paths(state))
+        .fallback(fallback)
+        .layer(
+            ServiceBuilder::new()
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(request_span)
+                        .on_response(trace::DefaultOnResponse::new().level(Level::INFO))
+                        .on_failure(trace::DefaultOnFailure::new().level(Level::ERROR)),
+                )
+                .layer(HandleErrorLayer::new(|error: BoxError| async move {
+                    if error.is::<tower::timeout::error::Elapsed>() {
+                        Ok(StatusCode::REQUEST_TIMEOUT)
+                    } else {
+                        Err((
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            format!("Unhandled internal error: {error}"),
+                        ))
+                    }
+                }))
+                .timeout(Duration::from_secs(10))
+                .into_inner(),
+        )
+}
+
+/// Like the default span, but without the query, it can hold tokens that shouldn't be logged
+fn request_span<B>(request: &Request<B>) -> Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        version = ?request.version(),
     )
 }
 
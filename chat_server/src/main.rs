@@ -4,17 +4,21 @@ use tokio::net::TcpListener;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let config = chat_server::config::init()?;
     logging::setup()?;
 
-    let r = chat_server::app();
-
-    let l = TcpListener::bind("0.0.0.0:8000").await?;
+    let l = TcpListener::bind(config.bind).await?;
 
     let addr = l.local_addr()?;
 
-    log::warn!("Listening on {addr}");
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    log::warn!("Listening on {scheme}://{addr}");
 
-    axum::serve(l, r).await?;
+    chat_server::serve(l, config).await?;
 
     Ok(())
 }
@@ -0,0 +1,252 @@
+use axum::{
+    Router,
+    extract::State,
+    http::header::CONTENT_TYPE,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use prometheus_client::{
+    encoding::{EncodeLabelSet, EncodeLabelValue, LabelValueEncoder, text::encode},
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+
+use crate::{AppState, admin::authorize, app_error::AppError};
+
+const CONTENT_TYPE_OPENMETRICS: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The label the messages of every ephemeral room are counted under together,
+/// so rooms that come and go don't add a label each, room names are never empty
+const EPHEMERAL_ROOM_LABEL: &str = "";
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RoomLabels {
+    pub room: String,
+}
+
+/// What the filter did to a message
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Parts of it were replaced with `*`
+    Censored,
+    /// The room's policy refused it
+    Rejected,
+    /// The user is restricted, for example for going over the rate limit
+    Blocked,
+}
+
+impl EncodeLabelValue for FilterAction {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> Result<(), std::fmt::Error> {
+        let value = match self {
+            FilterAction::Censored => "censored",
+            FilterAction::Rejected => "rejected",
+            FilterAction::Blocked => "blocked",
+        };
+        EncodeLabelValue::encode(&value, encoder)
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct FilterLabels {
+    pub action: FilterAction,
+}
+
+/// Why a connection couldn't join a room
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum UpgradeFailure {
+    /// The request was answered with an error, for example because the user is banned
+    Refused,
+    /// The websocket handshake failed
+    Handshake,
+}
+
+impl EncodeLabelValue for UpgradeFailure {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> Result<(), std::fmt::Error> {
+        let value = match self {
+            UpgradeFailure::Refused => "refused",
+            UpgradeFailure::Handshake => "handshake",
+        };
+        EncodeLabelValue::encode(&value, encoder)
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct UpgradeLabels {
+    pub reason: UpgradeFailure,
+}
+
+/// What a wrong password was guessed for
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum PasswordTarget {
+    /// The password of a protected room
+    Room,
+    /// The password of an account, the name might not exist
+    Account,
+}
+
+impl EncodeLabelValue for PasswordTarget {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> Result<(), std::fmt::Error> {
+        let value = match self {
+            PasswordTarget::Room => "room",
+            PasswordTarget::Account => "account",
+        };
+        EncodeLabelValue::encode(&value, encoder)
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PasswordLabels {
+    pub target: PasswordTarget,
+}
+
+/// What the server is doing, served in the Prometheus text format
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    /// The open websocket connections, suspended ones aren't counted
+    pub connections: Gauge,
+    pub rooms: Gauge,
+    /// Only persistent rooms have a label of their own
+    pub messages: Family<RoomLabels, Counter>,
+    pub filtered: Family<FilterLabels, Counter>,
+    /// The messages over the rate limit, the other strikes aren't counted here
+    pub strikes: Counter,
+    /// The strikes that ended in a timeout instead of closing the connection
+    pub timeouts: Counter,
+    /// How many times a connection fell behind its room's broadcast
+    pub lagged: Counter,
+    /// The broadcast messages skipped by the connections that fell behind
+    pub lagged_messages: Counter,
+    pub upgrade_failures: Family<UpgradeLabels, Counter>,
+    /// The wrong room and account passwords, they're counted here instead of in `strikes`
+    pub wrong_passwords: Family<PasswordLabels, Counter>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let connections = Gauge::default();
+        let rooms = Gauge::default();
+        let messages = Family::default();
+        let filtered = Family::default();
+        let strikes = Counter::default();
+        let timeouts = Counter::default();
+        let lagged = Counter::default();
+        let lagged_messages = Counter::default();
+        let upgrade_failures = Family::default();
+        let wrong_passwords = Family::default();
+
+        let mut registry = Registry::with_prefix("rs_chat");
+        registry.register(
+            "connections",
+            "Open websocket connections",
+            connections.clone(),
+        );
+        registry.register("rooms", "Active rooms", rooms.clone());
+        registry.register(
+            "messages",
+            "Messages sent in each persistent room and in all ephemeral ones",
+            messages.clone(),
+        );
+        registry.register(
+            "filtered_messages",
+            "Messages censored, rejected or blocked by the filter",
+            filtered.clone(),
+        );
+        registry.register(
+            "strikes",
+            "Messages sent over the rate limit",
+            strikes.clone(),
+        );
+        registry.register(
+            "timeouts",
+            "Timeouts added for going over the rate limit",
+            timeouts.clone(),
+        );
+        registry.register(
+            "lagged",
+            "Times a connection fell behind its room's broadcast",
+            lagged.clone(),
+        );
+        registry.register(
+            "lagged_messages",
+            "Broadcast messages skipped by connections that fell behind",
+            lagged_messages.clone(),
+        );
+        registry.register(
+            "upgrade_failures",
+            "Connections that couldn't join a room",
+            upgrade_failures.clone(),
+        );
+        registry.register(
+            "wrong_passwords",
+            "Wrong room and account passwords",
+            wrong_passwords.clone(),
+        );
+
+        Self {
+            registry,
+            connections,
+            rooms,
+            messages,
+            filtered,
+            strikes,
+            timeouts,
+            lagged,
+            lagged_messages,
+            upgrade_failures,
+            wrong_passwords,
+        }
+    }
+}
+
+impl Metrics {
+    /// Counts the message in its room, or with the other ephemeral rooms if `room` isn't set
+    pub fn message_sent(&self, room: Option<&str>) {
+        self.messages
+            .get_or_create(&RoomLabels {
+                room: room.unwrap_or(EPHEMERAL_ROOM_LABEL).to_string(),
+            })
+            .inc();
+    }
+
+    pub fn message_filtered(&self, action: FilterAction) {
+        self.filtered.get_or_create(&FilterLabels { action }).inc();
+    }
+
+    pub fn wrong_password(&self, target: PasswordTarget) {
+        self.wrong_passwords
+            .get_or_create(&PasswordLabels { target })
+            .inc();
+    }
+
+    pub fn upgrade_failed(&self, reason: UpgradeFailure) {
+        self.upgrade_failures
+            .get_or_create(&UpgradeLabels { reason })
+            .inc();
+    }
+}
+
+/// The metrics need the admin token, the room names show up in them
+pub fn paths(state: AppState) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+/// GET /metrics
+pub async fn metrics(State(state): State<AppState>) -> Result<Response, AppError> {
+    if !state.config.metrics.enabled {
+        return Err(AppError::not_found("The metrics are disabled"));
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    let rooms = state.components.lock().await.len() as i64;
+    state.metrics.rooms.set(rooms);
+
+    let mut body = String::new();
+    encode(&mut body, &state.metrics.registry).map_err(AppError::server_error)?;
+
+    Ok(([(CONTENT_TYPE, CONTENT_TYPE_OPENMETRICS)], body).into_response())
+}
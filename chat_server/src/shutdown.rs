@@ -0,0 +1,47 @@
+use futures::{
+    FutureExt,
+    future::{self, BoxFuture, Shared},
+};
+use tokio::signal;
+
+/// Resolves once the server should shut down, every clone resolves at the same time
+pub type ShutdownSignal = Shared<BoxFuture<'static, ()>>;
+
+/// The reason sent in the close frame of every connection when the server stops
+pub const SHUTDOWN_REASON: &str = "server shutting down";
+
+/// Resolves on SIGINT or SIGTERM
+pub fn signal() -> ShutdownSignal {
+    async {
+        let ctrl_c = async {
+            if let Err(err) = signal::ctrl_c().await {
+                log::error!("Couldn't listen for ctrl-c: {err}");
+                future::pending::<()>().await;
+            }
+        };
+
+        #[cfg(unix)]
+        let terminate = async {
+            match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+                Ok(mut sig) => {
+                    sig.recv().await;
+                }
+                Err(err) => {
+                    log::error!("Couldn't listen for SIGTERM: {err}");
+                    future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = future::pending::<()>();
+
+        tokio::select! {
+            () = ctrl_c => {}
+            () = terminate => {}
+        }
+
+        log::warn!("Shutdown signal received");
+    }
+    .boxed()
+    .shared()
+}
@@ -0,0 +1,69 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::anyhow;
+
+use crate::storage::{AccountRecord, RoomRecord, Storage, account_key};
+
+/// Keeps the rooms and accounts only as long as the server runs
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    rooms: Mutex<HashMap<String, RoomRecord>>,
+    accounts: Mutex<HashMap<String, AccountRecord>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> anyhow::Result<MutexGuard<'_, T>> {
+    mutex
+        .lock()
+        .map_err(|_| anyhow!("Memory storage lock poisoned"))
+}
+
+impl MemoryStorage {
+    fn rooms(&self) -> anyhow::Result<MutexGuard<'_, HashMap<String, RoomRecord>>> {
+        lock(&self.rooms)
+    }
+
+    fn accounts(&self) -> anyhow::Result<MutexGuard<'_, HashMap<String, AccountRecord>>> {
+        lock(&self.accounts)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load_room(&self, name: &str) -> anyhow::Result<Option<RoomRecord>> {
+        Ok(self.rooms()?.get(name).cloned())
+    }
+
+    fn save_room(&self, name: &str, room: &RoomRecord) -> anyhow::Result<()> {
+        self.rooms()?.insert(name.to_string(), room.clone());
+        Ok(())
+    }
+
+    fn remove_room(&self, name: &str) -> anyhow::Result<()> {
+        self.rooms()?.remove(name);
+        Ok(())
+    }
+
+    fn room_names(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.rooms()?.keys().cloned().collect())
+    }
+
+    fn load_account(&self, name: &str) -> anyhow::Result<Option<AccountRecord>> {
+        Ok(self.accounts()?.get(&account_key(name)).cloned())
+    }
+
+    fn create_account(&self, account: &AccountRecord) -> anyhow::Result<bool> {
+        match self.accounts()?.entry(account_key(&account.name)) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(account.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
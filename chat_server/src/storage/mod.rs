@@ -0,0 +1,230 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use chat_lib::{
+    RoomInfo,
+    filter::FilterPolicy,
+    prelude::*,
+    types::{MessageId, Reaction},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::StorageConfig,
+    ws::{Moderation, RoomAccess},
+};
+
+mod memory;
+mod sled_storage;
+
+pub use memory::MemoryStorage;
+pub use sled_storage::SledStorage;
+
+pub type SyncStorage = Arc<dyn Storage>;
+
+/// Everything about a room that outlives its connections
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomRecord {
+    /// The recent events, the oldest one is first
+    pub history: Vec<ServerMessage>,
+    /// Everyone the history refers to
+    pub users: Vec<User>,
+    pub reactions: HashMap<MessageId, Vec<Reaction>>,
+    pub moderation: Moderation,
+    /// The filter policy set by the owner, if they set one
+    pub filter: Option<FilterPolicy>,
+    /// Missing for rooms saved before the server kept track of it
+    pub info: Option<RoomInfo>,
+    pub access: RoomAccess,
+    /// Whether a registered user created the room, so it's kept when everyone leaves
+    pub persistent: bool,
+}
+
+/// A registered user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountRecord {
+    pub id: Uuid,
+    pub name: String,
+    /// The argon2 hash of the password as a PHC string
+    pub password_hash: String,
+}
+
+/// Accounts are looked up by their name, ignoring case
+fn account_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Where the rooms and accounts are kept between restarts
+///
+/// The calls are blocking, rooms are saved through `spawn_blocking` after their lock is released
+pub trait Storage: Debug + Send + std::marker::Sync {
+    /// # Errors
+    ///
+    /// This function errors if the backend fails or the stored room can't be read
+    fn load_room(&self, name: &str) -> anyhow::Result<Option<RoomRecord>>;
+
+    /// # Errors
+    ///
+    /// This function errors if the backend fails
+    fn save_room(&self, name: &str, room: &RoomRecord) -> anyhow::Result<()>;
+
+    /// # Errors
+    ///
+    /// This function errors if the backend fails
+    fn remove_room(&self, name: &str) -> anyhow::Result<()>;
+
+    /// # Errors
+    ///
+    /// This function errors if the backend fails
+    fn room_names(&self) -> anyhow::Result<Vec<String>>;
+
+    /// Finds the account with the name, ignoring case
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the backend fails or the stored account can't be read
+    fn load_account(&self, name: &str) -> anyhow::Result<Option<AccountRecord>>;
+
+    /// Saves the account, unless there's already one with the same name,
+    /// returns whether the account was created
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the backend fails
+    fn create_account(&self, account: &AccountRecord) -> anyhow::Result<bool>;
+
+    /// Makes sure everything saved so far is written out
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the backend fails
+    fn flush(&self) -> anyhow::Result<()>;
+}
+
+/// Opens the backend chosen by the config
+///
+/// # Errors
+///
+/// This function errors if the backend can't be opened
+pub fn open(config: &StorageConfig) -> anyhow::Result<SyncStorage> {
+    let storage: SyncStorage = match config {
+        StorageConfig::Memory => Arc::new(MemoryStorage::default()),
+        StorageConfig::Sled { path } => Arc::new(SledStorage::open(path)?),
+    };
+
+    Ok(storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ServerConfig, ws::room::Room};
+
+    fn backends() -> Vec<SyncStorage> {
+        vec![
+            Arc::new(MemoryStorage::default()),
+            Arc::new(SledStorage::temporary().expect("A temporary database should open")),
+        ]
+    }
+
+    fn record(messages: usize) -> RoomRecord {
+        let author = User::new(Uuid::new_v4(), "author".to_string());
+        let mut history = vec![ServerMessage::UserJoined(author.clone())];
+        history.extend((0..messages).map(|i| {
+            ServerMessage::NewMessage(Message::stamped(*author.get_id(), format!("message {i}")))
+        }));
+
+        RoomRecord {
+            history,
+            users: vec![author],
+            info: Some(RoomInfo::new("room".to_string())),
+            persistent: true,
+            ..Default::default()
+        }
+    }
+
+    fn contents(history: &ServerMessage) -> Vec<String> {
+        let ServerMessage::History { events, .. } = history else {
+            panic!("The history should be a History message");
+        };
+        events
+            .iter()
+            .filter_map(|msg| match msg {
+                ServerMessage::NewMessage(message) => Some(message.get_content().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn room_records_round_trip() {
+        for storage in backends() {
+            let record = record(3);
+            storage
+                .save_room("room", &record)
+                .expect("The room should save");
+
+            let loaded = storage
+                .load_room("room")
+                .expect("The room should load")
+                .expect("The room should be saved");
+            assert_eq!(
+                serde_json::to_value(&loaded).expect("Serialize shouldn't fail"),
+                serde_json::to_value(&record).expect("Serialize shouldn't fail"),
+                "{storage:?}"
+            );
+            assert_eq!(
+                storage.room_names().expect("The rooms should list"),
+                ["room"]
+            );
+
+            storage
+                .remove_room("room")
+                .expect("The room should be removed");
+            assert!(
+                storage
+                    .load_room("room")
+                    .expect("The room should load")
+                    .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn rooms_restore_their_history() {
+        for storage in backends() {
+            storage
+                .save_room("room", &record(3))
+                .expect("The room should save");
+
+            let room = Room::load(
+                "room".to_string(),
+                storage.clone(),
+                &ServerConfig::default(),
+            );
+            assert!(room.is_persistent());
+            assert_eq!(
+                contents(&room.history()),
+                ["message 0", "message 1", "message 2"]
+            );
+        }
+    }
+
+    #[test]
+    fn restored_history_is_cut_to_the_history_size() {
+        for storage in backends() {
+            storage
+                .save_room("room", &record(5))
+                .expect("The room should save");
+
+            let mut config = ServerConfig::default();
+            config.limits.history_size = 3;
+            let room = Room::load("room".to_string(), storage.clone(), &config);
+            assert_eq!(
+                contents(&room.history()),
+                ["message 2", "message 3", "message 4"]
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::storage::{AccountRecord, RoomRecord, Storage, account_key};
+
+const ROOMS_TREE: &str = "rooms";
+const ACCOUNTS_TREE: &str = "accounts";
+
+/// Keeps everything in an embedded sled database, the values are stored as json
+#[derive(Debug)]
+pub struct SledStorage {
+    db: sled::Db,
+    rooms: sled::Tree,
+    accounts: sled::Tree,
+}
+
+impl SledStorage {
+    /// # Errors
+    ///
+    /// This function errors if the database can't be opened,
+    /// for example if another server is using it
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let db = sled::open(path)
+            .with_context(|| format!("Couldn't open the database at {}", path.display()))?;
+        let rooms = db.open_tree(ROOMS_TREE)?;
+        let accounts = db.open_tree(ACCOUNTS_TREE)?;
+
+        Ok(Self {
+            db,
+            rooms,
+            accounts,
+        })
+    }
+}
+
+#[cfg(test)]
+impl SledStorage {
+    /// A database that's deleted when it's dropped
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the database can't be created
+    pub fn temporary() -> anyhow::Result<Self> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let rooms = db.open_tree(ROOMS_TREE)?;
+        let accounts = db.open_tree(ACCOUNTS_TREE)?;
+
+        Ok(Self {
+            db,
+            rooms,
+            accounts,
+        })
+    }
+}
+
+impl Storage for SledStorage {
+    fn load_room(&self, name: &str) -> anyhow::Result<Option<RoomRecord>> {
+        let Some(bytes) = self.rooms.get(name)? else {
+            return Ok(None);
+        };
+        let room = serde_json::from_slice(&bytes)
+            .with_context(|| format!("The stored room {name} is corrupted"))?;
+
+        Ok(Some(room))
+    }
+
+    fn save_room(&self, name: &str, room: &RoomRecord) -> anyhow::Result<()> {
+        self.rooms.insert(name, serde_json::to_vec(room)?)?;
+        Ok(())
+    }
+
+    fn remove_room(&self, name: &str) -> anyhow::Result<()> {
+        self.rooms.remove(name)?;
+        Ok(())
+    }
+
+    fn room_names(&self) -> anyhow::Result<Vec<String>> {
+        self.rooms
+            .iter()
+            .keys()
+            .map(|key| Ok(String::from_utf8(key?.to_vec())?))
+            .collect()
+    }
+
+    fn load_account(&self, name: &str) -> anyhow::Result<Option<AccountRecord>> {
+        let Some(bytes) = self.accounts.get(account_key(name))? else {
+            return Ok(None);
+        };
+        let account = serde_json::from_slice(&bytes)
+            .with_context(|| format!("The stored account {name} is corrupted"))?;
+
+        Ok(Some(account))
+    }
+
+    fn create_account(&self, account: &AccountRecord) -> anyhow::Result<bool> {
+        let created = self
+            .accounts
+            .compare_and_swap(
+                account_key(&account.name),
+                None::<&[u8]>,
+                Some(serde_json::to_vec(account)?),
+            )?
+            .is_ok();
+
+        Ok(created)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
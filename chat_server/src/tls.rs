@@ -0,0 +1,159 @@
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use axum::{Router, extract::connect_info::IntoMakeServiceWithConnectInfo};
+use axum_server::{
+    Handle, Server,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use futures::future;
+use tokio::{
+    net::TcpListener,
+    time::{self, Instant},
+};
+
+use crate::{config::TlsConfig, shutdown::ShutdownSignal};
+
+/// Serves the app over tls until the shutdown signal,
+/// the certificate is reloaded when it changes without dropping any connection
+///
+/// Returns the end of the shutdown timeout, which starts with the shutdown signal,
+/// and the connections left have until then to close
+///
+/// # Errors
+///
+/// This function errors if the certificate can't be loaded or the server fails
+pub(crate) async fn serve(
+    listener: TcpListener,
+    app: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    tls: &TlsConfig,
+    shutdown_timeout: Duration,
+    shutdown: ShutdownSignal,
+) -> anyhow::Result<Instant> {
+    let rustls = load(tls).await?;
+    let reload = tokio::spawn(watch(rustls.clone(), tls.clone(), shutdown.clone()));
+
+    let handle = Handle::<SocketAddr>::new();
+    let deadline = tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            let deadline = Instant::now() + shutdown_timeout;
+            handle.graceful_shutdown(Some(shutdown_timeout));
+            deadline
+        }
+    });
+
+    let served = Server::from_listener(listener)
+        .acceptor(RustlsAcceptor::new(rustls))
+        .handle(handle)
+        .serve(app)
+        .await;
+    reload.abort();
+    served?;
+
+    Ok(deadline.await?)
+}
+
+async fn load(tls: &TlsConfig) -> anyhow::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+        .await
+        .with_context(|| {
+            format!(
+                "Couldn't load the tls certificate {} with key {}",
+                tls.cert.display(),
+                tls.key.display()
+            )
+        })
+}
+
+/// The last time the certificate or its key changed
+async fn modified(tls: &TlsConfig) -> io::Result<SystemTime> {
+    let cert = tokio::fs::metadata(&tls.cert).await?.modified()?;
+    let key = tokio::fs::metadata(&tls.key).await?.modified()?;
+
+    Ok(cert.max(key))
+}
+
+/// Reloads the certificate when its files change or on SIGHUP,
+/// the old one is kept if the new one can't be loaded
+async fn watch(rustls: RustlsConfig, tls: TlsConfig, shutdown: ShutdownSignal) {
+    let mut last_modified = modified(&tls).await.ok();
+    let mut hangup = hangup();
+    let mut interval = (!tls.reload_interval().is_zero()).then(|| {
+        let mut interval = time::interval(tls.reload_interval());
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        interval
+    });
+
+    loop {
+        let forced = tokio::select! {
+            () = shutdown.clone() => return,
+            () = next_hangup(&mut hangup) => true,
+            () = next_tick(&mut interval) => false,
+        };
+
+        let current = modified(&tls)
+            .await
+            .inspect_err(|err| log::warn!("Couldn't check the tls certificate: {err}"))
+            .ok();
+        if !forced && (current.is_none() || current == last_modified) {
+            continue;
+        }
+
+        // a broken certificate is only tried again once it changes
+        last_modified = current;
+        match rustls.reload_from_pem_file(&tls.cert, &tls.key).await {
+            Ok(()) => log::warn!("Reloaded the tls certificate {}", tls.cert.display()),
+            Err(err) => {
+                log::error!("Couldn't reload the tls certificate, keeping the old one: {err}");
+            }
+        }
+    }
+}
+
+async fn next_tick(interval: &mut Option<time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => future::pending().await,
+    }
+}
+
+#[cfg(unix)]
+type Hangup = Option<tokio::signal::unix::Signal>;
+#[cfg(not(unix))]
+type Hangup = ();
+
+#[cfg(unix)]
+fn hangup() -> Hangup {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    signal(SignalKind::hangup())
+        .inspect_err(|err| log::error!("Couldn't listen for SIGHUP: {err}"))
+        .ok()
+}
+
+#[cfg(not(unix))]
+const fn hangup() -> Hangup {}
+
+#[cfg(unix)]
+async fn next_hangup(hangup: &mut Hangup) {
+    if let Some(sig) = hangup
+        && sig.recv().await.is_some()
+    {
+        return;
+    }
+    *hangup = None;
+    future::pending::<()>().await;
+}
+
+#[cfg(not(unix))]
+async fn next_hangup(_: &mut Hangup) {
+    future::pending::<()>().await;
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chat_lib::room::Access;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::consts::MAX_INVITE_SECS;
+
+/// What joining the room needs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Lock {
+    #[default]
+    Open,
+    /// The argon2 hash of the room's password as a PHC string
+    Password(String),
+    InviteOnly,
+}
+
+/// Who can join a room and the invites that let them, kept across restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomAccess {
+    lock: Lock,
+    /// Until when each invite code is valid
+    invites: HashMap<String, DateTime<Utc>>,
+}
+
+impl RoomAccess {
+    /// The access as the clients see it
+    #[must_use]
+    pub const fn access(&self) -> Access {
+        match self.lock {
+            Lock::Open => Access::Open,
+            Lock::Password(_) => Access::Password,
+            Lock::InviteOnly => Access::InviteOnly,
+        }
+    }
+
+    /// The hash the password has to match, if joining can be done with a password
+    #[must_use]
+    pub fn password_hash(&self) -> Option<&str> {
+        match &self.lock {
+            Lock::Password(hash) => Some(hash),
+            Lock::Open | Lock::InviteOnly => None,
+        }
+    }
+
+    /// Whether the invite code exists and hasn't expired yet
+    #[must_use]
+    pub fn is_invited(&self, code: &str) -> bool {
+        self.invites
+            .get(code)
+            .is_some_and(|until| *until > Utc::now())
+    }
+
+    /// Changes what joining needs, opening the room revokes all of its invites
+    pub fn set_lock(&mut self, lock: Lock) {
+        if matches!(lock, Lock::Open) {
+            self.invites.clear();
+        }
+        self.lock = lock;
+    }
+
+    /// Creates an invite code valid for `secs` seconds, returns it with when it expires
+    pub fn invite(&mut self, secs: u64) -> (String, DateTime<Utc>) {
+        self.prune();
+
+        let mut code = [0; 12];
+        OsRng.fill_bytes(&mut code);
+        let code = URL_SAFE_NO_PAD.encode(code);
+        #[allow(clippy::cast_possible_wrap)]
+        let until = Utc::now() + TimeDelta::seconds(secs.min(MAX_INVITE_SECS) as i64);
+        self.invites.insert(code.clone(), until);
+
+        (code, until)
+    }
+
+    /// Forgets the invites that already expired
+    pub fn prune(&mut self) {
+        let now = Utc::now();
+        self.invites.retain(|_, until| *until > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invites_last_at_most_the_longest_allowed() {
+        let mut access = RoomAccess::default();
+        let (code, until) = access.invite(u64::MAX);
+
+        assert!(access.is_invited(&code));
+        #[allow(clippy::cast_possible_wrap)]
+        let longest = Utc::now() + TimeDelta::seconds(MAX_INVITE_SECS as i64);
+        assert!(until <= longest);
+    }
+
+    #[test]
+    fn expired_invites_are_refused_and_pruned() {
+        let mut access = RoomAccess::default();
+        let (code, _) = access.invite(0);
+
+        assert!(!access.is_invited(&code));
+        access.prune();
+        assert!(access.invites.is_empty());
+    }
+}
@@ -1,12 +1,14 @@
-use std::{collections::VecDeque, time::Instant};
+use std::{sync::Arc, time::Instant};
 
 use chat_lib::{
+    Protocol,
+    filter::FilterPolicy,
     prelude::*,
-    types::{Message as ChatMessage, Sync},
-    ws_connection::{Message, WsConnection},
+    types::{Message as ChatMessage, MessageId, Presence, RequestId, Sanction, Sync},
+    ws_connection::{CloseCode, CloseFrame, Message, WsConnection},
 };
 use futures::{SinkExt, StreamExt};
-use rustrict::{CensorStr, Context};
+use rustrict::ContextProcessingOptions;
 use tokio::{
     sync::broadcast::{self, error::RecvError},
     time::sleep_until,
@@ -14,59 +16,121 @@ use tokio::{
 use uuid::Uuid;
 
 use crate::{
-    config::CONTEXT_OPTS,
-    consts::{HEARTBEAT_FREQUENCY, MAX_STRIKES, MESSAGE_LIMIT, TIMEOUT_DURATION, TIMEOUT_WINDOW},
-    ws::{MsgBroadcastReceiver, MsgBroadcastSender, Room},
+    AppState,
+    auth::{is_name_reserved, password},
+    clients::{SharedPenalties, Strike},
+    config::LimitsConfig,
+    consts::TYPING_INTERVAL,
+    filter::{Rejection, filter_message, is_inappropriate},
+    metrics::{FilterAction, Metrics},
+    shutdown::SHUTDOWN_REASON,
+    storage::SyncStorage,
+    ws::{
+        Direct, DirectReceiver, MsgBroadcastReceiver, MsgBroadcastSender, Room, access::Lock,
+        moderation::sanction_end, room::Suspended,
+    },
 };
 
 pub type WsResult<T = ()> = Result<T, anyhow::Error>;
 
+/// Why the sanctioned user's connection gets closed, if it does
+fn removal_reason(sanction: &Sanction) -> Option<String> {
+    match sanction {
+        Sanction::Kick => Some("Kicked from the room".to_string()),
+        Sanction::Ban(until) => Some(format!("Banned from the room until {until}")),
+        Sanction::Mute(_) | Sanction::Unmute | Sanction::Unban => None,
+    }
+}
+
+/// Whether the user has an account under the name, a guest's id never matches one
+async fn is_account(storage: &SyncStorage, id: &Uuid, name: Option<String>) -> bool {
+    let Some(name) = name else {
+        return false;
+    };
+    let storage = storage.clone();
+    match tokio::task::spawn_blocking(move || storage.load_account(&name)).await {
+        Ok(Ok(account)) => account.is_some_and(|account| account.id == *id),
+        Ok(Err(err)) => {
+            log::error!("Couldn't load the account of user {id}: {err}");
+            false
+        }
+        Err(err) => {
+            log::error!("Loading the account of user {id} panicked: {err}");
+            false
+        }
+    }
+}
+
 pub struct WsHandler<'a, F>
 where
     F: Future<Output = ()> + Clone,
 {
     stream: WsConnection,
     room: Sync<Room>,
-    message_counter: VecDeque<Instant>,
-    ctx: Context,
+    limits: LimitsConfig,
+    /// Shared by every connection of the ip
+    penalties: SharedPenalties,
+    ctx_opts: ContextProcessingOptions,
+    storage: SyncStorage,
+    metrics: Arc<Metrics>,
     id: Uuid,
+    protocol: Protocol,
     rx: MsgBroadcastReceiver,
     tx: MsgBroadcastSender,
+    /// Messages meant only for this connection
+    direct_rx: DirectReceiver,
     last_heartbeat: Instant,
-    end_of_timeout: Option<Instant>,
-    strikes: usize,
     sd: &'a mut F,
     stream_open: bool,
     in_room: bool,
+    /// If the last typing signal was that the user is typing
+    typing: bool,
+    /// When the room last heard that the user is typing
+    last_typing: Option<Instant>,
+    /// The id of the request being handled, everything sent meanwhile is a reply to it
+    current_request: Option<RequestId>,
+    /// The room's history from before joining, sent right after this connection's `UserJoined`
+    pending_history: Option<ServerMessage>,
 }
 
 impl<'a, F> WsHandler<'a, F>
 where
     F: Future<Output = ()> + Clone,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         stream: WsConnection,
-        ctx: Context,
+        state: &AppState,
+        penalties: SharedPenalties,
         id: Uuid,
+        protocol: Protocol,
         rx: MsgBroadcastReceiver,
         tx: MsgBroadcastSender,
+        direct_rx: DirectReceiver,
         room: Sync<Room>,
         sd: &'a mut F,
     ) -> Self {
         Self {
             stream,
-            ctx,
+            limits: state.config.limits,
+            penalties,
+            ctx_opts: state.config.filter.context_options(),
+            storage: state.storage.clone(),
+            metrics: state.metrics.clone(),
             room,
             id,
+            protocol,
             rx,
             tx,
+            direct_rx,
             sd,
             last_heartbeat: Instant::now(),
-            message_counter: VecDeque::new(),
             stream_open: true,
             in_room: true,
-            end_of_timeout: None,
-            strikes: 0,
+            typing: false,
+            last_typing: None,
+            current_request: None,
+            pending_history: None,
         }
     }
 
@@ -74,19 +138,26 @@ where
         if !self.in_room {
             return Ok(true);
         }
-        let next_heartbeat = self.last_heartbeat + HEARTBEAT_FREQUENCY;
+        let next_heartbeat = self.last_heartbeat + self.limits.heartbeat_frequency();
 
         tokio::select! {
             Some(res) = self.stream.next() => {
                 return self.handle_stream(res).await
             }
             res = self.rx.recv() => return self.handle_rx(res).await,
+            Some(direct) = self.direct_rx.recv() => return self.handle_direct(direct).await,
             () = sleep_until(next_heartbeat.into()) => {
                 self.send_heartbeat().await?;
                 return Ok(false);
             }
             () = self.sd.clone() => {
-                self.close_logged().await;
+                let frame = CloseFrame {
+                    code: CloseCode::Away,
+                    reason: SHUTDOWN_REASON.into(),
+                };
+                if let Err(err) = self.close_with(Some(frame)).await {
+                    log::error!("There was an error while trying to close socket: {err}");
+                }
             }
             else => {
                 self.close_logged().await;
@@ -98,20 +169,21 @@ where
 
     async fn handle_stream(&mut self, res: Result<Message, anyhow::Error>) -> WsResult<bool> {
         match res {
-            Err(err) => {
-                if self.stream_open {
-                    self.close_socket().await?;
-                }
-                Err(err)
-            }
+            // the connection dropped, whoever runs the handler decides if it leaves the room
+            Err(err) => Err(err),
             Ok(msg) => {
-                self.count_message();
-                if !self.can_send_message() {
+                let can_send = {
+                    let mut penalties = self.penalties.lock();
+                    penalties.count_message();
+                    penalties.can_send_message(&self.limits)
+                };
+                if !can_send {
                     self.timeout().await?;
                     return Ok(true);
                 }
                 match msg {
                     Message::Text(txt) => self.handle_text(&txt).await,
+                    Message::Binary(_) => self.handle_binary(&msg).await,
                     Message::Close(_) => {
                         if self.stream_open {
                             self.exit_room().await;
@@ -130,48 +202,286 @@ where
     }
 
     async fn handle_text(&mut self, txt: &str) -> WsResult<bool> {
-        if let Ok(msg) = serde_json::from_str::<ClientMessage>(txt) {
-            log::debug!("Processing message: {msg:?}");
+        if let Ok(request) = serde_json::from_str::<ClientRequest>(txt) {
+            self.handle_request(request).await?;
+        } else {
+            self.send_msg(txt).await?;
+        }
 
-            match msg {
-                ClientMessage::SendMessage(msg) => {
-                    self.send_msg(&msg).await?;
-                }
-                ClientMessage::ChangeUserName(name) => {
-                    self.change_name(name).await?;
-                }
-                ClientMessage::GetUserData(uuid) => {
-                    if let Some(user) = self.room.lock().await.get_user(&uuid) {
-                        self.stream
-                            .send(ServerMessage::UserData(user.clone()).as_wsmsg())
-                            .await?;
-                    } else {
-                        self.stream
-                            .send(ServerMessage::InvalidUser(uuid).as_wsmsg())
-                            .await?;
-                    }
-                }
-                ClientMessage::GetAllUserData => {
-                    let room = self.room.lock().await;
-                    let mut users = room.get_all_users();
-                    users.retain(|u| *u.get_id() != self.id);
-                    self.stream
-                        .send(ServerMessage::AllUsers(users).as_wsmsg())
-                        .await?;
-                }
-                ClientMessage::GetSelf => {
-                    let room = self.room.lock().await;
-                    let user = room.get_user(&self.id).expect("Should have self");
-                    self.stream
-                        .send(ServerMessage::SelfData(user.clone()).as_wsmsg())
-                        .await?;
+        Ok(false)
+    }
+
+    async fn handle_binary(&mut self, msg: &Message) -> WsResult<bool> {
+        match ClientRequest::decode(msg) {
+            Ok(request) => self.handle_request(request).await?,
+            Err(err) => {
+                self.send(ServerMessage::UnsupportedMessage(err.to_string()))
+                    .await?;
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn handle_request(&mut self, request: ClientRequest) -> WsResult {
+        self.current_request = request.request_id;
+        let res = self.handle_message(request.message).await;
+        self.current_request = None;
+
+        res
+    }
+
+    async fn handle_message(&mut self, msg: ClientMessage) -> WsResult {
+        log::debug!("Processing message: {msg:?}");
+
+        match msg {
+            ClientMessage::SendMessage(msg) => {
+                self.send_msg(&msg).await?;
+            }
+            ClientMessage::ChangeUserName(name) => {
+                self.change_name(name).await?;
+            }
+            ClientMessage::GetUserData(uuid) => {
+                let user = self.room.lock().await.get_user(&uuid).cloned();
+                if let Some(user) = user {
+                    self.send(ServerMessage::UserData(user)).await?;
+                } else {
+                    self.send(ServerMessage::InvalidUser(uuid)).await?;
                 }
             }
+            ClientMessage::GetAllUserData => {
+                let mut users = self.room.lock().await.get_all_users();
+                users.retain(|u| *u.get_id() != self.id);
+                self.send(ServerMessage::AllUsers(users)).await?;
+            }
+            ClientMessage::GetSelf => {
+                let user = self
+                    .room
+                    .lock()
+                    .await
+                    .get_user(&self.id)
+                    .cloned()
+                    .expect("Should have self");
+                self.send(ServerMessage::SelfData(user)).await?;
+            }
+            ClientMessage::EditMessage { id, content } => {
+                self.edit_msg(id, &content).await?;
+            }
+            ClientMessage::Typing(typing) => self.set_typing(typing).await,
+            ClientMessage::SendReply { reply_to, content } => {
+                self.send_reply(reply_to, &content).await?;
+            }
+            ClientMessage::AddReaction { id, reaction } => {
+                self.react(id, reaction, true).await?;
+            }
+            ClientMessage::RemoveReaction { id, reaction } => {
+                self.react(id, reaction, false).await?;
+            }
+            ClientMessage::SendDirectMessage { to, content } => {
+                self.send_direct_msg(to, &content).await?;
+            }
+            ClientMessage::DeleteMessage(id) => {
+                self.delete_msg(id).await?;
+            }
+            ClientMessage::Kick(user) => {
+                self.sanction(user, Sanction::Kick).await?;
+            }
+            ClientMessage::Mute { user, duration } => {
+                let sanction = sanction_end(duration).map_or(Sanction::Unmute, Sanction::Mute);
+                self.sanction(user, sanction).await?;
+            }
+            ClientMessage::Ban { user, duration } => {
+                let sanction = sanction_end(duration).map_or(Sanction::Unban, Sanction::Ban);
+                self.sanction(user, sanction).await?;
+            }
+            ClientMessage::SetModerator { user, moderator } => {
+                self.set_moderator(user, moderator).await?;
+            }
+            ClientMessage::SetFilterPolicy(policy) => {
+                self.set_filter(policy).await?;
+            }
+            ClientMessage::SetTopic(topic) => {
+                self.describe(topic, MAX_TOPIC_LENGTH, Room::set_topic)
+                    .await?;
+            }
+            ClientMessage::SetDescription(description) => {
+                self.describe(description, MAX_DESCRIPTION_LENGTH, Room::set_description)
+                    .await?;
+            }
+            ClientMessage::SetPassword(password) => {
+                self.set_password(password).await?;
+            }
+            ClientMessage::SetInviteOnly(invite_only) => {
+                self.set_invite_only(invite_only).await?;
+            }
+            ClientMessage::CreateInvite { duration } => {
+                self.invite(duration).await?;
+            }
+            ClientMessage::SetPresence(presence) => {
+                self.set_presence(presence).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sanctions the user, if this connection's user is allowed to
+    async fn sanction(&mut self, user: Uuid, sanction: Sanction) -> WsResult {
+        let mut room = self.room.lock().await;
+        if !room.moderation().can_sanction(&self.id, &user) {
+            drop(room);
+            return self.send(ServerMessage::ModerationDenied(user)).await;
+        }
+        // bans can be handed out and lifted after the user left
+        if matches!(sanction, Sanction::Kick | Sanction::Mute(_)) && !room.has_user(&user) {
+            drop(room);
+            return self.send(ServerMessage::InvalidUser(user)).await;
+        }
+        // a guest who left is banned by their address, without it only accounts can be banned
+        if matches!(sanction, Sanction::Ban(_)) && !room.has_user(&user) && !room.is_guest(&user) {
+            let name = room.known_user(&user).map(|u| u.get_name().to_string());
+            drop(room);
+            if !is_account(&self.storage, &user, name).await {
+                return self.send(ServerMessage::InvalidUser(user)).await;
+            }
+            room = self.room.lock().await;
+        }
+
+        log::info!("User {} sanctioned user {user}: {sanction:?}", self.id);
+        room.sanction(user, &sanction);
+        let removed = matches!(sanction, Sanction::Kick | Sanction::Ban(_));
+        let _ = self.tx.send(ServerMessage::Sanctioned {
+            user,
+            by: self.id,
+            sanction,
+        });
+        // a suspended connection isn't there to disconnect itself
+        if removed && let Some(user) = room.remove_suspended(&user) {
+            self.broadcast(&mut room, ServerMessage::UserLeft(user));
+        }
+
+        Ok(())
+    }
+
+    async fn set_moderator(&mut self, user: Uuid, moderator: bool) -> WsResult {
+        let mut room = self.room.lock().await;
+        if !room.moderation().is_owner(&self.id) {
+            drop(room);
+            return self.send(ServerMessage::ModerationDenied(user)).await;
+        }
+        if moderator && !room.has_user(&user) {
+            drop(room);
+            return self.send(ServerMessage::InvalidUser(user)).await;
+        }
+
+        if room.set_moderator(user, moderator) {
+            let _ = self.tx.send(room.moderation().moderators());
+        }
+
+        Ok(())
+    }
+
+    async fn set_filter(&mut self, policy: FilterPolicy) -> WsResult {
+        let mut room = self.room.lock().await;
+        if !room.moderation().is_owner(&self.id) {
+            drop(room);
+            return self.send(ServerMessage::ModerationDenied(self.id)).await;
+        }
+        if !room.set_filter(policy) {
+            drop(room);
+            return self.send(ServerMessage::FilterPinned).await;
+        }
+
+        log::info!("User {} changed the filter policy", self.id);
+        let _ = self
+            .tx
+            .send(ServerMessage::FilterPolicy((*room.filter()).clone()));
+
+        Ok(())
+    }
+
+    async fn set_password(&mut self, password: String) -> WsResult {
+        if !self.room.lock().await.moderation().is_owner(&self.id) {
+            return self.send(ServerMessage::ModerationDenied(self.id)).await;
+        }
+        if password.is_empty() {
+            return self.set_lock(Lock::Open).await;
+        }
+
+        let hash = tokio::task::spawn_blocking(move || password::hash(&password)).await??;
+        self.set_lock(Lock::Password(hash)).await
+    }
+
+    async fn set_invite_only(&mut self, invite_only: bool) -> WsResult {
+        let lock = if invite_only {
+            Lock::InviteOnly
         } else {
-            self.send_msg(txt).await?;
+            Lock::Open
+        };
+        self.set_lock(lock).await
+    }
+
+    async fn set_lock(&mut self, lock: Lock) -> WsResult {
+        let mut room = self.room.lock().await;
+        if !room.moderation().is_owner(&self.id) {
+            drop(room);
+            return self.send(ServerMessage::ModerationDenied(self.id)).await;
         }
 
-        Ok(false)
+        log::info!("User {} changed who can join room {}", self.id, room.name());
+        let _ = self.tx.send(room.set_lock(lock));
+
+        Ok(())
+    }
+
+    async fn invite(&mut self, duration: u64) -> WsResult {
+        let mut room = self.room.lock().await;
+        if !room.moderation().is_owner(&self.id) {
+            drop(room);
+            return self.send(ServerMessage::ModerationDenied(self.id)).await;
+        }
+
+        let msg = room.invite(duration);
+        drop(room);
+        self.send(msg).await
+    }
+
+    /// Changes the room's topic or description with `change`, if this connection's user is allowed to
+    async fn describe(
+        &mut self,
+        text: String,
+        max_length: usize,
+        change: fn(&mut Room, String) -> ServerMessage,
+    ) -> WsResult {
+        if self.check_muted().await? {
+            return Ok(());
+        }
+
+        let text = text.trim().to_string();
+        let mut room = self.room.lock().await;
+        if !room.moderation().can_describe(&self.id) {
+            drop(room);
+            return self.send(ServerMessage::ModerationDenied(self.id)).await;
+        }
+        if text.chars().count() > max_length || is_inappropriate(&room.filter(), &text) {
+            drop(room);
+            return self.send(ServerMessage::InvalidRoomInfo(text)).await;
+        }
+
+        log::info!("User {} changed the info of room {}", self.id, room.name());
+        let _ = self.tx.send(change(&mut room, text));
+
+        Ok(())
+    }
+
+    /// Tells the user until when they're muted, returns whether they are
+    async fn check_muted(&mut self) -> WsResult<bool> {
+        let muted_until = self.room.lock().await.moderation().muted_until(&self.id);
+        if let Some(until) = muted_until {
+            self.send(ServerMessage::Muted(until)).await?;
+        }
+
+        Ok(muted_until.is_some())
     }
 
     async fn change_name(&mut self, name: String) -> WsResult {
@@ -180,14 +490,12 @@ where
                 "User {} tried to change name above the allowed character limit",
                 self.id
             );
-            self.stream
-                .send(ServerMessage::NameTooLong(name).as_wsmsg())
-                .await?;
-        } else if name.is_inappropriate() {
-            self.stream
-                .send(ServerMessage::NameInappropriate.as_wsmsg())
-                .await?;
+            self.send(ServerMessage::NameTooLong(name)).await?;
+        } else if is_inappropriate(&self.room.lock().await.filter(), &name) {
+            self.send(ServerMessage::NameInappropriate).await?;
             //
+        } else if is_name_reserved(&self.storage, &name, Some(&self.id)).await {
+            self.send(ServerMessage::NameTaken(name)).await?;
         } else {
             self.set_name(name).await?;
         }
@@ -198,87 +506,135 @@ where
     async fn set_name(&mut self, name: String) -> WsResult {
         let mut room = self.room.lock().await;
 
-        match room.get_user_mut(&self.id) {
-            Some(user) => {
-                user.set_name(name);
-                let _ = self.tx.send(ServerMessage::UserNameChange(user.clone()));
-            }
-            None => {
-                self.stream
-                    .send(ServerMessage::InvalidUser(self.id).as_wsmsg())
-                    .await?;
-            }
+        if let Some(user) = room.get_user_mut(&self.id) {
+            user.set_name(name);
+            let msg = ServerMessage::UserNameChange(user.clone());
+            self.broadcast(&mut room, msg);
+        } else {
+            drop(room);
+            self.send(ServerMessage::InvalidUser(self.id)).await?;
         }
 
         Ok(())
     }
 
-    fn update_message_counter(&mut self) {
-        self.message_counter
-            .retain(|i| i.elapsed() < TIMEOUT_WINDOW);
-    }
-
-    fn can_send_message(&mut self) -> bool {
-        if let Some(t) = self.end_of_timeout {
-            if t < Instant::now() {
-                self.end_of_timeout = None;
-            } else {
-                return false;
-            }
+    async fn set_presence(&mut self, presence: Presence) -> WsResult {
+        let presence = match presence {
+            Presence::Custom(status) => Presence::Custom(status.trim().to_string()),
+            presence => presence,
+        };
+        let mut room = self.room.lock().await;
+        if let Presence::Custom(status) = &presence
+            && (status.is_empty()
+                || status.chars().count() > MAX_STATUS_LENGTH
+                || is_inappropriate(&room.filter(), status))
+        {
+            drop(room);
+            return self
+                .send(ServerMessage::InvalidPresence(status.clone()))
+                .await;
         }
 
-        self.update_message_counter();
-
-        self.message_counter.len() < MESSAGE_LIMIT
-    }
+        let Some(user) = room.get_user_mut(&self.id) else {
+            drop(room);
+            return self.send(ServerMessage::InvalidUser(self.id)).await;
+        };
+        if *user.get_presence() != presence {
+            user.set_presence(presence.clone());
+            // like typing, it's not worth replaying to later connections
+            let _ = self.tx.send(ServerMessage::PresenceChanged {
+                user: self.id,
+                presence,
+            });
+        }
 
-    fn count_message(&mut self) {
-        self.message_counter.push_back(Instant::now());
+        Ok(())
     }
 
     async fn timeout(&mut self) -> WsResult {
-        self.strikes += 1;
-
-        if self.strikes > MAX_STRIKES {
+        self.metrics.strikes.inc();
+        let strike = self.penalties.lock().strike(&self.limits);
+        if strike == Strike::StruckOut {
             return self.close_socket().await;
         }
-
-        if let Some(t) = self.end_of_timeout {
-            self.end_of_timeout = Some(t + TIMEOUT_DURATION);
-        } else {
-            self.end_of_timeout = Some(Instant::now() + TIMEOUT_DURATION);
-        }
+        self.metrics.timeouts.inc();
 
         self.send_timeout_message().await
     }
 
     async fn send_timeout_message(&mut self) -> WsResult {
-        self.stream
-            .send(ServerMessage::TimeoutAdded(TIMEOUT_DURATION.as_secs()).as_wsmsg())
-            .await
+        self.send(ServerMessage::TimeoutAdded(
+            self.limits.timeout_duration_secs,
+        ))
+        .await
     }
 
     async fn send_heartbeat(&mut self) -> WsResult {
-        self.stream
-            .send(ServerMessage::Heartbeat.as_wsmsg())
-            .await?;
+        self.send(ServerMessage::Heartbeat).await?;
         self.last_heartbeat = Instant::now();
 
         Ok(())
     }
 
+    /// Lets the room know the user is typing, unless they're muted
+    /// or already did less than [`TYPING_INTERVAL`] ago
+    async fn set_typing(&mut self, typing: bool) {
+        if !typing {
+            self.stop_typing();
+            return;
+        }
+        let throttled = self
+            .last_typing
+            .is_some_and(|at| at.elapsed() < TYPING_INTERVAL);
+        if throttled
+            || self
+                .room
+                .lock()
+                .await
+                .moderation()
+                .muted_until(&self.id)
+                .is_some()
+        {
+            return;
+        }
+
+        self.typing = true;
+        self.last_typing = Some(Instant::now());
+        let _ = self.tx.send(ServerMessage::UserTyping {
+            user: self.id,
+            typing,
+        });
+    }
+
+    /// Lets the room know the user isn't typing anymore, if they were
+    fn stop_typing(&mut self) {
+        if self.typing {
+            self.typing = false;
+            let _ = self.tx.send(ServerMessage::UserTyping {
+                user: self.id,
+                typing: false,
+            });
+        }
+    }
+
     async fn exit_room(&mut self) {
+        self.stop_typing();
         let mut room = self.room.lock().await;
         if let Some(user) = room.get_user(&self.id) {
-            let _ = self.tx.send(ServerMessage::UserLeft(user.clone()));
+            let msg = ServerMessage::UserLeft(user.clone());
+            self.broadcast(&mut room, msg);
         }
         room.remove_user(&self.id);
         self.in_room = false;
     }
 
     pub async fn close_socket(&mut self) -> WsResult {
+        self.close_with(None).await
+    }
+
+    async fn close_with(&mut self, frame: Option<CloseFrame>) -> WsResult {
         self.exit_room().await;
-        self.stream.send(Message::Close(None)).await?;
+        self.stream.send(Message::Close(frame)).await?;
 
         Ok(())
     }
@@ -292,8 +648,27 @@ where
     async fn handle_rx(&mut self, res: Result<ServerMessage, RecvError>) -> WsResult<bool> {
         match res {
             Ok(msg) => {
-                self.stream.send(msg.as_wsmsg()).await?;
                 log::trace!("User sent: {msg:?}");
+                let joined =
+                    matches!(&msg, ServerMessage::UserJoined(user) if *user.get_id() == self.id);
+                let removed_for = match &msg {
+                    ServerMessage::Sanctioned { user, sanction, .. } if *user == self.id => {
+                        removal_reason(sanction)
+                    }
+                    _ => None,
+                };
+                self.send(msg).await?;
+                if joined && let Some(history) = self.pending_history.take() {
+                    self.send(history).await?;
+                }
+                if let Some(reason) = removed_for {
+                    let frame = CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: reason.into(),
+                    };
+                    self.close_with(Some(frame)).await?;
+                    return Ok(true);
+                }
                 Ok(false)
             }
             Err(broadcast::error::RecvError::Closed) => {
@@ -304,34 +679,239 @@ where
             }
             Err(broadcast::error::RecvError::Lagged(n)) => {
                 log::warn!("Broadcast lagged behind by {n} messages");
+                self.metrics.lagged.inc();
+                self.metrics.lagged_messages.inc_by(n);
                 Ok(false)
             }
         }
     }
 
+    async fn handle_direct(&mut self, direct: Direct) -> WsResult<bool> {
+        match direct {
+            Direct::Message(msg) => {
+                self.send(msg).await?;
+                Ok(false)
+            }
+            Direct::Close(reason) => {
+                let frame = CloseFrame {
+                    code: CloseCode::Policy,
+                    reason: reason.into(),
+                };
+                self.close_with(Some(frame)).await?;
+                Ok(true)
+            }
+        }
+    }
+
     async fn send_msg(&mut self, txt: &str) -> WsResult {
-        let txt = self
-            .ctx
-            .process_with_options(txt.to_string(), &CONTEXT_OPTS);
-        match txt {
-            Ok(txt) => {
-                let _ = self.tx.send(ServerMessage::NewMessage(ChatMessage::new(
-                    self.id,
-                    txt.clone(),
-                )));
-            }
-            Err(ban) => {
-                self.stream
-                    .send(
-                        ServerMessage::Banned {
-                            duration: self.ctx.restricted_for(),
-                            reason: ban.generic_str().to_owned(),
-                        }
-                        .as_wsmsg(),
-                    )
-                    .await?;
+        if let Some(txt) = self.process_text(txt).await? {
+            self.stop_typing();
+            let msg = ServerMessage::NewMessage(ChatMessage::stamped(self.id, txt));
+            let mut room = self.room.lock().await;
+            self.metrics.message_sent(room.metrics_label());
+            self.broadcast(&mut room, msg);
+        }
+
+        Ok(())
+    }
+
+    async fn send_reply(&mut self, reply_to: MessageId, txt: &str) -> WsResult {
+        let Some(txt) = self.process_text(txt).await? else {
+            return Ok(());
+        };
+
+        self.stop_typing();
+        let msg = ChatMessage::stamped(self.id, txt).replying_to(reply_to);
+        let mut room = self.room.lock().await;
+        if room.has_message(reply_to) {
+            self.metrics.message_sent(room.metrics_label());
+            self.broadcast(&mut room, ServerMessage::NewMessage(msg));
+        } else {
+            drop(room);
+            self.send(ServerMessage::InvalidMessage(reply_to)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the message to `to` and echoes it back, without it reaching the rest of the room
+    async fn send_direct_msg(&mut self, to: Uuid, txt: &str) -> WsResult {
+        let Some(txt) = self.process_text(txt).await? else {
+            return Ok(());
+        };
+
+        let msg = ServerMessage::DirectMessage {
+            to,
+            message: ChatMessage::stamped(self.id, txt),
+        };
+        let room = self.room.lock().await;
+        if to != self.id && room.has_user(&to) && !room.understands(&to, &msg) {
+            drop(room);
+            let err = "The user's client can't receive direct messages".to_string();
+            return self.send(ServerMessage::UnsupportedMessage(err)).await;
+        }
+        let delivered = to == self.id || room.send_direct(&to, msg.clone());
+        if delivered {
+            self.metrics.message_sent(room.metrics_label());
+        }
+        drop(room);
+
+        if delivered {
+            self.send(msg).await
+        } else {
+            self.send(ServerMessage::InvalidUser(to)).await
+        }
+    }
+
+    async fn edit_msg(&mut self, id: MessageId, txt: &str) -> WsResult {
+        let Some(txt) = self.process_text(txt).await? else {
+            return Ok(());
+        };
+
+        let mut room = self.room.lock().await;
+        match room.edit_message(self.id, id, txt) {
+            Ok(message) => self.broadcast(&mut room, ServerMessage::MessageEdited(message)),
+            Err(err) => {
+                drop(room);
+                self.send(err.into_server_message(id)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_msg(&mut self, id: MessageId) -> WsResult {
+        let mut room = self.room.lock().await;
+        match room.delete_message(self.id, id) {
+            Ok(()) => self.broadcast(&mut room, ServerMessage::MessageDeleted(id)),
+            Err(err) => {
+                drop(room);
+                self.send(err.into_server_message(id)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn react(&mut self, id: MessageId, reaction: String, add: bool) -> WsResult {
+        if self.check_muted().await? {
+            return Ok(());
+        }
+
+        let reaction = reaction.trim().to_string();
+        if add
+            && (reaction.is_empty()
+                || reaction.chars().count() > MAX_REACTION_LENGTH
+                || reaction.chars().any(char::is_whitespace)
+                || is_inappropriate(&self.room.lock().await.filter(), &reaction))
+        {
+            return self.send(ServerMessage::InvalidReaction(reaction)).await;
+        }
+
+        let mut room = self.room.lock().await;
+        match room.react(self.id, id, reaction, add) {
+            Ok(msg) => self.broadcast(&mut room, msg),
+            Err(err) => {
+                drop(room);
+                self.send(err.into_server_message(id)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the text through the room's filter,
+    /// returns `None` if it was rejected, the user got banned for it or is muted
+    async fn process_text(&mut self, txt: &str) -> WsResult<Option<String>> {
+        if self.check_muted().await? {
+            return Ok(None);
+        }
+
+        let policy = self.room.lock().await.filter();
+        let filtered = filter_message(
+            &policy,
+            self.penalties.lock().ctx_mut(),
+            &self.ctx_opts,
+            txt,
+        );
+        match filtered {
+            Ok(filtered) => {
+                if filtered.censored {
+                    self.metrics.message_filtered(FilterAction::Censored);
+                }
+                Ok(Some(filtered.text))
             }
+            Err(Rejection::Inappropriate) => {
+                self.metrics.message_filtered(FilterAction::Rejected);
+                self.send(ServerMessage::MessageRejected).await?;
+                Ok(None)
+            }
+            Err(Rejection::Blocked(ban)) => {
+                self.metrics.message_filtered(FilterAction::Blocked);
+                let duration = self.penalties.lock().ctx().restricted_for();
+                self.send(ServerMessage::Banned {
+                    duration,
+                    reason: ban.generic_str().to_owned(),
+                })
+                .await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sends the message to everyone in the room, including this connection,
+    /// and stores it in the room's history
+    fn broadcast(&self, room: &mut Room, msg: ServerMessage) {
+        room.record(&msg);
+        let _ = self.tx.send(msg);
+    }
+
+    /// Whether the user is still in the room, it's not after the socket is closed properly
+    #[must_use]
+    pub const fn in_room(&self) -> bool {
+        self.in_room
+    }
+
+    /// Stops the handler of a dropped connection,
+    /// returns what a resumed connection needs to continue where this one stopped
+    pub fn suspend(mut self) -> Suspended {
+        self.stop_typing();
+        Suspended {
+            id: self.id,
+            direct_rx: self.direct_rx,
+            penalties: self.penalties,
+        }
+    }
+
+    /// Queues the room's history to be sent once this connection's join has been announced
+    pub fn queue_history(&mut self, history: ServerMessage) {
+        self.pending_history = Some(history);
+    }
+
+    /// Sends what a resumed connection missed while it was dropped, before any live message
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the connection drops again
+    pub async fn replay(&mut self, missed: Vec<ServerMessage>) -> WsResult {
+        for msg in missed {
+            self.send(msg).await?;
         }
         Ok(())
     }
+
+    /// Sends a message to this connection in the form its api version expects,
+    /// messages the version can't represent are dropped
+    ///
+    /// If a request is being handled the message is marked as a reply to it
+    async fn send(&mut self, msg: ServerMessage) -> WsResult {
+        if let Some(msg) = msg.into_version(self.protocol.version) {
+            let response = ServerResponse::new(self.current_request, msg);
+            self.stream
+                .send(response.encode(self.protocol.encoding))
+                .await?;
+        }
+
+        Ok(())
+    }
 }
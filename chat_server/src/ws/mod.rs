@@ -2,21 +2,77 @@ use std::{collections::HashMap, sync::Arc};
 
 use chat_lib::types::ServerMessage;
 use room::Room;
-use tokio::sync::{Mutex, broadcast};
+use tokio::sync::{Mutex, broadcast, mpsc};
 
-use crate::ws::room::RoomComponents;
+use crate::{config::ServerConfig, storage::SyncStorage, ws::room::RoomComponents};
 
+pub mod access;
 mod handler;
+pub mod moderation;
 mod room_args;
 mod router;
 mod routes;
 
 pub mod room;
 
-pub use router::paths;
+pub use access::RoomAccess;
+pub use moderation::Moderation;
+
+pub(crate) use router::paths;
+pub(crate) use routes::remove_if_empty;
 
 pub type BroadCastT = ServerMessage;
 pub type MsgBroadcastSender = broadcast::Sender<BroadCastT>;
 pub type MsgBroadcastReceiver = broadcast::Receiver<BroadCastT>;
 
+/// What's sent to a single connection, bypassing the room's broadcast
+#[derive(Debug)]
+pub enum Direct {
+    Message(ServerMessage),
+    /// Closes the connection with the reason, its user leaves the room
+    Close(String),
+}
+
+pub type DirectSender = mpsc::UnboundedSender<Direct>;
+pub type DirectReceiver = mpsc::UnboundedReceiver<Direct>;
+
 pub type SyncRoomComponents = Arc<Mutex<HashMap<String, Arc<Mutex<RoomComponents>>>>>;
+
+/// Opens the persistent rooms, the ones declared in the server config and the saved ones,
+/// they're kept even without anyone in them
+///
+/// Saved rooms that aren't persistent are forgotten, they'd start over anyway
+#[must_use]
+pub fn open_rooms(storage: &SyncStorage, config: &ServerConfig) -> SyncRoomComponents {
+    let saved = storage
+        .room_names()
+        .inspect_err(|err| log::error!("Couldn't list the saved rooms: {err}"))
+        .unwrap_or_default();
+
+    let mut rooms = HashMap::new();
+    for name in config.rooms.iter().chain(&saved) {
+        if rooms.contains_key(name) {
+            continue;
+        }
+        let room = Room::load(name.clone(), storage.clone(), config);
+        if room.is_persistent() {
+            rooms.insert(name.clone(), RoomComponents::sync(room, config));
+        } else if let Err(err) = storage.remove_room(name) {
+            log::error!("Couldn't remove room {name} from the storage: {err}");
+        }
+    }
+
+    Arc::new(Mutex::new(rooms))
+}
+
+/// Saves the changes the rooms haven't saved yet, so nothing is lost when the server stops
+pub async fn save_rooms(rooms: &SyncRoomComponents) {
+    let rooms = rooms.lock().await.values().cloned().collect::<Vec<_>>();
+    for components in rooms {
+        let room = components.lock().await.room.clone();
+        let save = room.lock().await.take_changes();
+        if let Some(save) = save {
+            save.write().await;
+        }
+    }
+}
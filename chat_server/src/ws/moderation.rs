@@ -0,0 +1,160 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::IpAddr,
+};
+
+use chat_lib::{ServerMessage, types::Sanction};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::consts::MAX_SANCTION_SECS;
+
+/// Who moderates a room and who is sanctioned in it, kept across reconnects and restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Moderation {
+    /// The registered user who created the room, can appoint moderators
+    owner: Option<Uuid>,
+    moderators: BTreeSet<Uuid>,
+    /// Until when each user is muted
+    mutes: HashMap<Uuid, DateTime<Utc>>,
+    /// Until when each registered user is banned
+    bans: HashMap<Uuid, DateTime<Utc>>,
+    /// The address each banned guest joined from and until when it's banned,
+    /// guests get a new id every time they join so their address is banned instead
+    guest_bans: HashMap<Uuid, (IpAddr, DateTime<Utc>)>,
+}
+
+/// The time a sanction lasting `secs` seconds ends, `None` if it lifts the sanction instead
+#[must_use]
+pub fn sanction_end(secs: u64) -> Option<DateTime<Utc>> {
+    if secs == 0 {
+        return None;
+    }
+    #[allow(clippy::cast_possible_wrap)]
+    let duration = TimeDelta::seconds(secs.min(MAX_SANCTION_SECS) as i64);
+
+    Some(Utc::now() + duration)
+}
+
+impl Moderation {
+    /// Makes the user the owner, if the room doesn't have one yet
+    pub fn claim(&mut self, id: Uuid) -> bool {
+        if self.owner.is_some() {
+            return false;
+        }
+        self.owner = Some(id);
+
+        true
+    }
+
+    #[must_use]
+    pub fn is_owner(&self, id: &Uuid) -> bool {
+        self.owner.as_ref() == Some(id)
+    }
+
+    #[must_use]
+    pub fn is_moderator(&self, id: &Uuid) -> bool {
+        self.is_owner(id) || self.moderators.contains(id)
+    }
+
+    /// Whether the user can change the room's topic and description,
+    /// anyone can in a room without an owner
+    #[must_use]
+    pub fn can_describe(&self, id: &Uuid) -> bool {
+        self.owner.is_none() || self.is_moderator(id)
+    }
+
+    /// Whether `by` can sanction `user`, moderators can only be sanctioned by the owner
+    #[must_use]
+    pub fn can_sanction(&self, by: &Uuid, user: &Uuid) -> bool {
+        by != user
+            && self.is_moderator(by)
+            && !self.is_owner(user)
+            && (self.is_owner(by) || !self.is_moderator(user))
+    }
+
+    /// Appoints or dismisses the moderator, returns whether anything changed
+    pub fn set_moderator(&mut self, id: Uuid, moderator: bool) -> bool {
+        if self.is_owner(&id) {
+            return false;
+        }
+        if moderator {
+            self.moderators.insert(id)
+        } else {
+            self.moderators.remove(&id)
+        }
+    }
+
+    /// Remembers the sanction, kicks leave nothing behind,
+    /// `guest_ip` is where the user joined from if they're a guest
+    pub fn apply(&mut self, user: Uuid, sanction: &Sanction, guest_ip: Option<IpAddr>) {
+        match sanction {
+            Sanction::Kick => {}
+            Sanction::Mute(until) => {
+                self.mutes.insert(user, *until);
+            }
+            Sanction::Unmute => {
+                self.mutes.remove(&user);
+            }
+            Sanction::Ban(until) => {
+                if let Some(ip) = guest_ip {
+                    self.guest_bans.insert(user, (ip, *until));
+                } else {
+                    self.bans.insert(user, *until);
+                }
+            }
+            Sanction::Unban => {
+                self.bans.remove(&user);
+                self.guest_bans.remove(&user);
+            }
+        }
+    }
+
+    /// Until when the user is muted, if they are
+    #[must_use]
+    pub fn muted_until(&self, id: &Uuid) -> Option<DateTime<Utc>> {
+        self.mutes
+            .get(id)
+            .copied()
+            .filter(|until| *until > Utc::now())
+    }
+
+    /// Until when the user is banned, if they are
+    #[must_use]
+    pub fn banned_until(&self, id: &Uuid) -> Option<DateTime<Utc>> {
+        self.bans
+            .get(id)
+            .or_else(|| self.guest_bans.get(id).map(|(_, until)| until))
+            .copied()
+            .filter(|until| *until > Utc::now())
+    }
+
+    /// Until when guests joining from the address are banned, if they are
+    #[must_use]
+    pub fn guest_banned_until(&self, ip: &IpAddr) -> Option<DateTime<Utc>> {
+        self.guest_bans
+            .values()
+            .filter(|(banned, until)| banned == ip && *until > Utc::now())
+            .map(|(_, until)| *until)
+            .max()
+    }
+
+    /// Forgets the sanctions that already ended
+    pub fn prune(&mut self) {
+        let now = Utc::now();
+        self.mutes.retain(|_, until| *until > now);
+        self.bans.retain(|_, until| *until > now);
+        self.guest_bans.retain(|_, (_, until)| *until > now);
+    }
+
+    /// The message telling the clients who can moderate the room
+    #[must_use]
+    pub fn moderators(&self) -> ServerMessage {
+        ServerMessage::Moderators {
+            owner: self.owner,
+            moderators: self.moderators.iter().copied().collect(),
+        }
+    }
+}
@@ -1,12 +1,30 @@
-use std::{collections::HashMap, fmt::Debug, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Debug,
+    net::IpAddr,
+    sync::{Arc, Weak},
+};
 
-use chat_lib::prelude::*;
-use tokio::sync::{Mutex, broadcast};
+use chat_lib::{
+    RoomInfo, Version,
+    filter::FilterPolicy,
+    prelude::*,
+    types::{Message, MessageId, Reaction, Sanction},
+};
+use chrono::Utc;
+use tokio::sync::{Mutex, Notify, broadcast};
 use uuid::Uuid;
 
 use crate::{
-    consts::BROADCAST_BUFFER_SIZE,
-    ws::{BroadCastT, MsgBroadcastSender},
+    clients::SharedPenalties,
+    config::{LimitsConfig, ServerConfig},
+    consts::ROOM_SAVE_DELAY,
+    filter::normalize_policy,
+    storage::{MemoryStorage, RoomRecord, SyncStorage},
+    ws::{
+        BroadCastT, Direct, DirectReceiver, DirectSender, Moderation, MsgBroadcastSender,
+        RoomAccess, access::Lock,
+    },
 };
 
 pub struct RoomComponents {
@@ -20,53 +38,549 @@ impl Debug for RoomComponents {
     }
 }
 
-impl Default for RoomComponents {
-    fn default() -> Self {
-        Self::new()
+impl RoomComponents {
+    /// Opens the already loaded room and starts saving its changes in the background
+    #[must_use]
+    pub fn from_room(room: Room, config: &ServerConfig) -> Self {
+        let (tx, _rx) = broadcast::channel::<BroadCastT>(config.limits.broadcast_buffer_size);
+        let changed = room.changed.clone();
+        let room = Arc::new(Mutex::new(room));
+        tokio::spawn(save_changes(Arc::downgrade(&room), changed));
+
+        Self { room, tx }
     }
-}
 
-impl RoomComponents {
     #[must_use]
-    pub fn new() -> Self {
-        let (tx, _rx) = broadcast::channel::<BroadCastT>(BROADCAST_BUFFER_SIZE);
-        Self {
-            room: Arc::new(Mutex::new(Room::new())),
-            tx,
-        }
+    pub fn sync(room: Room, config: &ServerConfig) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self::from_room(room, config)))
     }
+}
+
+fn is_message(msg: &ServerMessage, id: MessageId) -> bool {
+    matches!(msg, ServerMessage::NewMessage(message) if message.get_id() == Some(&id))
+}
 
+/// Why a message couldn't be changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageChangeError {
+    /// The message isn't in the history, either it never existed or it's too old
+    NotFound,
+    NotAuthor,
+}
+
+impl MessageChangeError {
     #[must_use]
-    pub fn sync() -> Arc<Mutex<Self>> {
-        Arc::new(Mutex::new(Self::new()))
+    pub const fn into_server_message(self, id: MessageId) -> ServerMessage {
+        match self {
+            MessageChangeError::NotFound => ServerMessage::InvalidMessage(id),
+            MessageChangeError::NotAuthor => ServerMessage::NotAuthor(id),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Where the room's filter policy comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterSource {
+    /// The default of the server config
+    Default,
+    /// Set by the room's owner
+    Owner,
+    /// Set for this room in the server config, it can't be changed
+    Config,
+}
+
+/// What's left of a dropped connection, waiting for it to resume
+#[derive(Debug)]
+pub struct Suspended {
+    pub id: Uuid,
+    pub direct_rx: DirectReceiver,
+    pub penalties: SharedPenalties,
+}
+
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Room {
+    name: String,
+    /// Where the room is saved a while after it changes
+    storage: SyncStorage,
+    /// Whether the room changed since it was last saved
+    dirty: bool,
+    /// Wakes the task saving the room's changes
+    changed: Arc<Notify>,
     users: HashMap<Uuid, User>,
+    /// The direct channel of every connection in the room
+    connections: HashMap<Uuid, DirectSender>,
+    /// The api version of every connection in the room, older ones drop newer messages
+    versions: HashMap<Uuid, Version>,
+    /// The recent events, the oldest one is at the front
+    history: VecDeque<ServerMessage>,
+    history_size: usize,
+    /// Users who left, but are still referenced by the history
+    departed: HashMap<Uuid, User>,
+    /// The reactions to the messages in the history and who reacted with them
+    reactions: HashMap<MessageId, BTreeMap<String, BTreeSet<Uuid>>>,
+    /// Dropped connections by their resume token, their users are still in the room
+    suspended: HashMap<String, Suspended>,
+    moderation: Moderation,
+    filter: Arc<FilterPolicy>,
+    filter_source: FilterSource,
+    info: RoomInfo,
+    access: RoomAccess,
+    /// Created by a registered user, rooms in the server config are persistent without it
+    persistent: bool,
+    /// Declared in the server config, nobody can claim it
+    declared: bool,
+    /// Where the guests in the room joined from, so they can be banned by it,
+    /// kept for the guests who left while the history refers to them
+    guest_ips: HashMap<Uuid, IpAddr>,
+    /// Closed by the operator, nobody joins it and it's not saved anymore
+    closed: bool,
+}
+
+/// A snapshot of a room waiting to be written to the storage
+#[derive(Debug)]
+pub struct PendingSave {
+    storage: SyncStorage,
+    name: String,
+    record: RoomRecord,
+}
+
+impl PendingSave {
+    /// Writes the snapshot without blocking the runtime,
+    /// failing to do so only loses the latest changes so it's just logged
+    pub async fn write(self) {
+        let Self {
+            storage,
+            name,
+            record,
+        } = self;
+        let res = tokio::task::spawn_blocking(move || {
+            storage
+                .save_room(&name, &record)
+                .map_err(|err| anyhow::anyhow!("Couldn't save room {name}: {err}"))
+        })
+        .await;
+
+        match res {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => log::error!("{err}"),
+            Err(err) => log::error!("Saving a room panicked: {err}"),
+        }
+    }
+}
+
+/// Saves the room a while after it changes, until the room is dropped
+async fn save_changes(room: Weak<Mutex<Room>>, changed: Arc<Notify>) {
+    loop {
+        changed.notified().await;
+        tokio::time::sleep(ROOM_SAVE_DELAY).await;
+        let Some(room) = room.upgrade() else {
+            return;
+        };
+        let save = room.lock().await.take_changes();
+        drop(room);
+        if let Some(save) = save {
+            save.write().await;
+        }
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        // happens once per room, when it's closed, so writing right away is fine
+        if let Some(PendingSave {
+            storage,
+            name,
+            record,
+        }) = self.take_changes()
+            && let Err(err) = storage.save_room(&name, &record)
+        {
+            log::error!("Couldn't save room {name}: {err}");
+        }
+        // wakes the saving task, so it sees the room is gone
+        self.changed.notify_one();
+    }
 }
 
 #[allow(unused)]
 impl Default for Room {
     fn default() -> Self {
-        Self::new()
+        Self::new(
+            String::new(),
+            LimitsConfig::default().history_size,
+            Arc::new(MemoryStorage::default()),
+        )
     }
 }
 
 impl Room {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(name: String, history_size: usize, storage: SyncStorage) -> Self {
         Self {
+            info: RoomInfo::new(name.clone()),
+            name,
+            storage,
+            dirty: false,
+            changed: Arc::default(),
             users: HashMap::new(),
+            connections: HashMap::new(),
+            versions: HashMap::new(),
+            history: VecDeque::with_capacity(history_size),
+            history_size,
+            departed: HashMap::new(),
+            reactions: HashMap::new(),
+            suspended: HashMap::new(),
+            moderation: Moderation::default(),
+            filter: Arc::default(),
+            filter_source: FilterSource::Default,
+            access: RoomAccess::default(),
+            persistent: false,
+            declared: false,
+            guest_ips: HashMap::new(),
+            closed: false,
+        }
+    }
+
+    /// Creates the room with the history it had when it was last saved,
+    /// only persistent rooms are restored, the others start over
+    #[must_use]
+    pub fn load(name: String, storage: SyncStorage, config: &ServerConfig) -> Self {
+        let declared = config.rooms.contains(&name);
+        let record = storage
+            .load_room(&name)
+            .inspect_err(|err| log::error!("Couldn't load room {name}: {err}"))
+            .ok()
+            .flatten()
+            .filter(|record| declared || record.persistent);
+
+        let history_size = config.limits.history_size;
+        let pinned_filter = config.filter.rooms.get(&name).cloned();
+        let mut room = Self::new(name, history_size, storage);
+        room.declared = declared;
+        let (filter, source) = match (
+            pinned_filter,
+            record.as_ref().and_then(|r| r.filter.clone()),
+        ) {
+            (Some(filter), _) => (filter, FilterSource::Config),
+            (None, Some(filter)) => (filter, FilterSource::Owner),
+            (None, None) => (config.filter.policy.clone(), FilterSource::Default),
+        };
+        room.filter = Arc::new(normalize_policy(filter));
+        room.filter_source = source;
+
+        let Some(record) = record else {
+            // the creator is whoever joins first
+            room.info.created_at = Some(Utc::now());
+            room.info.persistent = declared;
+            return room;
+        };
+
+        if let Some(info) = record.info {
+            room.info = RoomInfo {
+                name: room.name.clone(),
+                ..info
+            };
+        }
+        room.access = record.access;
+        room.access.prune();
+        room.info.access = room.access.access();
+        room.persistent = record.persistent;
+        room.info.persistent = declared || record.persistent;
+
+        let skip = record.history.len().saturating_sub(history_size);
+        room.history.extend(record.history.into_iter().skip(skip));
+        // nobody is connected yet, so everyone the history refers to has left
+        room.departed = record
+            .users
+            .into_iter()
+            .map(|user| (*user.get_id(), user))
+            .collect();
+        room.prune_departed();
+        room.moderation = record.moderation;
+        room.moderation.prune();
+        for (id, reactions) in record.reactions {
+            if !room.has_message(id) {
+                continue;
+            }
+            let reactions = reactions
+                .into_iter()
+                .map(|reaction| {
+                    let users = reaction.get_users().iter().copied().collect();
+                    (reaction.get_reaction().to_string(), users)
+                })
+                .collect();
+            room.reactions.insert(id, reactions);
+        }
+
+        room
+    }
+
+    /// Everything about the room that should survive a restart
+    #[must_use]
+    pub fn to_record(&self) -> RoomRecord {
+        let reactions = self
+            .reactions
+            .keys()
+            .filter_map(|id| match self.reactions_changed(*id) {
+                ServerMessage::ReactionsChanged { id, reactions } => Some((id, reactions)),
+                _ => None,
+            })
+            .collect();
+
+        RoomRecord {
+            history: self.history.iter().cloned().collect(),
+            users: self.referenced_users(),
+            reactions,
+            moderation: self.moderation.clone(),
+            filter: (self.filter_source == FilterSource::Owner).then(|| (*self.filter).clone()),
+            info: Some(self.info.clone()),
+            access: self.access.clone(),
+            persistent: self.persistent,
+        }
+    }
+
+    /// Marks the room changed, it's saved in the background a while later,
+    /// so a burst of changes is written once and never while holding the room's lock
+    ///
+    /// Rooms that aren't persistent aren't saved at all
+    fn persist(&mut self) {
+        if !self.is_persistent() || self.closed {
+            return;
+        }
+        self.dirty = true;
+        self.changed.notify_one();
+    }
+
+    /// The room to save, if it changed since it was last taken
+    pub fn take_changes(&mut self) -> Option<PendingSave> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+
+        Some(PendingSave {
+            storage: self.storage.clone(),
+            name: self.name.clone(),
+            record: self.to_record(),
+        })
+    }
+
+    /// Everyone the history refers to
+    fn referenced_users(&self) -> Vec<User> {
+        let mut users = HashMap::new();
+        for id in self.history.iter().filter_map(ServerMessage::user_id) {
+            if let Some(user) = self.known_user(&id) {
+                users.entry(id).or_insert_with(|| user.clone());
+            }
+        }
+
+        users.into_values().collect()
+    }
+
+    /// Stores the message in the history if it's worth replaying
+    pub fn record(&mut self, msg: &ServerMessage) {
+        if !matches!(
+            msg,
+            ServerMessage::NewMessage(_)
+                | ServerMessage::UserJoined(_)
+                | ServerMessage::UserLeft(_)
+        ) || self.history_size == 0
+        {
+            return;
+        }
+
+        if self.history.len() >= self.history_size {
+            if let Some(ServerMessage::NewMessage(message)) = self.history.pop_front()
+                && let Some(id) = message.get_id()
+            {
+                self.reactions.remove(id);
+            }
+            self.prune_departed();
+        }
+        self.history.push_back(msg.clone());
+        self.persist();
+    }
+
+    /// The history in the form it's sent to new connections
+    #[must_use]
+    pub fn history(&self) -> ServerMessage {
+        ServerMessage::History {
+            users: self.referenced_users(),
+            events: self.replay(0),
         }
     }
 
+    /// The events of the history after the message, for a dropped connection that saw it last,
+    /// if the message is unknown or too old it can't tell what was missed,
+    /// so the whole history is sent again as a [`ServerMessage::History`]
+    #[must_use]
+    pub fn missed_since(&self, after: Option<MessageId>) -> Vec<ServerMessage> {
+        match after.and_then(|id| self.history.iter().position(|msg| is_message(msg, id))) {
+            Some(pos) => self.replay(pos + 1),
+            None => vec![self.history()],
+        }
+    }
+
+    /// The events of the history from `start`, each message followed by its reactions
+    fn replay(&self, start: usize) -> Vec<ServerMessage> {
+        let mut events = Vec::with_capacity(self.history.len().saturating_sub(start));
+        for msg in self.history.iter().skip(start) {
+            events.push(msg.clone());
+            if let ServerMessage::NewMessage(message) = msg
+                && let Some(id) = message.get_id()
+                && self.reactions.contains_key(id)
+            {
+                events.push(self.reactions_changed(*id));
+            }
+        }
+
+        events
+    }
+
+    /// Adds or removes the user's reaction to the message,
+    /// returns the message announcing the message's new reactions
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the message isn't in the history
+    pub fn react(
+        &mut self,
+        user: Uuid,
+        id: MessageId,
+        reaction: String,
+        add: bool,
+    ) -> Result<ServerMessage, MessageChangeError> {
+        if !self.has_message(id) {
+            return Err(MessageChangeError::NotFound);
+        }
+
+        let reactions = self.reactions.entry(id).or_default();
+        if add {
+            reactions.entry(reaction).or_default().insert(user);
+        } else if let Some(users) = reactions.get_mut(&reaction) {
+            users.remove(&user);
+            if users.is_empty() {
+                reactions.remove(&reaction);
+            }
+        }
+        if reactions.is_empty() {
+            self.reactions.remove(&id);
+        }
+        self.persist();
+
+        Ok(self.reactions_changed(id))
+    }
+
+    fn reactions_changed(&self, id: MessageId) -> ServerMessage {
+        let reactions = self
+            .reactions
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|(reaction, users)| {
+                Reaction::new(reaction.clone(), users.iter().copied().collect())
+            })
+            .collect();
+
+        ServerMessage::ReactionsChanged { id, reactions }
+    }
+
+    /// Whether the message is in the history, only those can be replied or reacted to
+    #[must_use]
+    pub fn has_message(&self, id: MessageId) -> bool {
+        self.history.iter().any(|msg| is_message(msg, id))
+    }
+
+    /// Edits the message in the history, returns the edited message
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the message isn't in the history or `author` didn't write it
+    pub fn edit_message(
+        &mut self,
+        author: Uuid,
+        id: MessageId,
+        content: String,
+    ) -> Result<Message, MessageChangeError> {
+        let message = self.authored_message_mut(author, id)?;
+        message.edit(content);
+        let message = message.clone();
+        self.persist();
+
+        Ok(message)
+    }
+
+    /// Removes the message from the history
+    ///
+    /// # Errors
+    ///
+    /// This function errors if the message isn't in the history or `author` didn't write it
+    pub fn delete_message(
+        &mut self,
+        author: Uuid,
+        id: MessageId,
+    ) -> Result<(), MessageChangeError> {
+        self.authored_message_mut(author, id)?;
+        self.history.retain(|msg| !is_message(msg, id));
+        self.reactions.remove(&id);
+        self.persist();
+
+        Ok(())
+    }
+
+    fn authored_message_mut(
+        &mut self,
+        author: Uuid,
+        id: MessageId,
+    ) -> Result<&mut Message, MessageChangeError> {
+        let message = self
+            .history
+            .iter_mut()
+            .find_map(|msg| match msg {
+                ServerMessage::NewMessage(message) if message.get_id() == Some(&id) => {
+                    Some(message)
+                }
+                _ => None,
+            })
+            .ok_or(MessageChangeError::NotFound)?;
+
+        if *message.get_author() == author {
+            Ok(message)
+        } else {
+            Err(MessageChangeError::NotAuthor)
+        }
+    }
+
+    fn prune_departed(&mut self) {
+        let history = &self.history;
+        self.departed
+            .retain(|id, _| history.iter().any(|msg| msg.is_user(*id)));
+        let (users, departed) = (&self.users, &self.departed);
+        self.guest_ips
+            .retain(|id, _| users.contains_key(id) || departed.contains_key(id));
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.users.is_empty()
     }
 
+    /// How many users are in the room, including those whose connection is suspended
+    #[must_use]
+    pub fn user_count(&self) -> usize {
+        self.users.len()
+    }
+
+    /// How many users are in the room only because their connection is waiting to be resumed
+    #[must_use]
+    pub fn suspended_count(&self) -> usize {
+        self.suspended.len()
+    }
+
     #[must_use]
     pub fn has_user(&self, id: &Uuid) -> bool {
         self.users.contains_key(id)
@@ -77,6 +591,18 @@ impl Room {
         self.users.get(id)
     }
 
+    /// The user, even if they left, as long as the history refers to them
+    #[must_use]
+    pub fn known_user(&self, id: &Uuid) -> Option<&User> {
+        self.users.get(id).or_else(|| self.departed.get(id))
+    }
+
+    /// Whether the user joined as a guest, it's remembered for a while after they left
+    #[must_use]
+    pub fn is_guest(&self, id: &Uuid) -> bool {
+        self.guest_ips.contains_key(id)
+    }
+
     #[must_use]
     pub fn get_all_users(&self) -> Vec<User> {
         self.users.values().cloned().collect()
@@ -94,10 +620,518 @@ impl Room {
     }
 
     pub fn remove_user(&mut self, id: &Uuid) -> Option<User> {
-        self.users.remove(id)
+        self.connections.remove(id);
+        self.versions.remove(id);
+        let user = self.users.remove(id)?;
+        if self.history.iter().any(|msg| msg.is_user(*id)) {
+            self.departed.insert(*id, user.clone());
+        } else {
+            self.guest_ips.remove(id);
+        }
+
+        Some(user)
+    }
+
+    /// Whether nobody joined the room since it was created
+    #[must_use]
+    pub const fn is_new(&self) -> bool {
+        self.info.creator.is_none() && self.info.created_at.is_some()
     }
 
     pub fn add_user(&mut self, user: User) {
+        let created = self.is_new();
+        if created {
+            self.info.creator = Some(user.clone());
+        }
         self.users.entry(*user.get_id()).insert_entry(user);
+        if created {
+            self.persist();
+        }
+    }
+
+    pub fn add_connection(&mut self, id: Uuid, tx: DirectSender, version: Version) {
+        self.connections.insert(id, tx);
+        self.versions.insert(id, version);
+    }
+
+    /// Keeps up with the version of a resumed connection, it can differ from the dropped one's
+    pub fn set_version(&mut self, id: Uuid, version: Version) {
+        self.versions.insert(id, version);
+    }
+
+    /// Whether the user's connection can represent the message, V1 drops the newer ones
+    #[must_use]
+    pub fn understands(&self, id: &Uuid, msg: &ServerMessage) -> bool {
+        self.versions
+            .get(id)
+            .is_some_and(|version| msg.clone().into_version(*version).is_some())
+    }
+
+    /// Remembers where the guest joined from, a ban against them bans the address
+    pub fn add_guest(&mut self, id: Uuid, ip: IpAddr) {
+        self.guest_ips.insert(id, ip);
+    }
+
+    /// Keeps the user in the room until the connection is resumed with the token or expires
+    pub fn suspend(&mut self, token: String, suspended: Suspended) {
+        self.suspended.insert(token, suspended);
+    }
+
+    #[must_use]
+    pub fn can_resume(&self, token: &str) -> bool {
+        self.suspended.contains_key(token)
+    }
+
+    /// Takes the dropped connection back
+    pub fn resume(&mut self, token: &str) -> Option<Suspended> {
+        self.suspended.remove(token)
+    }
+
+    /// Ends the suspension, returns the user if it was still waiting to be resumed
+    pub fn expire(&mut self, token: &str) -> Option<User> {
+        let suspended = self.suspended.remove(token)?;
+        self.get_user(&suspended.id).cloned()
+    }
+
+    /// Whether the user is in the room only because its connection is waiting to be resumed
+    #[must_use]
+    pub fn is_suspended(&self, id: &Uuid) -> bool {
+        self.suspended.values().any(|suspended| suspended.id == *id)
+    }
+
+    /// Drops the suspended connections of the user, without removing the user
+    pub fn cancel_suspension(&mut self, id: &Uuid) {
+        self.suspended.retain(|_, suspended| suspended.id != *id);
+    }
+
+    #[must_use]
+    pub const fn moderation(&self) -> &Moderation {
+        &self.moderation
+    }
+
+    /// Makes the registered user the owner if they're creating the room
+    ///
+    /// Rooms that already existed and rooms declared in the server config can't be claimed
+    pub fn claim(&mut self, id: Uuid) {
+        if !self.is_new() || self.declared {
+            return;
+        }
+        self.moderation.claim(id);
+        self.persist();
+    }
+
+    /// Keeps the room, its history and its settings when everyone leaves
+    pub fn make_persistent(&mut self) {
+        self.persistent = true;
+        self.info.persistent = true;
+        self.persist();
+    }
+
+    /// Whether the room is kept when everyone leaves
+    #[must_use]
+    pub const fn is_persistent(&self) -> bool {
+        self.info.persistent
+    }
+
+    /// Stops saving the room, the changes that weren't saved yet are dropped
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.dirty = false;
+    }
+
+    /// Whether the operator closed the room
+    #[must_use]
+    pub const fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// The room's own label in the metrics, ephemeral rooms are counted together
+    #[must_use]
+    pub fn metrics_label(&self) -> Option<&str> {
+        self.is_persistent().then_some(self.name.as_str())
+    }
+
+    /// Remembers the sanction against the user
+    pub fn sanction(&mut self, user: Uuid, sanction: &Sanction) {
+        self.moderation.prune();
+        let guest_ip = self.guest_ips.get(&user).copied();
+        self.moderation.apply(user, sanction, guest_ip);
+        self.persist();
+    }
+
+    /// Appoints or dismisses the moderator, returns whether anything changed
+    pub fn set_moderator(&mut self, id: Uuid, moderator: bool) -> bool {
+        let changed = self.moderation.set_moderator(id, moderator);
+        if changed {
+            self.persist();
+        }
+
+        changed
+    }
+
+    /// The policy everything said in the room goes through
+    #[must_use]
+    pub fn filter(&self) -> Arc<FilterPolicy> {
+        self.filter.clone()
+    }
+
+    /// Replaces the filter policy with the one the owner set,
+    /// returns `false` if the server config sets it for this room
+    pub fn set_filter(&mut self, policy: FilterPolicy) -> bool {
+        if self.filter_source == FilterSource::Config {
+            return false;
+        }
+        self.filter = Arc::new(normalize_policy(policy));
+        self.filter_source = FilterSource::Owner;
+        self.persist();
+
+        true
+    }
+
+    /// What the room is about and where it came from
+    #[must_use]
+    pub const fn info(&self) -> &RoomInfo {
+        &self.info
+    }
+
+    /// Changes the topic, an empty one removes it,
+    /// returns the message announcing the room's new info
+    pub fn set_topic(&mut self, topic: String) -> ServerMessage {
+        self.info.topic = Some(topic).filter(|topic| !topic.is_empty());
+        self.persist();
+
+        ServerMessage::RoomInfo(self.info.clone())
+    }
+
+    /// Changes the description, an empty one removes it,
+    /// returns the message announcing the room's new info
+    pub fn set_description(&mut self, description: String) -> ServerMessage {
+        self.info.description = Some(description).filter(|description| !description.is_empty());
+        self.persist();
+
+        ServerMessage::RoomInfo(self.info.clone())
+    }
+
+    /// Who can join the room
+    #[must_use]
+    pub const fn access(&self) -> &RoomAccess {
+        &self.access
+    }
+
+    /// Changes what joining needs, returns the message announcing the room's new info
+    pub fn set_lock(&mut self, lock: Lock) -> ServerMessage {
+        self.access.set_lock(lock);
+        self.info.access = self.access.access();
+        self.persist();
+
+        ServerMessage::RoomInfo(self.info.clone())
+    }
+
+    /// Creates an invite valid for `secs` seconds, returns the message telling its code
+    pub fn invite(&mut self, secs: u64) -> ServerMessage {
+        let (code, expires_at) = self.access.invite(secs);
+        self.persist();
+
+        ServerMessage::InviteCreated { code, expires_at }
+    }
+
+    /// Removes the user if only their suspended connection keeps them in the room,
+    /// as there's no connection to disconnect
+    pub fn remove_suspended(&mut self, id: &Uuid) -> Option<User> {
+        if !self.is_suspended(id) {
+            return None;
+        }
+        self.cancel_suspension(id);
+
+        self.remove_user(id)
+    }
+
+    /// Sends the message only to the connection of the user,
+    /// returns if the user has a connection in the room
+    #[must_use]
+    pub fn send_direct(&self, to: &Uuid, msg: ServerMessage) -> bool {
+        self.connections
+            .get(to)
+            .is_some_and(|tx| tx.send(Direct::Message(msg)).is_ok())
+    }
+
+    /// Tells the user's connection to close itself, returns if the connection is still open
+    #[must_use]
+    pub fn close_connection(&self, id: &Uuid, reason: &str) -> bool {
+        !self.is_suspended(id)
+            && self
+                .connections
+                .get(id)
+                .is_some_and(|tx| tx.send(Direct::Close(reason.to_string())).is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    /// A room with a guest who said something, so the history refers to them
+    fn room_with_guest() -> (Room, Uuid) {
+        let mut room = Room::default();
+        let guest = User::new(Uuid::new_v4(), "guest".to_string());
+        let id = *guest.get_id();
+        room.add_guest(id, IP);
+        room.add_user(guest);
+        room.record(&ServerMessage::NewMessage(Message::stamped(
+            id,
+            "hello".to_string(),
+        )));
+
+        (room, id)
+    }
+
+    fn message(room: &mut Room, content: &str) -> MessageId {
+        let message = Message::stamped(Uuid::new_v4(), content.to_string());
+        let id = *message
+            .get_id()
+            .expect("A stamped message should have an id");
+        room.record(&ServerMessage::NewMessage(message));
+
+        id
+    }
+
+    fn contents(events: &[ServerMessage]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|msg| match msg {
+                ServerMessage::NewMessage(message) => Some(message.get_content()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resuming_after_a_known_message_replays_what_came_after_it() {
+        let mut room = Room::default();
+        let seen = message(&mut room, "seen");
+        message(&mut room, "missed");
+        message(&mut room, "missed too");
+
+        let missed = room.missed_since(Some(seen));
+
+        assert_eq!(contents(&missed), ["missed", "missed too"]);
+    }
+
+    #[test]
+    fn resuming_after_a_forgotten_message_resets_the_history() {
+        let mut room = Room::default();
+        let seen = message(&mut room, "seen");
+        for i in 0..room.history_size {
+            message(&mut room, &format!("message {i}"));
+        }
+
+        let missed = room.missed_since(Some(seen));
+
+        let [ServerMessage::History { events, .. }] = missed.as_slice() else {
+            panic!("The whole history should be sent again, got {missed:?}");
+        };
+        assert_eq!(events.len(), room.history_size);
+        assert!(!contents(events).contains(&"seen"));
+    }
+
+    #[test]
+    fn history_keeps_the_latest_events() {
+        let mut room = Room::default();
+        for i in 0..room.history_size + 5 {
+            message(&mut room, &format!("message {i}"));
+        }
+        room.record(&ServerMessage::Announcement("not replayed".to_string()));
+
+        let ServerMessage::History { events, .. } = room.history() else {
+            panic!("The history should be a History message");
+        };
+        let contents = contents(&events);
+        assert_eq!(contents.len(), room.history_size);
+        assert_eq!(contents.first(), Some(&"message 5"));
+        assert_eq!(
+            contents.last().copied(),
+            Some(format!("message {}", room.history_size + 4).as_str())
+        );
+    }
+
+    #[test]
+    fn reactions_are_replayed_after_their_message() {
+        let mut room = Room::default();
+        let id = message(&mut room, "hello");
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        room.react(first, id, "+1".to_string(), true)
+            .expect("The message should be in the history");
+        room.react(second, id, "+1".to_string(), true)
+            .expect("The message should be in the history");
+        room.react(second, id, "heart".to_string(), true)
+            .expect("The message should be in the history");
+        room.react(second, id, "heart".to_string(), false)
+            .expect("The message should be in the history");
+
+        let ServerMessage::History { events, .. } = room.history() else {
+            panic!("The history should be a History message");
+        };
+        let [
+            ServerMessage::NewMessage(_),
+            ServerMessage::ReactionsChanged {
+                id: reacted,
+                reactions,
+            },
+        ] = events.as_slice()
+        else {
+            panic!("The reactions should follow their message, got {events:?}");
+        };
+        assert_eq!(*reacted, id);
+        let [reaction] = reactions.as_slice() else {
+            panic!("Only the reaction someone still has should be replayed, got {reactions:?}");
+        };
+        assert_eq!(reaction.get_reaction(), "+1");
+        assert_eq!(reaction.get_users().len(), 2);
+    }
+
+    #[test]
+    fn reactions_are_forgotten_with_their_message() {
+        let mut room = Room::default();
+        let id = message(&mut room, "hello");
+        room.react(Uuid::new_v4(), id, "+1".to_string(), true)
+            .expect("The message should be in the history");
+        for i in 0..room.history_size {
+            message(&mut room, &format!("message {i}"));
+        }
+
+        assert_eq!(
+            room.react(Uuid::new_v4(), id, "+1".to_string(), true)
+                .map(|_| ()),
+            Err(MessageChangeError::NotFound)
+        );
+        let ServerMessage::History { events, .. } = room.history() else {
+            panic!("The history should be a History message");
+        };
+        assert!(
+            !events
+                .iter()
+                .any(|msg| matches!(msg, ServerMessage::ReactionsChanged { .. }))
+        );
+    }
+
+    #[test]
+    fn only_the_author_can_edit_their_message() {
+        let mut room = Room::default();
+        let author = Uuid::new_v4();
+        let message = Message::stamped(author, "hello".to_string());
+        let id = *message
+            .get_id()
+            .expect("A stamped message should have an id");
+        room.record(&ServerMessage::NewMessage(message));
+
+        assert_eq!(
+            room.edit_message(Uuid::new_v4(), id, "hijacked".to_string())
+                .map(|_| ()),
+            Err(MessageChangeError::NotAuthor)
+        );
+        assert_eq!(
+            room.edit_message(author, Uuid::now_v7(), "elsewhere".to_string())
+                .map(|_| ()),
+            Err(MessageChangeError::NotFound)
+        );
+        let edited = room
+            .edit_message(author, id, "hello again".to_string())
+            .expect("The author should be able to edit");
+        assert_eq!(edited.get_content(), "hello again");
+        assert!(edited.get_edited_at().is_some());
+
+        let ServerMessage::History { events, .. } = room.history() else {
+            panic!("The history should be a History message");
+        };
+        assert_eq!(contents(&events), ["hello again"]);
+    }
+
+    #[test]
+    fn only_the_author_can_delete_their_message() {
+        let mut room = Room::default();
+        let author = Uuid::new_v4();
+        let message = Message::stamped(author, "hello".to_string());
+        let id = *message
+            .get_id()
+            .expect("A stamped message should have an id");
+        room.record(&ServerMessage::NewMessage(message));
+        room.react(author, id, "+1".to_string(), true)
+            .expect("The message should be in the history");
+
+        assert_eq!(
+            room.delete_message(Uuid::new_v4(), id),
+            Err(MessageChangeError::NotAuthor)
+        );
+        assert_eq!(
+            room.delete_message(author, Uuid::now_v7()),
+            Err(MessageChangeError::NotFound)
+        );
+        room.delete_message(author, id)
+            .expect("The author should be able to delete");
+
+        let ServerMessage::History { events, .. } = room.history() else {
+            panic!("The history should be a History message");
+        };
+        assert!(events.is_empty());
+        assert_eq!(
+            room.delete_message(author, id),
+            Err(MessageChangeError::NotFound)
+        );
+    }
+
+    #[test]
+    fn owners_can_change_the_filter_unless_the_config_pins_it() {
+        let pinned = FilterPolicy::default().with_mode(chat_lib::filter::FilterMode::Off);
+        let mut config = ServerConfig::default();
+        config
+            .filter
+            .rooms
+            .insert("pinned".to_string(), pinned.clone());
+        let storage: SyncStorage = Arc::new(MemoryStorage::default());
+        let policy = FilterPolicy {
+            deny: vec!["Apple".to_string()],
+            ..FilterPolicy::default()
+        };
+
+        let mut room = Room::load("pinned".to_string(), storage.clone(), &config);
+        assert!(!room.set_filter(policy.clone()));
+        assert_eq!(*room.filter(), pinned);
+
+        let mut room = Room::load("open".to_string(), storage, &config);
+        assert!(room.set_filter(policy));
+        assert_eq!(room.filter().deny, ["apple"]);
+    }
+
+    #[test]
+    fn guests_who_left_are_banned_by_their_address() {
+        let (mut room, id) = room_with_guest();
+        room.remove_user(&id)
+            .expect("The guest should be in the room");
+        assert!(room.is_guest(&id));
+
+        room.sanction(id, &Sanction::Ban(Utc::now() + chrono::TimeDelta::hours(1)));
+
+        assert!(room.moderation().guest_banned_until(&IP).is_some());
+    }
+
+    #[test]
+    fn guests_are_forgotten_with_the_history_about_them() {
+        let (mut room, id) = room_with_guest();
+        room.remove_user(&id)
+            .expect("The guest should be in the room");
+        let other = Uuid::new_v4();
+        for i in 0..room.history_size {
+            room.record(&ServerMessage::NewMessage(Message::stamped(
+                other,
+                format!("message {i}"),
+            )));
+        }
+
+        assert!(!room.is_guest(&id));
+        assert!(room.known_user(&id).is_none());
     }
 }
@@ -1,6 +1,62 @@
+use axum::http::HeaderMap;
+use chat_lib::{
+    Encoding,
+    auth::{RESUME_AFTER_HEADER, RESUME_TOKEN_HEADER, ROOM_INVITE_HEADER, ROOM_PASSWORD_HEADER},
+    types::MessageId,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub struct RoomArgs {
     pub name: Option<String>,
+    /// The session token, for clients that can't send it in the `Authorization` header
+    pub token: Option<String>,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+/// What the client sent to get into a protected room, taken from the headers
+#[derive(Debug, Clone, Default)]
+pub struct RoomCredentials {
+    /// The password of a protected room
+    pub password: Option<String>,
+    /// An invite code made by the owner of a protected room
+    pub invite: Option<String>,
+}
+
+impl RoomCredentials {
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            password: header(headers, ROOM_PASSWORD_HEADER),
+            invite: header(headers, ROOM_INVITE_HEADER),
+        }
+    }
+}
+
+/// What a dropped connection sends to come back as its user, taken from the headers
+#[derive(Debug, Clone)]
+pub struct ResumeArgs {
+    /// The resume token the dropped connection got
+    pub token: String,
+    /// The last message the dropped connection got, the ones after it are replayed
+    pub after: Option<MessageId>,
+}
+
+impl ResumeArgs {
+    /// The resume args, if the connection is resuming a dropped one
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        Some(Self {
+            token: header(headers, RESUME_TOKEN_HEADER)?,
+            after: header(headers, RESUME_AFTER_HEADER).and_then(|id| id.parse().ok()),
+        })
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
 }
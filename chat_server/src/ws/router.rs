@@ -1,18 +1,11 @@
-use std::{collections::HashMap, sync::Arc};
-
 use axum::{Router, routing::get};
-use tokio::sync::Mutex;
 
-use crate::ws::{
-    room::RoomComponents,
-    routes::{about, room_ls, room_ws, root},
+use crate::{
+    AppState,
+    ws::routes::{about, room_ls, room_ws, root},
 };
 
-pub fn paths() -> Router {
-    let rooms = HashMap::<String, Arc<Mutex<RoomComponents>>>::new();
-    let rooms = Arc::new(Mutex::new(rooms));
-    let state = crate::AppState { components: rooms };
-
+pub fn paths(state: AppState) -> Router {
     Router::new()
         .route("/", get(root))
         .route("/about", get(about))
@@ -1,29 +1,49 @@
-use std::future;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, PoisonError},
+};
 
 use axum::{
     Json,
-    extract::{Path, Query, State, WebSocketUpgrade},
-    response::Response,
+    extract::{ConnectInfo, Path, Query, State, WebSocketUpgrade},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use chat_lib::{
+    Discovery, Encoding, Protocol, RoomInfo, Version,
+    filter::FilterPolicy,
+    prelude::*,
+    room::RoomListing,
+    types::Sync,
+    ws_connection::{CloseCode, CloseFrame, Message},
 };
-use chat_lib::{Discovery, Version, prelude::*};
+use futures::SinkExt;
 use names::{Generator, Name};
-use rustrict::{CensorStr, Context};
+use rustrict::CensorStr;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
 use crate::{
     AppState,
+    admin::CLOSE_REASON,
     app_error::AppError,
-    consts::MAX_ROOM_LENGTH,
+    auth::{authenticate, is_name_reserved, password},
+    clients::{ClientGuard, SharedPenalties, admit, client_ip},
+    consts::{MAX_ROOM_LENGTH, SUPPORTED_API_VERSIONS},
+    filter::is_inappropriate,
     limited_string::LimitedString,
+    metrics::{Metrics, PasswordTarget, UpgradeFailure},
     version,
-    ws::{handler::WsHandler, room::RoomComponents, room_args::RoomArgs},
+    ws::{
+        DirectSender, MsgBroadcastSender, Room, SyncRoomComponents,
+        handler::WsHandler,
+        room::RoomComponents,
+        room_args::{ResumeArgs, RoomArgs, RoomCredentials},
+    },
 };
 
 pub fn is_version_supported(version: Version) -> bool {
-    match version {
-        Version::V1 => true,
-        Version::V2 | Version::V3 => false,
-    }
+    SUPPORTED_API_VERSIONS.contains(&version)
 }
 
 /// GET /
@@ -32,32 +52,136 @@ pub async fn root() -> &'static str {
 }
 
 /// GET /about
-pub async fn about(State(AppState { components: rooms }): State<AppState>) -> Json<Discovery> {
-    let rooms = rooms.lock().await.keys().cloned().collect::<Vec<_>>();
+pub async fn about(
+    State(AppState {
+        components: rooms, ..
+    }): State<AppState>,
+) -> Json<Discovery> {
+    let rooms = rooms.lock().await;
+    let mut infos = Vec::with_capacity(rooms.len());
+    for room_components in rooms.values() {
+        let room = room_components.lock().await.room.clone();
+        infos.push(room.lock().await.info().clone());
+    }
+    drop(rooms);
+
     Json(Discovery {
         server_version: version(),
-        available_rooms: rooms,
-        supported_api_versions: vec![Version::V1],
+        available_rooms: infos.iter().map(|info| info.name.clone()).collect(),
+        rooms: infos,
+        supported_api_versions: SUPPORTED_API_VERSIONS.to_vec(),
+        supported_encodings: vec![Encoding::Json, Encoding::MessagePack],
     })
 }
 
 /// GET /{version}/room/{path}/ls
+///
+/// [`Version::V1`] only gets the users, later versions get a [`RoomListing`]
 pub async fn room_ls(
     Path((version, path)): Path<(Version, LimitedString<{ MAX_ROOM_LENGTH }>)>,
-    State(AppState { components: rooms }): State<AppState>,
-) -> Result<Json<Vec<User>>, AppError> {
+    State(state): State<AppState>,
+) -> Result<Response, AppError> {
     if !is_version_supported(version) {
         return Err(AppError::bad_request("Unsupported api version"));
     }
 
-    let rooms = rooms.lock().await;
-    let Some(room_components) = rooms.get(path.as_str()) else {
-        return Ok(Json(Vec::new()));
+    let room = state.components.lock().await.get(path.as_str()).cloned();
+    let listing = if let Some(room_components) = room {
+        let room = room_components.lock().await.room.clone();
+        let room = room.lock().await;
+        RoomListing {
+            info: room.info().clone(),
+            users: room.get_all_users(),
+        }
+    } else {
+        // nobody is in it, but it may have been saved before
+        let storage = state.storage.clone();
+        let name = path.to_string();
+        let info = tokio::task::spawn_blocking(move || storage.load_room(&name))
+            .await
+            .map_err(AppError::server_error)?
+            .inspect_err(|err| log::error!("Couldn't load room {}: {err}", path.as_str()))
+            .ok()
+            .flatten()
+            .and_then(|record| record.info)
+            .unwrap_or_default();
+        RoomListing {
+            info: RoomInfo {
+                name: path.to_string(),
+                ..info
+            },
+            users: Vec::new(),
+        }
     };
-    let room_components = room_components.lock().await;
-    let room = room_components.room.lock().await;
 
-    Ok(Json(room.get_all_users()))
+    Ok(match version {
+        Version::V1 => Json(listing.users).into_response(),
+        _ => Json(listing).into_response(),
+    })
+}
+
+/// The open room, or the room loaded from the storage and opened,
+/// it's loaded without holding the registry's lock, so other rooms aren't held up by the storage
+async fn open_room(state: &AppState, path: &str) -> Result<Sync<RoomComponents>, AppError> {
+    {
+        let components = state.components.lock().await;
+        refuse_closed(state, path)?;
+        if let Some(room_components) = components.get(path) {
+            return Ok(room_components.clone());
+        }
+    }
+
+    let storage = state.storage.clone();
+    let config = state.config.clone();
+    let name = path.to_string();
+    let room = tokio::task::spawn_blocking(move || Room::load(name, storage, &config))
+        .await
+        .map_err(AppError::server_error)?;
+
+    // someone else may have opened it meanwhile, then theirs is kept
+    let mut components = state.components.lock().await;
+    // or the operator closed it meanwhile
+    refuse_closed(state, path)?;
+
+    Ok(components
+        .entry(path.to_string())
+        .or_insert_with(|| RoomComponents::sync(room, &state.config))
+        .clone())
+}
+
+/// Keeps everyone out of the rooms the operator closed,
+/// it's called with the lock on the components held
+fn refuse_closed(state: &AppState, path: &str) -> Result<(), AppError> {
+    let closed = state
+        .closed_rooms
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains(path);
+    if closed {
+        return Err(AppError::forbidden(CLOSE_REASON));
+    }
+
+    Ok(())
+}
+
+/// A new guest, named as they asked if the name is allowed in the room
+async fn guest(state: &AppState, filter: &FilterPolicy, name: Option<String>) -> User {
+    let name = match name {
+        Some(name)
+            if !is_inappropriate(filter, &name)
+                && !is_name_reserved(&state.storage, &name, None).await =>
+        {
+            Some(name)
+        }
+        _ => None,
+    };
+    let name = name.unwrap_or_else(|| {
+        Generator::with_naming(Name::Numbered)
+            .next()
+            .expect("Generator should not fail")
+    });
+
+    User::new(Uuid::new_v4(), name)
 }
 
 /// GET /{version}/room/{path}
@@ -66,6 +190,24 @@ pub async fn room_ws(
     Path((version, path)): Path<(Version, LimitedString<{ MAX_ROOM_LENGTH }>)>,
     State(state): State<AppState>,
     Query(args): Query<RoomArgs>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let metrics = state.metrics.clone();
+    let ip = client_ip(peer.ip(), &headers, &state.config.trusted_proxies);
+    join_room(ws, version, path, state, args, headers, ip)
+        .await
+        .inspect_err(|_| metrics.upgrade_failed(UpgradeFailure::Refused))
+}
+
+async fn join_room(
+    ws: WebSocketUpgrade,
+    version: Version,
+    path: LimitedString<{ MAX_ROOM_LENGTH }>,
+    state: AppState,
+    args: RoomArgs,
+    headers: HeaderMap,
+    ip: IpAddr,
 ) -> Result<Response, AppError> {
     if !is_version_supported(version) {
         return Err(AppError::bad_request("Unsupported api version"));
@@ -76,57 +218,680 @@ pub async fn room_ws(
         return Err(AppError::bad_request("Inappropriate room name"));
     }
 
-    // TODO: make graceful shutdown
-    let sd = future::pending();
-    let rooms = state.components;
-    let room_components = rooms
-        .lock()
-        .await
-        .entry(path.clone())
-        .or_insert(RoomComponents::sync())
-        .clone();
+    if state.shutdown.peek().is_some() {
+        return Err(AppError::unavailable("The server is shutting down"));
+    }
+
+    let (client, penalties) = admit(&state, ip)?;
+
+    let protocol = Protocol::new(version, args.encoding);
+    if let Some(resume) = ResumeArgs::from_headers(&headers) {
+        return resume_ws(ws, state, path, protocol, resume, client).await;
+    }
+
+    let account = authenticate(&headers, args.token.as_deref(), &state.tokens)?;
+    if let Some(account) = &account
+        && is_connected(&state.components, &path, account.get_id()).await
+    {
+        return Err(AppError::bad_request("Already connected to this room"));
+    }
+
+    let room_components = open_room(&state, &path).await?;
 
-    let id = Uuid::new_v4();
     let tx = room_components.lock().await.tx.clone();
     let rx = tx.subscribe();
     let room = room_components.lock().await.room.clone();
 
-    let ws = ws.on_upgrade(move |stream| async move {
-        let name = args.name.filter(|n| !n.is_inappropriate()).unwrap_or(
-            Generator::with_naming(Name::Numbered)
-                .next()
-                .expect("Generator should not fail"),
-        );
+    let registered = account.is_some();
+    let new_user = if let Some(account) = account {
+        account
+    } else {
+        let filter = room.lock().await.filter();
+        guest(&state, &filter, args.name.clone()).await
+    };
+    let id = *new_user.get_id();
+    let credentials = RoomCredentials::from_headers(&headers);
+    let admitted = check_entry(
+        &state,
+        &room,
+        &id,
+        &ip,
+        registered,
+        &credentials,
+        &penalties,
+    )
+    .await;
+    if let Err(err) = admitted {
+        remove_if_empty(&state, &path, &room).await;
+        return Err(err);
+    }
+
+    let (direct_tx, direct_rx) = mpsc::unbounded_channel();
+
+    let connections = state.connections.clone();
+    let ws = ws
+        .on_failed_upgrade(count_failed_upgrade(state.metrics.clone()))
+        .on_upgrade(move |stream| {
+            connections.track_future(async move {
+                let mut sd = state.shutdown.clone();
+                let token = resume_token(&state, protocol.version);
+                let guest_ip = (!registered).then_some(ip);
+                let entered = enter_registered(&state, &path, &room_components, |room| {
+                    enter_room(
+                        room,
+                        new_user,
+                        guest_ip,
+                        direct_tx,
+                        protocol.version,
+                        &tx,
+                        token.clone(),
+                    )
+                })
+                .await;
+                let Some(history) = entered else {
+                    let mut stream = WsConnection::from(stream);
+                    let frame = CloseFrame {
+                        code: CloseCode::Again,
+                        reason: "The room was replaced while joining it".into(),
+                    };
+                    let _ = stream.send(Message::Close(Some(frame))).await;
+                    return;
+                };
+
+                // keeps the connection counted against the ip until it ends
+                let _client = client;
+                let mut loop_ctx = WsHandler::new(
+                    stream.into(),
+                    &state,
+                    penalties,
+                    id,
+                    protocol,
+                    rx,
+                    tx.clone(),
+                    direct_rx,
+                    room.clone(),
+                    &mut sd,
+                );
+                loop_ctx.queue_history(history);
+
+                run_connection(loop_ctx, &state, &path, room, tx, token).await;
+            })
+        });
+
+    Ok(ws)
+}
+
+/// Enters the room while holding the components, so it can't be found empty and removed meanwhile,
+/// if it was removed while the connection was upgraded it's put back,
+/// `None` if another room took its name since
+async fn enter_registered<T>(
+    state: &AppState,
+    path: &str,
+    room_components: &Sync<RoomComponents>,
+    enter: impl FnOnce(&mut Room) -> T,
+) -> Option<T> {
+    let mut components = state.components.lock().await;
+    // a closed room stays out, entering it closes the connection
+    let current = if refuse_closed(state, path).is_ok() {
+        components
+            .entry(path.to_string())
+            .or_insert_with(|| room_components.clone())
+            .clone()
+    } else {
+        room_components.clone()
+    };
+    if !Arc::ptr_eq(&current, room_components) {
+        return None;
+    }
+    let room = room_components.lock().await.room.clone();
+    let entered = enter(&mut *room.lock().await);
+
+    Some(entered)
+}
+
+/// Adds the user and their connection to the room and announces them,
+/// returns the history to replay to the new connection
+///
+/// `guest_ip` is where the user joined from if they're a guest
+fn enter_room(
+    room: &mut Room,
+    user: User,
+    guest_ip: Option<IpAddr>,
+    direct_tx: DirectSender,
+    version: Version,
+    tx: &MsgBroadcastSender,
+    token: Option<ServerMessage>,
+) -> ServerMessage {
+    let id = *user.get_id();
+    // taken before joining, so the new connection doesn't get its own join replayed
+    let history = room.history();
+    // the account joined again before its dropped connection resumed
+    room.cancel_suspension(&id);
+    if let Some(ip) = guest_ip {
+        room.add_guest(id, ip);
+    } else {
+        // rooms created by registered users are kept when everyone leaves
+        if room.is_new() {
+            room.make_persistent();
+        }
+        room.claim(id);
+    }
+    room.add_user(user.clone());
+    room.add_connection(id, direct_tx, version);
+    welcome(room, &id);
+    // closed while the connection was upgraded, it leaves as soon as it starts
+    if room.is_closed() {
+        let _ = room.close_connection(&id, CLOSE_REASON);
+    }
+    let msg = ServerMessage::UserJoined(user);
+    room.record(&msg);
+    let _ = tx.send(msg);
+    if let Some(msg) = token {
+        let _ = room.send_direct(&id, msg);
+    }
+
+    history
+}
+
+/// Lets the user in if they aren't banned and have what the room needs,
+/// a wrong password counts as a failed entry
+async fn check_entry(
+    state: &AppState,
+    room: &Sync<Room>,
+    id: &Uuid,
+    ip: &IpAddr,
+    registered: bool,
+    credentials: &RoomCredentials,
+    penalties: &SharedPenalties,
+) -> Result<(), AppError> {
+    {
+        let room = room.lock().await;
+        // closed after it was opened for this join
+        if room.is_closed() {
+            return Err(AppError::forbidden(CLOSE_REASON));
+        }
+        check_ban(&room, id, ip, registered)?;
+    }
+
+    check_access(room, id, credentials).await.inspect_err(|_| {
+        if credentials.password.is_some() {
+            state.metrics.wrong_password(PasswordTarget::Room);
+            penalties.lock().fail_entry(&state.config.limits);
+        }
+    })
+}
 
-        let mut sd = sd.clone();
-        let new_user = User::new(id, name);
+/// Keeps banned users out, a guest's id is new so it's their address that's banned
+fn check_ban(room: &Room, id: &Uuid, ip: &IpAddr, registered: bool) -> Result<(), AppError> {
+    let moderation = room.moderation();
+    let banned_until = if registered {
+        moderation.banned_until(id)
+    } else {
+        moderation.guest_banned_until(ip)
+    };
+
+    match banned_until {
+        Some(until) => Err(AppError::forbidden(format!(
+            "Banned from this room until {until}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Lets the user into a protected room with its password or an invite,
+/// moderators can always join
+async fn check_access(
+    room: &Sync<Room>,
+    id: &Uuid,
+    credentials: &RoomCredentials,
+) -> Result<(), AppError> {
+    let hash = {
+        let room = room.lock().await;
+        let access = room.access();
+        if access.access().is_open()
+            || room.moderation().is_moderator(id)
+            || credentials
+                .invite
+                .as_deref()
+                .is_some_and(|code| access.is_invited(code))
         {
-            room.lock().await.add_user(new_user.clone());
+            return Ok(());
+        }
+
+        access.password_hash().map(str::to_string)
+    };
+
+    match (hash, credentials.password.clone()) {
+        (Some(hash), Some(password)) => {
+            let matches = tokio::task::spawn_blocking(move || password::verify(&password, &hash))
+                .await
+                .map_err(AppError::server_error)?;
+            if matches {
+                Ok(())
+            } else {
+                Err(AppError::forbidden("Wrong room password"))
+            }
         }
-        let _ = tx.send(ServerMessage::UserJoined(new_user.clone()));
+        (Some(_), None) if credentials.invite.is_none() => Err(AppError::unauthorized(
+            "This room needs a password or an invite code",
+        )),
+        (None, _) if credentials.invite.is_none() => {
+            Err(AppError::unauthorized("This room needs an invite code"))
+        }
+        _ => Err(AppError::forbidden("The invite code is invalid or expired")),
+    }
+}
+
+/// Tells the user who just joined how the room is run
+fn welcome(room: &Room, id: &Uuid) {
+    let _ = room.send_direct(id, room.moderation().moderators());
+    let _ = room.send_direct(id, ServerMessage::FilterPolicy((*room.filter()).clone()));
+    let _ = room.send_direct(id, ServerMessage::RoomInfo(room.info().clone()));
+}
+
+/// Upgrades a connection that comes back as the user of a dropped one
+async fn resume_ws(
+    ws: WebSocketUpgrade,
+    state: AppState,
+    path: String,
+    protocol: Protocol,
+    resume: ResumeArgs,
+    client: ClientGuard,
+) -> Result<Response, AppError> {
+    let ResumeArgs { token, after } = resume;
+    let expired = || AppError::bad_request("The session expired or doesn't exist");
+
+    let room_components = state
+        .components
+        .lock()
+        .await
+        .get(&path)
+        .cloned()
+        .ok_or_else(expired)?;
+    let tx = room_components.lock().await.tx.clone();
+    let room = room_components.lock().await.room.clone();
+    if !room.lock().await.can_resume(&token) {
+        return Err(expired());
+    }
 
-        let ctx = Context::new();
-        let mut loop_ctx = WsHandler::new(stream.into(), ctx, id, rx, tx, room.clone(), &mut sd);
+    let connections = state.connections.clone();
+    let ws = ws
+        .on_failed_upgrade(count_failed_upgrade(state.metrics.clone()))
+        .on_upgrade(move |stream| {
+            connections.track_future(async move {
+                let mut sd = state.shutdown.clone();
+                let new_token = resume_token(&state, protocol.version);
+                // subscribes under the lock, so no message falls between the replay and the live ones
+                let resumed = {
+                    let mut room = room.lock().await;
+                    room.resume(&token).map(|suspended| {
+                        room.set_version(suspended.id, protocol.version);
+                        if let Some(msg) = new_token.clone() {
+                            let _ = room.send_direct(&suspended.id, msg);
+                        }
+                        (suspended, tx.subscribe(), room.missed_since(after))
+                    })
+                };
 
-        loop {
-            let should_quit = match loop_ctx.ws_step().await {
-                Ok(quit) => quit,
-                Err(err) => {
-                    log::warn!("Couldn't send a message: {err}");
-                    let _ = loop_ctx.close_socket().await;
-                    true
+                // it expired while upgrading
+                let Some((suspended, rx, missed)) = resumed else {
+                    let mut stream = WsConnection::from(stream);
+                    let frame = CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "The session expired".into(),
+                    };
+                    let _ = stream.send(Message::Close(Some(frame))).await;
+                    return;
+                };
+
+                log::info!("User {} resumed their session", suspended.id);
+                let _client = client;
+                // the penalties stay with the session, even if it resumes from another ip
+                let mut loop_ctx = WsHandler::new(
+                    stream.into(),
+                    &state,
+                    suspended.penalties,
+                    suspended.id,
+                    protocol,
+                    rx,
+                    tx.clone(),
+                    suspended.direct_rx,
+                    room.clone(),
+                    &mut sd,
+                );
+                // a failed replay drops the connection again in the first step
+                if let Err(err) = loop_ctx.replay(missed).await {
+                    log::warn!("Couldn't replay the missed messages: {err}");
                 }
-            };
 
-            if should_quit {
-                break;
+                run_connection(loop_ctx, &state, &path, room, tx, new_token).await;
+            })
+        });
+
+    Ok(ws)
+}
+
+/// Counts the failed websocket handshakes
+fn count_failed_upgrade(metrics: Arc<Metrics>) -> impl FnOnce(axum::Error) + Send + 'static {
+    move |err| {
+        log::warn!("Websocket upgrade failed: {err}");
+        metrics.upgrade_failed(UpgradeFailure::Handshake);
+    }
+}
+
+/// A fresh resume token, if resuming is enabled and the connection's version knows about it
+fn resume_token(state: &AppState, version: Version) -> Option<ServerMessage> {
+    let grace = state.config.limits.resume_grace_secs;
+    (grace > 0 && version != Version::V1).then(|| ServerMessage::ResumeToken {
+        token: Uuid::new_v4().simple().to_string(),
+        grace,
+    })
+}
+
+/// Runs the connection until it ends, then the user either leaves the room,
+/// or if the connection dropped, stays in it until the connection resumes or the grace period ends
+async fn run_connection<F>(
+    mut loop_ctx: WsHandler<'_, F>,
+    state: &AppState,
+    path: &str,
+    room: Sync<Room>,
+    tx: MsgBroadcastSender,
+    token: Option<ServerMessage>,
+) where
+    F: Future<Output = ()> + Clone,
+{
+    state.metrics.connections.inc();
+    let dropped = loop {
+        match loop_ctx.ws_step().await {
+            Ok(false) => {}
+            Ok(true) => break false,
+            Err(err) => {
+                log::warn!("Connection dropped: {err}");
+                break true;
             }
         }
+    };
+    state.metrics.connections.dec();
 
-        if room.lock().await.is_empty() {
-            rooms.lock().await.remove_entry(&path);
+    if loop_ctx.in_room() {
+        if dropped && let Some(ServerMessage::ResumeToken { token, .. }) = token {
+            let suspended = loop_ctx.suspend();
+            room.lock().await.suspend(token.clone(), suspended);
+            state.connections.spawn(expire_session(
+                state.clone(),
+                path.to_string(),
+                room,
+                tx,
+                token,
+            ));
+            return;
         }
-    });
+        let _ = loop_ctx.close_socket().await;
+    }
 
-    Ok(ws)
+    remove_if_empty(state, path, &room).await;
+}
+
+/// Removes the user of the suspended connection after the grace period, unless it resumed
+async fn expire_session(
+    state: AppState,
+    path: String,
+    room: Sync<Room>,
+    tx: MsgBroadcastSender,
+    token: String,
+) {
+    tokio::select! {
+        () = tokio::time::sleep(state.config.limits.resume_grace()) => {}
+        () = state.shutdown.clone() => {}
+    }
+
+    {
+        let mut room = room.lock().await;
+        if let Some(user) = room.expire(&token) {
+            log::info!("The session of user {} expired", user.get_id());
+            room.remove_user(user.get_id());
+            let msg = ServerMessage::UserLeft(user);
+            room.record(&msg);
+            let _ = tx.send(msg);
+        }
+    }
+
+    remove_if_empty(&state, &path, &room).await;
+}
+
+/// Removes the room once everyone left, unless it's persistent,
+/// whatever was saved of it is forgotten so it starts over when it's joined again
+pub(crate) async fn remove_if_empty(state: &AppState, path: &str, room: &Sync<Room>) {
+    {
+        // a join finds the room through the components, so holding them keeps it from
+        // getting in after the room is found empty
+        let mut components = state.components.lock().await;
+        let Some(room_components) = components.get(path) else {
+            return;
+        };
+        // the room may already have been replaced by a new one under the same name
+        if !Arc::ptr_eq(&room_components.lock().await.room, room) {
+            return;
+        }
+        let room = room.lock().await;
+        if !room.is_empty() || room.is_persistent() {
+            return;
+        }
+        drop(room);
+        components.remove(path);
+    }
+
+    let storage = state.storage.clone();
+    let name = path.to_string();
+    match tokio::task::spawn_blocking(move || storage.remove_room(&name)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => log::error!("Couldn't remove room {path} from the storage: {err}"),
+        Err(err) => log::error!("Removing room {path} from the storage panicked: {err}"),
+    }
+}
+
+/// Whether the user has a live connection in the room
+async fn is_connected(rooms: &SyncRoomComponents, path: &str, id: &Uuid) -> bool {
+    let Some(room_components) = rooms.lock().await.get(path).cloned() else {
+        return false;
+    };
+    let room = room_components.lock().await.room.clone();
+    let room = room.lock().await;
+
+    room.has_user(id) && !room.is_suspended(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::{
+        admin::{Reached, close_room},
+        config::ServerConfig,
+        storage::MemoryStorage,
+        ws::access::Lock,
+    };
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    #[tokio::test]
+    async fn closed_rooms_refuse_to_be_joined_again() {
+        let state = AppState::test();
+        let room_components = open_room(&state, "lobby")
+            .await
+            .expect("The room should open");
+        let room = room_components.lock().await.room.clone();
+
+        let Json(Reached { count }) = close_room(State(state.clone()), Path("lobby".to_string()))
+            .await
+            .expect("The room should close");
+        assert_eq!(count, 0);
+
+        assert!(open_room(&state, "lobby").await.is_err());
+        assert!(!state.components.lock().await.contains_key("lobby"));
+
+        // a join that opened the room before it was closed
+        let (_client, penalties) = admit(&state, IP).expect("The ip should be admitted");
+        let entry = check_entry(
+            &state,
+            &room,
+            &Uuid::new_v4(),
+            &IP,
+            false,
+            &RoomCredentials::default(),
+            &penalties,
+        )
+        .await;
+        assert!(entry.is_err());
+    }
+
+    #[tokio::test]
+    async fn closed_persistent_rooms_are_forgotten() {
+        let state = AppState::test();
+        let room_components = open_room(&state, "lobby")
+            .await
+            .expect("The room should open");
+        let room = room_components.lock().await.room.clone();
+        let save = {
+            let mut room = room.lock().await;
+            room.make_persistent();
+            room.take_changes().expect("The room should have changed")
+        };
+        save.write().await;
+        let stored = state
+            .storage
+            .load_room("lobby")
+            .expect("The storage should be readable");
+        assert!(stored.is_some());
+
+        let Json(Reached { count }) = close_room(State(state.clone()), Path("lobby".to_string()))
+            .await
+            .expect("The room should close");
+        assert_eq!(count, 0);
+
+        let stored = state
+            .storage
+            .load_room("lobby")
+            .expect("The storage should be readable");
+        assert!(stored.is_none());
+        assert!(open_room(&state, "lobby").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rooms_emptied_during_a_join_are_put_back() {
+        let state = AppState::test();
+        let room_components = open_room(&state, "lobby")
+            .await
+            .expect("The room should open");
+        let room = room_components.lock().await.room.clone();
+
+        // the last user left while the joining connection was upgraded
+        remove_if_empty(&state, "lobby", &room).await;
+        assert!(!state.components.lock().await.contains_key("lobby"));
+
+        let user = User::new(Uuid::new_v4(), "user".to_string());
+        let entered = enter_registered(&state, "lobby", &room_components, |room| {
+            room.add_user(user);
+        })
+        .await;
+        assert!(entered.is_some());
+
+        remove_if_empty(&state, "lobby", &room).await;
+        let components = state.components.lock().await;
+        let registered = components.get("lobby").expect("The room should be kept");
+        assert!(Arc::ptr_eq(registered, &room_components));
+    }
+
+    #[tokio::test]
+    async fn replaced_rooms_arent_removed_or_entered() {
+        let state = AppState::test();
+        let old = open_room(&state, "lobby")
+            .await
+            .expect("The room should open");
+        let old_room = old.lock().await.room.clone();
+        remove_if_empty(&state, "lobby", &old_room).await;
+        let new = open_room(&state, "lobby")
+            .await
+            .expect("The room should open again");
+
+        remove_if_empty(&state, "lobby", &old_room).await;
+        assert!(state.components.lock().await.contains_key("lobby"));
+
+        let entered = enter_registered(&state, "lobby", &old, |_| ()).await;
+        assert!(entered.is_none());
+        let components = state.components.lock().await;
+        let registered = components
+            .get("lobby")
+            .expect("The new room should be kept");
+        assert!(Arc::ptr_eq(registered, &new));
+    }
+
+    fn credentials(password: Option<&str>, invite: Option<&str>) -> RoomCredentials {
+        RoomCredentials {
+            password: password.map(ToString::to_string),
+            invite: invite.map(ToString::to_string),
+        }
+    }
+
+    /// A room owned by the returned user, locked with `lock`, and an invite to it
+    fn locked_room(lock: Lock) -> (Sync<Room>, Uuid, String) {
+        let owner = Uuid::new_v4();
+        let storage = Arc::new(MemoryStorage::default());
+        let mut room = Room::load("locked".to_string(), storage, &ServerConfig::default());
+        room.claim(owner);
+        let _ = room.set_lock(lock);
+        let ServerMessage::InviteCreated { code, .. } = room.invite(60) else {
+            panic!("The invite should be created");
+        };
+
+        (Arc::new(tokio::sync::Mutex::new(room)), owner, code)
+    }
+
+    #[tokio::test]
+    async fn password_protected_rooms_need_the_password_or_an_invite() {
+        let hash = password::hash("secret").expect("The password should hash");
+        let (room, owner, code) = locked_room(Lock::Password(hash));
+        let stranger = Uuid::new_v4();
+
+        let access = |password, invite| {
+            let room = room.clone();
+            async move { check_access(&room, &stranger, &credentials(password, invite)).await }
+        };
+        assert!(access(Some("secret"), None).await.is_ok());
+        assert!(access(None, Some(&code)).await.is_ok());
+        assert!(matches!(
+            access(Some("wrong"), None).await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            access(None, Some("made up")).await,
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            access(None, None).await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        let owner_access = check_access(&room, &owner, &RoomCredentials::default()).await;
+        assert!(owner_access.is_ok());
+    }
+
+    #[tokio::test]
+    async fn invite_only_rooms_need_an_invite() {
+        let (room, _, code) = locked_room(Lock::InviteOnly);
+        let stranger = Uuid::new_v4();
+
+        let with_invite = check_access(&room, &stranger, &credentials(None, Some(&code))).await;
+        assert!(with_invite.is_ok());
+        let with_password =
+            check_access(&room, &stranger, &credentials(Some("secret"), None)).await;
+        assert!(matches!(with_password, Err(AppError::Unauthorized(_))));
+
+        // opening the room revokes its invites, they're not needed anymore
+        let _ = room.lock().await.set_lock(Lock::Open);
+        assert!(!room.lock().await.access().is_invited(&code));
+        let open = check_access(&room, &stranger, &RoomCredentials::default()).await;
+        assert!(open.is_ok());
+    }
 }